@@ -0,0 +1,257 @@
+use crate::charset::{self, Charset, ENGLISH};
+use crate::Keymui;
+use color_eyre::eyre::{anyhow, Context, ContextCompat, Result};
+use kc::Corpus;
+use std::fs;
+
+/// What each corpus of a blend adds up to, split by weight. Blends count
+/// fractions of n-grams, so counts are scaled up before rounding.
+const BLEND_TOTAL: f64 = 1e9;
+
+/// Parses `english:70 rust:30` into corpus names and weights normalized to
+/// sum to one.
+pub fn parse_weights(s: &str) -> Result<Vec<(String, f64)>> {
+    let mut weights = vec![];
+    for part in s.split_whitespace() {
+        let (name, weight) = part
+            .rsplit_once(':')
+            .with_context(|| format!("expected <corpus>:<weight>, got {}", part))?;
+        let weight: f64 = weight
+            .trim_end_matches('%')
+            .parse()
+            .with_context(|| format!("invalid weight {}", weight))?;
+        if weight < 0.0 {
+            return Err(anyhow!("weights can't be negative"));
+        }
+        weights.push((name.to_string(), weight));
+    }
+    let sum: f64 = weights.iter().map(|(_, w)| w).sum();
+    if weights.len() < 2 || sum == 0.0 {
+        return Err(anyhow!("a blend needs at least two corpora with weights"));
+    }
+    Ok(weights.into_iter().map(|(n, w)| (n, w / sum)).collect())
+}
+
+/// The characters of a corpus by index, each with the shifted characters
+/// that count towards the same index. These come from the charset the
+/// corpus was built with, if it was saved, and are guessed otherwise.
+fn char_groups(corpus: &Corpus, charset: Option<&Charset>) -> Vec<(usize, Vec<char>)> {
+    if let Some(charset) = charset {
+        return charset
+            .groups
+            .iter()
+            .filter(|g| !g.is_empty())
+            .map(|g| (corpus.corpus_char(g[0]), g))
+            .filter(|(i, g)| corpus.uncorpus_unigram(*i) == g[0])
+            .map(|(i, g)| {
+                let chars = g.iter().copied().filter(|c| corpus.corpus_char(*c) == i);
+                (i, dedup_chars(chars))
+            })
+            .collect();
+    }
+    corpus
+        .chars
+        .iter()
+        .enumerate()
+        .map(|(i, _)| (i, corpus.uncorpus_unigram(i)))
+        .filter(|(_, c)| *c != '\0')
+        .map(|(i, c)| {
+            let shifted = c
+                .to_uppercase()
+                .chain(
+                    ENGLISH
+                        .iter()
+                        .filter(|g| g.starts_with(c))
+                        .flat_map(|g| g.chars().skip(1)),
+                )
+                .filter(|s| *s != c && corpus.corpus_char(*s) == i);
+            (i, dedup_chars(Some(c).into_iter().chain(shifted)))
+        })
+        .collect()
+}
+
+fn dedup_chars(chars: impl Iterator<Item = char>) -> Vec<char> {
+    let mut out: Vec<char> = vec![];
+    for c in chars {
+        if !out.contains(&c) {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Adds `source`'s n-grams to `target`, scaled so that each kind of n-gram
+/// sums to `share` of [`BLEND_TOTAL`]. `index` gives the target index of
+/// each source index.
+fn add_scaled(
+    target: &mut [f64],
+    source: &[u32],
+    share: f64,
+    mut index: impl FnMut(usize) -> usize,
+) {
+    let total: f64 = source.iter().map(|c| *c as f64).sum();
+    if total == 0.0 {
+        return;
+    }
+    for (i, count) in source.iter().enumerate() {
+        if *count > 0 {
+            target[index(i)] += share * BLEND_TOTAL * *count as f64 / total;
+        }
+    }
+}
+
+impl Keymui {
+    /// Reads a corpus along with its saved charset, if it has one.
+    fn read_corpus_named(&self, name: &str) -> Result<(Corpus, Option<Charset>)> {
+        let path = self
+            .corpora
+            .get(name)
+            .with_context(|| format!("corpus {} doesn't exist", name))?;
+        let b = fs::read(path).context("couldn't read corpus file")?;
+        let corpus = rmp_serde::from_slice(&b).context("couldn't deserialize corpus")?;
+        let charset_path = charset::beside(path);
+        let charset = match charset_path.exists() {
+            true => Some(Charset::read(&charset_path)?),
+            false => None,
+        };
+        Ok((corpus, charset))
+    }
+
+    /// Combines corpora into a new one in which each contributes its weight,
+    /// regardless of its size. Corpora with different charsets are merged
+    /// by character.
+    pub fn blend_corpora(&mut self, name: &str, weights: &[(String, f64)]) -> Result<()> {
+        let sources = weights
+            .iter()
+            .map(|(n, w)| {
+                let (corpus, charset) = self.read_corpus_named(n)?;
+                let groups = char_groups(&corpus, charset.as_ref());
+                Ok((corpus, groups, *w))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut groups: Vec<Vec<char>> = vec![];
+        for (_, source_groups, _) in &sources {
+            for (_, group) in source_groups {
+                if !groups.iter().any(|g| g[0] == group[0]) {
+                    groups.push(group.clone());
+                }
+            }
+        }
+        let charset = Charset { groups };
+        let mut blend = Corpus::with_char_list(charset.groups.clone());
+
+        let mut chars = vec![0.0; blend.chars.len()];
+        let mut bigrams = vec![0.0; blend.bigrams.len()];
+        let mut skipgrams = vec![0.0; blend.skipgrams.len()];
+        let mut trigrams = vec![0.0; blend.trigrams.len()];
+        for (corpus, source_groups, share) in &sources {
+            // index of every source character in the blend
+            let mut map = vec![0; corpus.chars.len()];
+            for (i, group) in source_groups {
+                map[*i] = blend.corpus_char(group[0]);
+            }
+            let used: Vec<usize> = (0..corpus.chars.len())
+                .filter(|i| corpus.chars[*i] > 0)
+                .collect();
+
+            add_scaled(&mut chars, &corpus.chars, *share, |i| map[i]);
+            let mut bigram_map = vec![0; corpus.bigrams.len()];
+            let mut trigram_map = vec![0; corpus.trigrams.len()];
+            for a in &used {
+                for b in &used {
+                    bigram_map[corpus.bigram_idx(*a, *b)] = blend.bigram_idx(map[*a], map[*b]);
+                    for c in &used {
+                        trigram_map[corpus.trigram_idx(*a, *b, *c)] =
+                            blend.trigram_idx(map[*a], map[*b], map[*c]);
+                    }
+                }
+            }
+            add_scaled(&mut bigrams, &corpus.bigrams, *share, |i| bigram_map[i]);
+            add_scaled(&mut skipgrams, &corpus.skipgrams, *share, |i| bigram_map[i]);
+            add_scaled(&mut trigrams, &corpus.trigrams, *share, |i| trigram_map[i]);
+        }
+
+        let round = |counts: Vec<f64>| -> Vec<u32> {
+            counts.into_iter().map(|c| c.round() as u32).collect()
+        };
+        blend.chars = round(chars);
+        blend.bigrams = round(bigrams);
+        blend.skipgrams = round(skipgrams);
+        blend.trigrams = round(trigrams);
+
+        let path = self.corpora_dir().join(format!("{}.corpus", name));
+        fs::write(&path, rmp_serde::to_vec(&blend)?)
+            .with_context(|| format!("couldn't write corpus to {}", &path.display()))?;
+        charset.write(&charset::beside(&path))?;
+        self.set_corpus_list()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_charsets_keep_their_groups() {
+        let charset = Charset {
+            groups: vec![vec!['a', 'A'], vec!['ä', 'Ä'], vec![',', ';']],
+        };
+        let corpus = Corpus::with_char_list(charset.groups.clone());
+        let groups: Vec<Vec<char>> = char_groups(&corpus, Some(&charset))
+            .into_iter()
+            .map(|(_, g)| g)
+            .collect();
+        assert_eq!(groups, [vec!['a', 'A'], vec!['ä', 'Ä'], vec![',', ';']]);
+        // without the charset, `;` can't be told apart from a separate key
+        assert!(char_groups(&corpus, None).iter().any(|(_, g)| g == &[',']));
+    }
+
+    #[test]
+    fn weights_are_normalized() {
+        let weights = parse_weights("english:70 rust:30%").unwrap();
+        assert_eq!(weights.len(), 2);
+        assert_eq!(weights[0].0, "english");
+        assert!((weights[0].1 - 0.7).abs() < 1e-9);
+        assert_eq!(weights[1].0, "rust");
+        assert!((weights[1].1 - 0.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn corpus_names_may_contain_colons() {
+        let weights = parse_weights("a:b:1 c:1").unwrap();
+        assert_eq!(weights[0].0, "a:b");
+    }
+
+    #[test]
+    fn invalid_weights_are_rejected() {
+        assert!(parse_weights("").is_err());
+        assert!(parse_weights("english:1").is_err());
+        assert!(parse_weights("english rust:1").is_err());
+        assert!(parse_weights("english:x rust:1").is_err());
+        assert!(parse_weights("english:-1 rust:2").is_err());
+        assert!(parse_weights("english:0 rust:0").is_err());
+    }
+
+    #[test]
+    fn sources_add_up_to_their_share() {
+        let mut target = vec![0.0; 3];
+        // a small and a large source end up with the same weight
+        add_scaled(&mut target, &[1, 3], 0.5, |i| i);
+        add_scaled(&mut target, &[0, 0, 400], 0.5, |i| i);
+        assert_eq!(
+            target,
+            [0.125 * BLEND_TOTAL, 0.375 * BLEND_TOTAL, 0.5 * BLEND_TOTAL]
+        );
+    }
+
+    #[test]
+    fn sources_are_mapped_to_target_indexes() {
+        let mut target = vec![0.0; 2];
+        add_scaled(&mut target, &[1, 1], 1.0, |i| 1 - i);
+        add_scaled(&mut target, &[0, 0], 1.0, |_| {
+            panic!("empty sources add nothing")
+        });
+        assert_eq!(target, [0.5 * BLEND_TOTAL, 0.5 * BLEND_TOTAL]);
+    }
+}
@@ -0,0 +1,300 @@
+use crate::layout_display::{ColorStyle, DisplayKeys, LayoutDisplay};
+use crate::{Keymui, Message};
+use color_eyre::eyre::{Context, ContextCompat, Result};
+use core::fmt;
+use iced::futures::channel::mpsc::{self, UnboundedSender};
+use iced::futures::{future, SinkExt, StreamExt};
+use iced::{subscription, Subscription};
+use kc::Corpus;
+use km::{MetricContext, MetricData};
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::{fs, iter};
+
+/// How many layouts the browser lists at once, since every row draws a
+/// thumbnail.
+pub const MAX_SHOWN: usize = 50;
+
+/// How many metrics a row shows next to the score.
+const QUICK_STATS: usize = 4;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum KeyboardFilter {
+    #[default]
+    All,
+    Board(String),
+}
+
+impl fmt::Display for KeyboardFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyboardFilter::All => write!(f, "all keyboards"),
+            KeyboardFilter::Board(board) => write!(f, "{}", board),
+        }
+    }
+}
+
+/// Quick stats of a layout against the selected metrics and corpus, with
+/// the keys of its thumbnail.
+#[derive(Debug, Clone)]
+pub struct Summary {
+    /// Percentage of each metric, in metric order.
+    pub percentages: Vec<f32>,
+    pub keys: DisplayKeys,
+}
+
+pub enum Analysis {
+    Done {
+        percentages: Vec<f32>,
+        thumbnail: LayoutDisplay,
+    },
+    Failed(String),
+}
+
+#[derive(Default)]
+pub struct Browser {
+    pub search: String,
+    pub keyboard: KeyboardFilter,
+    /// Bumped whenever the analyses are outdated, which restarts the
+    /// background analysis.
+    generation: usize,
+    analyses: HashMap<String, Analysis>,
+}
+
+impl Browser {
+    pub fn invalidate(&mut self) {
+        self.generation += 1;
+        self.analyses.clear();
+    }
+
+    pub fn analysis(&self, name: &str) -> Option<&Analysis> {
+        self.analyses.get(name)
+    }
+
+    /// Whether work started for `generation` is still wanted.
+    pub fn is_current(&self, generation: usize) -> bool {
+        generation == self.generation
+    }
+
+    /// Stores a finished analysis, unless it was started for outdated
+    /// metrics, corpus or layouts.
+    pub fn summarized(
+        &mut self,
+        generation: usize,
+        name: String,
+        summary: Result<Summary, String>,
+    ) {
+        if !self.is_current(generation) {
+            return;
+        }
+        let analysis = match summary {
+            Ok(summary) => Analysis::Done {
+                percentages: summary.percentages,
+                thumbnail: LayoutDisplay::thumbnail(summary.keys),
+            },
+            Err(e) => Analysis::Failed(e),
+        };
+        self.analyses.insert(name, analysis);
+    }
+}
+
+/// Scores how well `query` matches `candidate` as a case-insensitive
+/// subsequence, or `None` if it doesn't. Consecutive characters and
+/// characters at the start of a word score higher, gaps lower. Whitespace
+/// in the query is ignored, so an empty query matches everything equally.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut last: Option<usize> = None;
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = pos + candidate[pos..].iter().position(|c| *c == q)?;
+        score += 1;
+        if last.is_some_and(|l| l + 1 == found) {
+            score += 5;
+        } else if last.is_some() {
+            score -= (found - pos).min(3) as i32;
+        }
+        if found == 0 || !candidate[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        last = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+/// What the background analysis of the browser's layouts works on. Everything
+/// is read from the files again, so nothing has to be shared with the UI.
+pub struct Job {
+    generation: usize,
+    metrics: PathBuf,
+    corpus: PathBuf,
+    layouts: Vec<(String, PathBuf)>,
+}
+
+impl Job {
+    /// Reads and deserializes the metrics and corpus once for all layouts.
+    fn read_data(&self) -> Result<(MetricData, Corpus)> {
+        let metrics = fs::read(&self.metrics)
+            .with_context(|| format!("couldn't read metrics {}", self.metrics.display()))?;
+        let metrics = rmp_serde::from_slice(&metrics).context("couldn't deserialize metrics")?;
+        let corpus = fs::read(&self.corpus)
+            .with_context(|| format!("couldn't read corpus {}", self.corpus.display()))?;
+        let corpus = rmp_serde::from_slice(&corpus).context("couldn't deserialize corpus")?;
+        Ok((metrics, corpus))
+    }
+
+    fn run(self, sender: &UnboundedSender<Message>) {
+        let (metrics, corpus) = match self.read_data() {
+            Ok(data) => data,
+            Err(e) => {
+                let error = format!("{:#}", e);
+                let _ = sender.unbounded_send(Message::BrowserFailed(self.generation, error));
+                return;
+            }
+        };
+        for (name, path) in self.layouts {
+            let summary =
+                summarize(&path, metrics.clone(), corpus.clone()).map_err(|e| format!("{:#}", e));
+            let message = Message::LayoutSummarized(self.generation, name, summary);
+            if sender.unbounded_send(message).is_err() {
+                // the analysis was restarted or the browser closed
+                return;
+            }
+        }
+    }
+}
+
+fn summarize(path: &Path, metrics: MetricData, corpus: Corpus) -> Result<Summary> {
+    let layout = Keymui::read_layout(path)?;
+    let ctx = MetricContext::new(&layout, metrics, corpus)
+        .context("couldn't create metric context for layout")?;
+    let mut stats = vec![0.0; ctx.analyzer.data.metrics.len()];
+    ctx.analyzer.recalc_stats(&mut stats, &ctx.layout);
+    Ok(Summary {
+        percentages: Keymui::percentages(&ctx, &stats),
+        keys: LayoutDisplay::keys(&ctx, ColorStyle::Frequency, 0),
+    })
+}
+
+/// Analyzes every layout in a background thread, one
+/// [`Message::LayoutSummarized`] at a time, or a [`Message::BrowserFailed`]
+/// if the metrics or corpus can't be read. A new job replaces the running
+/// one.
+pub fn subscription(job: Option<Job>) -> Subscription<Message> {
+    let Some(job) = job else {
+        return Subscription::none();
+    };
+    let generation = job.generation;
+    subscription::channel(
+        ("layout-browser", generation),
+        16,
+        move |mut output| async move {
+            let (sender, mut messages) = mpsc::unbounded();
+            thread::spawn(move || job.run(&sender));
+            while let Some(message) = messages.next().await {
+                let _ = output.send(message).await;
+            }
+            loop {
+                future::pending::<()>().await;
+            }
+        },
+    )
+}
+
+impl Keymui {
+    pub fn browser_job(&self) -> Option<Job> {
+        Some(Job {
+            generation: self.browser.generation,
+            metrics: self
+                .metric_lists
+                .get(self.current_metrics.as_ref()?)?
+                .clone(),
+            corpus: self.corpora.get(self.current_corpus.as_ref()?)?.clone(),
+            layouts: self
+                .layout_sources
+                .iter()
+                .map(|(name, path)| (name.clone(), path.clone()))
+                .collect(),
+        })
+    }
+
+    /// The filter options: every keyboard some layout is made for.
+    pub fn browser_keyboards(&self) -> Vec<KeyboardFilter> {
+        let boards: BTreeSet<&String> = self.layouts.values().map(|l| &l.board).collect();
+        iter::once(KeyboardFilter::All)
+            .chain(boards.into_iter().map(|b| KeyboardFilter::Board(b.clone())))
+            .collect()
+    }
+
+    /// Layouts for the selected keyboard whose name or authors match the
+    /// search, best matches first.
+    pub fn browser_matches(&self) -> Vec<&str> {
+        let mut matches: Vec<(i32, &str)> = self
+            .layouts
+            .iter()
+            .filter(|(_, l)| match &self.browser.keyboard {
+                KeyboardFilter::All => true,
+                KeyboardFilter::Board(board) => l.board == *board,
+            })
+            .filter_map(|(name, l)| {
+                let score = [name.as_str(), l.name.as_str()]
+                    .into_iter()
+                    .chain(l.authors.iter().map(String::as_str))
+                    .filter_map(|field| fuzzy_score(&self.browser.search, field))
+                    .max()?;
+                Some((score, name.as_str()))
+            })
+            .collect();
+        // the sort is stable, so equal matches stay in name order
+        matches.sort_by_key(|(score, _)| Reverse(*score));
+        matches.into_iter().map(|(_, name)| name).collect()
+    }
+
+    /// The score, if one is set, and the first few metrics.
+    pub fn quick_stats(&self, percentages: &[f32]) -> String {
+        let score = self
+            .comparison_score(percentages)
+            .map(|score| format!("score {:.3}", score));
+        let metrics = self.metric_context.iter().flat_map(|ctx| {
+            ctx.metrics
+                .iter()
+                .zip(percentages)
+                .take(QUICK_STATS)
+                .map(|(m, p)| format!("{} {}", m.short, self.format_stat(&m.short, *p)))
+        });
+        score
+            .into_iter()
+            .chain(metrics)
+            .collect::<Vec<String>>()
+            .join("  ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_matches_are_case_insensitive_subsequences() {
+        assert!(fuzzy_score("SMK", "Semimak").is_some());
+        assert_eq!(fuzzy_score("kms", "Semimak"), None);
+        assert_eq!(fuzzy_score("semimaks", "semimak"), None);
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert_eq!(
+            fuzzy_score("se mi", "semimak"),
+            fuzzy_score("semi", "semimak")
+        );
+    }
+
+    #[test]
+    fn consecutive_characters_and_word_starts_rank_higher() {
+        let score = |query, candidate| fuzzy_score(query, candidate).unwrap();
+        assert!(score("sem", "semimak") > score("sem", "sturdy-elem"));
+        assert!(score("k", "semi-kick") > score("k", "semimak"));
+        assert!(score("sk", "sk-layout") > score("sk", "s-layout-k"));
+    }
+}
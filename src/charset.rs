@@ -0,0 +1,227 @@
+use color_eyre::eyre::{anyhow, Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The characters a corpus counts, grouped by key: the first character of
+/// each group is the unshifted one, and the rest are typed on the same key
+/// with shift. Stored in the data dir's `charsets` folder as a JSON list of
+/// groups, such as `["aA", "äÄ", " ", ",<"]`.
+#[derive(Debug, Clone, Default)]
+pub struct Charset {
+    pub groups: Vec<Vec<char>>,
+}
+
+pub const ENGLISH: &[&str] = &[
+    "aA", "bB", "cC", "dD", "eE", "fF", "gG", "hH", "iI", "jJ", "kK", "lL", "mM", "nN", "oO", "pP",
+    "qQ", "rR", "sS", "tT", "uU", "vV", "wW", "xX", "yY", "zZ", " ", ",<", ".>", "/?", "'\"", ";:",
+    "1!", "2@", "3#", "4$", "5%", "6^", "7&", "8*", "9(", "0)", "-_", "=+", "[{", "]}", "\\|",
+    "`~",
+];
+const GERMAN: &[&str] = &["äÄ", "öÖ", "üÜ", "ß"];
+const FRENCH: &[&str] = &[
+    "éÉ", "èÈ", "àÀ", "ùÙ", "çÇ", "âÂ", "êÊ", "îÎ", "ôÔ", "ûÛ", "ëË", "ïÏ", "œŒ", "«", "»",
+];
+const SPANISH: &[&str] = &["ñÑ", "áÁ", "éÉ", "íÍ", "óÓ", "úÚ", "üÜ", "¿", "¡"];
+
+impl Charset {
+    fn from_groups<'a>(groups: impl Iterator<Item = &'a str>) -> Self {
+        Self {
+            groups: groups.map(|g| g.chars().collect()).collect(),
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let s = fs::read_to_string(path)
+            .with_context(|| format!("couldn't read charset {}", &path.display()))?;
+        let groups: Vec<String> = serde_json::from_str(&s)
+            .with_context(|| format!("couldn't parse charset {}", &path.display()))?;
+        Ok(Self::from_groups(groups.iter().map(|g| g.as_str())))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let groups: Vec<String> = self.groups.iter().map(|g| g.iter().collect()).collect();
+        fs::write(path, serde_json::to_string_pretty(&groups)?)
+            .with_context(|| format!("couldn't write charset {}", &path.display()))
+    }
+
+    /// Every character that occurs in `text`, with upper and lowercase
+    /// letters grouped onto one key. Control characters and whitespace other
+    /// than spaces are left out.
+    pub fn detect(text: &str) -> Self {
+        let mut seen: Vec<char> = vec![];
+        let mut known = HashSet::new();
+        for c in text.chars() {
+            if (c.is_whitespace() && c != ' ') || c.is_control() {
+                continue;
+            }
+            // shifted letters share a key with their lowercase form
+            let c = match (c.is_uppercase(), single(c.to_lowercase())) {
+                (true, Some(lower)) => lower,
+                _ => c,
+            };
+            if known.insert(c) {
+                seen.push(c);
+            }
+        }
+        seen.sort_unstable();
+
+        let mut charset = Self::default();
+        for c in seen {
+            let mut group = vec![c];
+            if let Some(upper) = single(c.to_uppercase()).filter(|u| *u != c) {
+                group.push(upper);
+            }
+            charset.groups.push(group);
+        }
+        charset
+    }
+
+    /// Adds the groups of `other` whose characters aren't covered yet.
+    pub fn extend(&mut self, other: Charset) {
+        let mut known: HashSet<char> = self.groups.iter().flatten().copied().collect();
+        for group in other.groups {
+            let group: Vec<char> = group.into_iter().filter(|c| known.insert(*c)).collect();
+            if !group.is_empty() {
+                self.groups.push(group);
+            }
+        }
+    }
+}
+
+/// Where the charset a corpus was built with is kept, next to the corpus.
+pub fn beside(corpus: &Path) -> PathBuf {
+    corpus.with_extension("charset.json")
+}
+
+/// The one character a case conversion produced, if it produced exactly one.
+fn single(mut chars: impl Iterator<Item = char>) -> Option<char> {
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// Writes the built-in charsets unless the charset folder already exists.
+pub fn install_defaults(dir: &Path) -> Result<()> {
+    if dir.exists() {
+        return Ok(());
+    }
+    fs::create_dir_all(dir)?;
+    let english = Charset::from_groups(ENGLISH.iter().copied());
+    for (name, extra) in [
+        ("english", &[][..]),
+        ("german", GERMAN),
+        ("french", FRENCH),
+        ("spanish", SPANISH),
+    ] {
+        let mut charset = english.clone();
+        charset.extend(Charset::from_groups(extra.iter().copied()));
+        charset.write(&dir.join(format!("{}.json", name)))?;
+    }
+    Ok(())
+}
+
+/// Builds a charset from a spec like `german`, `auto` or `english+auto`,
+/// reading named charsets from `dir`. `auto` stands for the characters
+/// `detect` finds in the input, and each part adds only characters not
+/// covered yet.
+pub fn resolve(
+    dir: &Path,
+    spec: &str,
+    mut detect: impl FnMut() -> Result<Charset>,
+) -> Result<Charset> {
+    let mut charset = Charset::default();
+    for part in spec.split('+').map(str::trim).filter(|p| !p.is_empty()) {
+        if part == "auto" {
+            charset.extend(detect()?);
+            continue;
+        }
+        let path = dir.join(format!("{}.json", part));
+        if !path.exists() {
+            return Err(anyhow!(
+                "charset {} doesn't exist, available: auto, {}",
+                part,
+                names(dir).join(", ")
+            ));
+        }
+        charset.extend(Charset::read(&path)?);
+    }
+    if charset.groups.is_empty() {
+        return Err(anyhow!("charset is empty"));
+    }
+    Ok(charset)
+}
+
+pub fn names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            Some(path.file_stem()?.to_string_lossy().to_string())
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    fn groups(charset: &Charset) -> Vec<String> {
+        charset.groups.iter().map(|g| g.iter().collect()).collect()
+    }
+
+    /// A charset folder with the built-in charsets, for one test.
+    fn charset_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("keymui-charset-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        install_defaults(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn detect_groups_cases_onto_one_key() {
+        let charset = Charset::detect("Hello, World!\n\tßİ");
+        assert_eq!(
+            groups(&charset),
+            [" ", "!", ",", "dD", "eE", "hH", "lL", "oO", "rR", "wW", "ß", "İ"]
+        );
+    }
+
+    #[test]
+    fn extend_skips_covered_characters() {
+        let mut charset = Charset::from_groups(["aA", "b"].into_iter());
+        charset.extend(Charset::from_groups(["Aa", "bB", "c"].into_iter()));
+        assert_eq!(groups(&charset), ["aA", "b", "B", "c"]);
+    }
+
+    #[test]
+    fn resolve_combines_named_and_detected_charsets() {
+        let dir = charset_dir("resolve");
+        assert_eq!(names(&dir), ["english", "french", "german", "spanish"]);
+
+        let mut detected = 0;
+        let charset = resolve(&dir, "german + auto", || {
+            detected += 1;
+            Ok(Charset::detect("ä€"))
+        })
+        .unwrap();
+        assert_eq!(detected, 1);
+        let groups = groups(&charset);
+        assert_eq!(groups.len(), ENGLISH.len() + GERMAN.len() + 1);
+        assert_eq!(groups[ENGLISH.len()], "äÄ");
+        assert_eq!(groups.last().unwrap(), "€");
+
+        let error = resolve(&dir, "klingon", || unreachable!()).unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("available: auto, english, french, german, spanish"));
+        assert!(resolve(&dir, "", || unreachable!()).is_err());
+        assert!(resolve(&dir, "auto", || Ok(Charset::default())).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,6 +1,13 @@
+use crate::blend;
+use crate::corpus_import::CorpusImport;
+use crate::download::{DataKind, DataSource, FetchSource, Location};
+use crate::editor::{parse_authors, EditorStart};
+use crate::export::ExportFormat;
+use crate::log::Level;
+use crate::report::ReportFormat;
 use crate::{Keymui, Message};
-use color_eyre::eyre::Result;
-use iced::Application;
+use color_eyre::eyre::{ContextCompat, Result};
+use iced::{Application, Command};
 use std::fs::File;
 use std::io::Write;
 
@@ -9,31 +16,107 @@ pub enum UserArg {
     Key,
     NaturalNum,
     String,
+    /// The rest of the input, spaces included.
+    Rest,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum UserCommand {
     SetMetricsDirectory,
+    SetLayoutDirectory,
+    RemoveLayoutDirectory,
     Reload,
+    FetchData,
+    DataStatus,
+    AddDataSource,
+    RemoveDataSource,
     ImportCorpus,
+    ImportCorpusDir,
+    ImportCorpusFrom,
+    RebuildCorpus,
+    BlendCorpora,
     ViewNotification,
+    Problems,
     Swap,
     Precision,
     NgramFrequency,
     SaveLayout,
+    NewLayout,
+    ImportLayout,
+    ExportLayout,
+    ExportReport,
+    RenderLayout,
+    SetAuthors,
+    Undo,
+    Redo,
+    GotoNode,
+    RenameNode,
+    DeleteNode,
+    DiffNode,
+    SelectLayout,
+    SelectMetrics,
+    SelectCorpus,
+    Stats,
+    Nstrokes,
+    Optimize,
+    StopOptimize,
+    SetWeight,
+    SetScore,
+    Pin,
+    Unpin,
+    Compare,
+    Uncompare,
+    ClearComparison,
 }
 
 impl UserCommand {
     pub fn args(self) -> Vec<UserArg> {
         match self {
             UserCommand::SetMetricsDirectory => vec![],
+            UserCommand::SetLayoutDirectory => vec![UserArg::Rest],
+            UserCommand::RemoveLayoutDirectory => vec![UserArg::Rest],
             UserCommand::Reload => vec![],
-            UserCommand::ImportCorpus => vec![],
+            UserCommand::FetchData => vec![UserArg::Rest],
+            UserCommand::DataStatus => vec![],
+            UserCommand::AddDataSource => vec![UserArg::String, UserArg::String, UserArg::Rest],
+            UserCommand::RemoveDataSource => vec![UserArg::String],
+            UserCommand::ImportCorpus => vec![UserArg::Rest],
+            UserCommand::ImportCorpusDir => vec![UserArg::Rest],
+            UserCommand::ImportCorpusFrom => vec![UserArg::Rest],
+            UserCommand::RebuildCorpus => vec![UserArg::String],
+            UserCommand::BlendCorpora => vec![UserArg::String, UserArg::Rest],
             UserCommand::ViewNotification => vec![],
+            UserCommand::Problems => vec![],
             UserCommand::Swap => vec![UserArg::Key, UserArg::Key],
             UserCommand::Precision => vec![UserArg::NaturalNum],
             UserCommand::NgramFrequency => vec![UserArg::String, UserArg::String],
-            UserCommand::SaveLayout => vec![UserArg::String],
+            UserCommand::SaveLayout => vec![UserArg::String, UserArg::NaturalNum],
+            UserCommand::NewLayout => vec![UserArg::Rest],
+            UserCommand::ImportLayout => vec![UserArg::Rest],
+            UserCommand::ExportLayout => vec![UserArg::String, UserArg::Rest],
+            UserCommand::RenderLayout => vec![UserArg::Rest],
+            UserCommand::ExportReport => vec![UserArg::String, UserArg::NaturalNum, UserArg::Rest],
+            UserCommand::SetAuthors => vec![UserArg::Rest],
+            UserCommand::Undo => vec![],
+            UserCommand::Redo => vec![],
+            UserCommand::GotoNode => vec![UserArg::NaturalNum],
+            UserCommand::RenameNode => vec![UserArg::Rest],
+            UserCommand::DeleteNode => vec![UserArg::NaturalNum],
+            UserCommand::DiffNode => vec![UserArg::NaturalNum],
+            UserCommand::SelectLayout => vec![UserArg::String],
+            UserCommand::SelectMetrics => vec![UserArg::String],
+            UserCommand::SelectCorpus => vec![UserArg::String],
+            UserCommand::Stats => vec![],
+            UserCommand::Nstrokes => vec![UserArg::String, UserArg::NaturalNum],
+            UserCommand::Optimize => vec![UserArg::NaturalNum],
+            UserCommand::StopOptimize => vec![],
+            UserCommand::SetWeight => vec![UserArg::String, UserArg::String],
+            UserCommand::SetScore => vec![UserArg::Rest],
+            UserCommand::Pin => vec![UserArg::Key],
+            UserCommand::Unpin => vec![UserArg::Key],
+            UserCommand::Compare => vec![UserArg::String],
+            UserCommand::Uncompare => vec![UserArg::String],
+            UserCommand::ClearComparison => vec![],
         }
     }
     pub fn is_priority(self) -> bool {
@@ -45,13 +128,50 @@ impl std::fmt::Display for UserCommand {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             UserCommand::SetMetricsDirectory => write!(f, "set-metrics-directory"),
+            UserCommand::SetLayoutDirectory => write!(f, "set-layout-directory"),
+            UserCommand::RemoveLayoutDirectory => write!(f, "remove-layout-directory"),
             UserCommand::Reload => write!(f, "reload"),
+            UserCommand::FetchData => write!(f, "fetch-data"),
+            UserCommand::DataStatus => write!(f, "data-status"),
+            UserCommand::AddDataSource => write!(f, "add-data-source"),
+            UserCommand::RemoveDataSource => write!(f, "remove-data-source"),
             UserCommand::ImportCorpus => write!(f, "import-corpus"),
+            UserCommand::ImportCorpusDir => write!(f, "import-corpus-dir"),
+            UserCommand::ImportCorpusFrom => write!(f, "import-corpus-from"),
+            UserCommand::RebuildCorpus => write!(f, "rebuild-corpus"),
+            UserCommand::BlendCorpora => write!(f, "blend-corpora"),
             UserCommand::ViewNotification => write!(f, "view-notification"),
+            UserCommand::Problems => write!(f, "problems"),
             UserCommand::Swap => write!(f, "swap"),
             UserCommand::Precision => write!(f, "precision"),
             UserCommand::NgramFrequency => write!(f, "ngram-frequency"),
             UserCommand::SaveLayout => write!(f, "save-layout"),
+            UserCommand::NewLayout => write!(f, "new-layout"),
+            UserCommand::ImportLayout => write!(f, "import-layout"),
+            UserCommand::ExportLayout => write!(f, "export-layout"),
+            UserCommand::ExportReport => write!(f, "export-report"),
+            UserCommand::RenderLayout => write!(f, "render-layout"),
+            UserCommand::SetAuthors => write!(f, "set-authors"),
+            UserCommand::Undo => write!(f, "undo"),
+            UserCommand::Redo => write!(f, "redo"),
+            UserCommand::GotoNode => write!(f, "goto-node"),
+            UserCommand::RenameNode => write!(f, "rename-node"),
+            UserCommand::DeleteNode => write!(f, "delete-node"),
+            UserCommand::DiffNode => write!(f, "diff-node"),
+            UserCommand::SelectLayout => write!(f, "select-layout"),
+            UserCommand::SelectMetrics => write!(f, "select-metrics"),
+            UserCommand::SelectCorpus => write!(f, "select-corpus"),
+            UserCommand::Stats => write!(f, "stats"),
+            UserCommand::Nstrokes => write!(f, "nstrokes"),
+            UserCommand::Optimize => write!(f, "optimize"),
+            UserCommand::StopOptimize => write!(f, "stop-optimize"),
+            UserCommand::SetWeight => write!(f, "set-weight"),
+            UserCommand::SetScore => write!(f, "set-score"),
+            UserCommand::Pin => write!(f, "pin"),
+            UserCommand::Unpin => write!(f, "unpin"),
+            UserCommand::Compare => write!(f, "compare"),
+            UserCommand::Uncompare => write!(f, "uncompare"),
+            UserCommand::ClearComparison => write!(f, "clear-comparison"),
         }
     }
 }
@@ -85,12 +205,44 @@ pub fn commonest_completion(matches: Vec<&str>) -> usize {
     0
 }
 
+/// Matches the words after a command to its arguments. A trailing
+/// [`UserArg::Rest`] gets everything after the other arguments as one
+/// more element, which is left out when nothing is left. Missing
+/// arguments are left out as well, so handlers check the length.
+pub fn split_args(arg_types: &[UserArg], words: &[&str]) -> Vec<String> {
+    let positional = match arg_types.last() {
+        Some(UserArg::Rest) => arg_types.len() - 1,
+        _ => arg_types.len(),
+    };
+    let mut args: Vec<String> = words
+        .iter()
+        .take(positional)
+        .map(|s| s.to_string())
+        .collect();
+    if matches!(arg_types.last(), Some(UserArg::Rest)) && words.len() > positional {
+        args.push(words[positional..].join(" "));
+    }
+    args
+}
+
 impl Keymui {
-    pub fn parse_command(&mut self) -> Result<()> {
+    pub fn parse_command(&mut self) -> Result<Command<Message>> {
         let input = self.command_input.clone();
+        if let Some(command) = self.execute_line(&input)? {
+            self.command_input = String::new();
+            self.filter_commands();
+            Ok(command)
+        } else {
+            Ok(Command::none())
+        }
+    }
+
+    /// Runs a single line of command input. Returns `None` if it didn't
+    /// name a known command.
+    pub fn execute_line(&mut self, input: &str) -> Result<Option<Command<Message>>> {
         let split: Vec<&str> = input.split_whitespace().collect();
         if split.is_empty() {
-            return Ok(());
+            return Ok(None);
         }
         let command = self
             .commands
@@ -98,30 +250,277 @@ impl Keymui {
             .find(|c| c.to_string() == split[0])
             .copied();
         if let Some(cmd) = command {
-            let args: Vec<&str> = split
-                .iter()
-                .skip(1)
-                .take(cmd.args().len())
-                .copied()
-                .collect();
+            let args = split_args(&cmd.args(), &split[1..]);
+            let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
 
-            self.run_command(&cmd, &args)?;
-
-            self.command_input = String::new();
-            self.filter_commands();
+            Ok(Some(self.run_command(&cmd, &args)?))
+        } else {
+            Ok(None)
         }
-        Ok(())
     }
 
-    pub fn run_command(&mut self, cmd: &UserCommand, args: &[&str]) -> Result<()> {
+    pub fn run_command(&mut self, cmd: &UserCommand, args: &[&str]) -> Result<Command<Message>> {
         let message = match cmd {
             UserCommand::SetMetricsDirectory => Some(Message::SetMetricsDirectory),
+            UserCommand::SetLayoutDirectory => {
+                Some(Message::SetLayoutDirectory(match args.join(" ").trim() {
+                    "" => None,
+                    path => Some(self.expand_path(path)?),
+                }))
+            }
+            UserCommand::RemoveLayoutDirectory => {
+                let path = self.expand_path(args.join(" ").trim())?;
+                Some(Message::RemoveLayoutDirectory(path))
+            }
             UserCommand::Reload => Some(Message::Reload),
-            UserCommand::ImportCorpus => Some(Message::ImportNewCorpus),
+            UserCommand::FetchData => {
+                let args = args.join(" ");
+                let (overwrite, path) = match args.trim().split_once(' ') {
+                    Some(("overwrite", path)) => (true, path.trim()),
+                    _ if args.trim() == "overwrite" => (true, ""),
+                    _ => (false, args.trim()),
+                };
+                let sources = &self.config.data_sources;
+                let source = match path {
+                    "" => FetchSource::Sources(sources.clone()),
+                    name if sources.iter().any(|s| s.name == name) => FetchSource::Sources(
+                        sources.iter().filter(|s| s.name == name).cloned().collect(),
+                    ),
+                    path => FetchSource::local(self.expand_path(path)?),
+                };
+                Some(Message::FetchData(source, overwrite))
+            }
+            UserCommand::DataStatus => {
+                self.report_data_status();
+                None
+            }
+            UserCommand::AddDataSource => match args {
+                [name, target, location] if !location.is_empty() => {
+                    let target = DataKind::parse(target)
+                        .context("the target must be layouts, metrics or corpora")?;
+                    let location = Location::parse(location, |p| self.expand_path(p))?;
+                    Some(Message::AddDataSource(DataSource {
+                        name: name.to_string(),
+                        target,
+                        location,
+                    }))
+                }
+                _ => {
+                    self.warn(
+                        "usage: add-data-source <name> <layouts|metrics|corpora> <url or folder>",
+                    );
+                    None
+                }
+            },
+            UserCommand::RemoveDataSource => args
+                .first()
+                .map(|name| Message::RemoveDataSource(name.to_string())),
+            UserCommand::ImportCorpus => Some(Message::PickCorpusFiles(
+                CorpusImport::parse(&args.join(" ")),
+                false,
+            )),
+            UserCommand::ImportCorpusDir => Some(Message::PickCorpusFiles(
+                CorpusImport::parse(&args.join(" ")),
+                true,
+            )),
+            UserCommand::BlendCorpora => match args {
+                [name, weights] => match blend::parse_weights(weights) {
+                    Ok(weights) => Some(Message::BlendCorpora(name.to_string(), weights)),
+                    Err(e) => {
+                        self.error("usage: blend-corpora <name> <corpus>:<weight> ...", &e);
+                        None
+                    }
+                },
+                _ => {
+                    self.warn("usage: blend-corpora <name> <corpus>:<weight> ...");
+                    None
+                }
+            },
+            UserCommand::RebuildCorpus => args
+                .first()
+                .map(|name| Message::RebuildCorpus(name.to_string())),
+            UserCommand::ImportCorpusFrom => {
+                let import = CorpusImport::parse(&args.join(" "));
+                if import.paths.is_empty() {
+                    self.warn(
+                        "usage: import-corpus-from [name=..] [charset=..] [include=..] [exclude=..] <paths>",
+                    );
+                    None
+                } else {
+                    Some(Message::ImportCorpus(import))
+                }
+            }
             UserCommand::ViewNotification => Some(Message::ViewNotification),
+            UserCommand::NewLayout => Some(Message::OpenEditor(match args.join(" ").trim() {
+                "" => match &self.current_layout {
+                    Some(layout) => EditorStart::Layout(layout.clone()),
+                    None => EditorStart::Blank,
+                },
+                "blank" => EditorStart::Blank,
+                layout => EditorStart::Layout(layout.to_string()),
+            })),
+            UserCommand::ImportLayout => Some(Message::ImportLayout(match args.join(" ").trim() {
+                "" => None,
+                path => Some(self.expand_path(path)?),
+            })),
+            UserCommand::ExportLayout => {
+                let format = args.first().copied().unwrap_or("");
+                let formats = match (format, ExportFormat::parse(format)) {
+                    ("all", _) => ExportFormat::ALL.to_vec(),
+                    (_, Some(format)) => vec![format],
+                    _ => {
+                        self.warn(
+                            "usage: export-layout <qmk|zmk|kanata|kmonad|xkb|keylayout|all> [folder]",
+                        );
+                        return Ok(Command::none());
+                    }
+                };
+                let dir = match args.get(1).map_or("", |dir| dir.trim()) {
+                    "" => None,
+                    dir => Some(self.expand_path(dir)?),
+                };
+                for path in self.export_layout(&formats, dir.as_deref())? {
+                    self.info(format!("exported layout to {}", path.display()));
+                }
+                None
+            }
+            UserCommand::ExportReport => {
+                let format = args.first().and_then(|f| ReportFormat::parse(f));
+                let count = match args.get(1).copied().unwrap_or("") {
+                    "" => Some(10),
+                    count => count.parse().ok(),
+                };
+                let (Some(format), Some(count)) = (format, count) else {
+                    self.warn("usage: export-report <md|csv|json> [nstrokes per metric] [folder]");
+                    return Ok(Command::none());
+                };
+                let dir = match args.get(2).map_or("", |dir| dir.trim()) {
+                    "" => None,
+                    dir => Some(self.expand_path(dir)?),
+                };
+                let path = self.export_report(format, count, dir.as_deref())?;
+                self.info(format!("exported report to {}", path.display()));
+                None
+            }
+            UserCommand::RenderLayout => {
+                let path = self.render_layout(&args.join(" "))?;
+                self.info(format!("rendered layout to {}", path.display()));
+                None
+            }
+            UserCommand::SetAuthors => Some(Message::SetAuthors(parse_authors(&args.join(" ")))),
+            UserCommand::Problems => {
+                if !self.report_problems() {
+                    self.info("every file loaded fine");
+                }
+                None
+            }
+            UserCommand::Undo => Some(Message::Undo),
+            UserCommand::Redo => Some(Message::Redo),
+            UserCommand::GotoNode => args
+                .first()
+                .and_then(|n| n.parse().ok())
+                .map(Message::JumpToNode),
+            UserCommand::RenameNode => Some(Message::RenameNode(args.join(" "))),
+            UserCommand::DeleteNode => args
+                .first()
+                .and_then(|n| n.parse().ok())
+                .map(Message::DeleteNode),
+            UserCommand::DiffNode => args
+                .first()
+                .and_then(|n| n.parse().ok())
+                .map(Message::DiffNode),
+            UserCommand::Optimize => Some(Message::StartOptimizer(
+                args.first()
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or(1_000_000),
+            )),
+            UserCommand::StopOptimize => Some(Message::StopOptimizer),
+            UserCommand::SetWeight => match (args.first(), args.get(1).map(|w| w.parse::<f32>())) {
+                (Some(metric), Some(Ok(weight))) => {
+                    Some(Message::SetWeight(metric.to_string(), weight))
+                }
+                _ => {
+                    self.warn("usage: set-weight <metric> <weight>");
+                    None
+                }
+            },
+            UserCommand::SetScore => Some(Message::SetScore(args.join(" "))),
+            UserCommand::Pin => args
+                .first()
+                .and_then(|x| x.chars().next())
+                .map(Message::PinKey),
+            UserCommand::Unpin => args
+                .first()
+                .and_then(|x| x.chars().next())
+                .map(Message::UnpinKey),
+            UserCommand::Compare => args
+                .first()
+                .map(|name| Message::CompareLayout(name.to_string())),
+            UserCommand::Uncompare => args
+                .first()
+                .map(|name| Message::UncompareLayout(name.to_string())),
+            UserCommand::ClearComparison => Some(Message::ClearComparison),
+            UserCommand::SelectLayout => match args.first() {
+                Some(name) if self.layouts.contains_key(*name) => {
+                    Some(Message::LayoutSelected(name.to_string()))
+                }
+                _ => {
+                    self.warn("layout doesn't exist");
+                    None
+                }
+            },
+            UserCommand::SelectMetrics => match args.first() {
+                Some(name) if self.metric_lists.contains_key(*name) => {
+                    Some(Message::ContextSelected(name.to_string()))
+                }
+                _ => {
+                    self.warn("metric data doesn't exist");
+                    None
+                }
+            },
+            UserCommand::SelectCorpus => match args.first() {
+                Some(name) if self.corpora.contains_key(*name) => {
+                    Some(Message::CorpusSelected(name.to_string()))
+                }
+                _ => {
+                    self.warn("corpus doesn't exist");
+                    None
+                }
+            },
+            UserCommand::Stats => {
+                if let Some(table) = self.stats_table() {
+                    self.log_entry(
+                        Level::Info,
+                        format!(
+                            "stats for {}",
+                            self.current_layout.clone().unwrap_or_default()
+                        ),
+                        Some(table),
+                    );
+                }
+                None
+            }
+            UserCommand::Nstrokes => {
+                let metric = self.metric_context.as_ref().and_then(|ctx| {
+                    args.first()
+                        .and_then(|arg| ctx.metrics.iter().position(|m| m.short == *arg))
+                });
+                if let Some(metric) = metric {
+                    let count = args.get(1).and_then(|n| n.parse().ok()).unwrap_or(20);
+                    let _ = self.update(Message::SetNstrokesMetric(metric));
+                    let table = self.nstrokes_table(count);
+                    self.log_entry(
+                        Level::Info,
+                        format!("top {} nstrokes for {}", count, args[0]),
+                        Some(table),
+                    );
+                } else {
+                    self.warn("metric doesn't exist");
+                }
+                None
+            }
             UserCommand::Swap => {
                 let keys: Vec<char> = args.iter().filter_map(|x| x.chars().next()).collect();
-                println!("{:?}", keys);
                 if keys.len() == 2 {
                     Some(Message::SwapKeys(keys[0], keys[1]))
                 } else {
@@ -167,47 +566,67 @@ impl Keymui {
                         .iter()
                         .map(|x| 100.0 * *x as f32 / corpus_total)
                         .collect();
-                    self.notification =
-                        (format!("total: ({:.2}%, {:.2}%)", total[0], total[1]), None);
+                    self.info(format!("total: ({:.2}%, {:.2}%)", total[0], total[1]));
                 }
                 None
             }
             UserCommand::SaveLayout => {
-                if let Some(ctx) = &self.metric_context {
+                // a node of the tree other than the current one can be saved
+                // by passing its id
+                let node_layout = match args.get(1).map(|n| n.parse::<usize>()) {
+                    Some(Ok(id)) => match (&self.metric_context, self.current_tree()) {
+                        (Some(ctx), Some(tree)) => match tree.node(id) {
+                            Some(node) => Some(
+                                node.layout
+                                    .iter()
+                                    .map(|c| ctx.analyzer.corpus.corpus_char(*c))
+                                    .collect(),
+                            ),
+                            None => {
+                                self.warn("node doesn't exist");
+                                return Ok(Command::none());
+                            }
+                        },
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                if let Some(ctx) = &mut self.metric_context {
                     if args.is_empty()
                         || self
                             .layouts
                             .values()
                             .any(|data| data.name.to_lowercase() == args[0])
                     {
-                        self.notification = (
-                            "Layout name must be provided and different from an existing layout"
-                                .to_string(),
-                            None,
+                        self.warn(
+                            "Layout name must be provided and different from an existing layout",
                         );
-                        return Ok(());
+                        return Ok(Command::none());
                     }
                     let name = args[0].to_owned();
+                    let current = node_layout.map(|l| std::mem::replace(&mut ctx.layout.0, l));
                     let data = ctx
                         .layout_data()
                         .name(name.clone())
-                        .authors(vec!["User".to_string()]);
+                        .authors(self.config.authors.clone());
+                    if let Some(current) = current {
+                        ctx.layout.0 = current;
+                    }
                     let s = serde_json::to_string_pretty(&data)?;
                     let path = self
-                        .data_dir()
-                        .join("layouts/")
+                        .layouts_dir()
                         .join(format!("{}.json", name.to_lowercase()));
                     let mut file = File::create(&path)?;
                     write!(file, "{}", &s)?;
-                    println!("Saved layout to {:?}", path);
+                    self.info(format!("saved layout to {}", path.display()));
                 }
                 Some(Message::Reload)
             }
         };
-        if let Some(m) = message {
-            let _ = self.update(m);
-        };
-        Ok(())
+        Ok(match message {
+            Some(m) => self.update(m),
+            None => Command::none(),
+        })
     }
 
     pub fn filter_commands(&mut self) {
@@ -225,3 +644,42 @@ impl Keymui {
             .collect();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(arg_types: &[UserArg], input: &str) -> Vec<String> {
+        let words: Vec<&str> = input.split_whitespace().collect();
+        split_args(arg_types, &words)
+    }
+
+    #[test]
+    fn rest_is_its_own_argument() {
+        let types = [UserArg::String, UserArg::NaturalNum, UserArg::Rest];
+        assert_eq!(split(&types, "md"), ["md"]);
+        assert_eq!(split(&types, "md 5"), ["md", "5"]);
+        assert_eq!(
+            split(&types, "md 5 ~/my reports"),
+            ["md", "5", "~/my reports"]
+        );
+    }
+
+    #[test]
+    fn rest_alone_takes_everything() {
+        assert!(split(&[UserArg::Rest], "").is_empty());
+        assert_eq!(split(&[UserArg::Rest], "a  b c"), ["a b c"]);
+    }
+
+    #[test]
+    fn missing_arguments_are_left_out() {
+        let types = [UserArg::String, UserArg::String, UserArg::Rest];
+        assert_eq!(split(&types, "name layouts"), ["name", "layouts"]);
+        assert_eq!(split(&types, "name"), ["name"]);
+    }
+
+    #[test]
+    fn extra_words_are_dropped_without_rest() {
+        assert_eq!(split(&[UserArg::Key, UserArg::Key], "a b c"), ["a", "b"]);
+    }
+}
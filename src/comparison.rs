@@ -0,0 +1,195 @@
+use crate::Keymui;
+use color_eyre::eyre::{ContextCompat, Result};
+use kc::Corpus;
+use km::{MetricContext, MetricData};
+use std::cmp::Ordering;
+
+/// A layout analyzed against the selected metrics and corpus.
+#[derive(Debug, Clone)]
+pub struct ComparedLayout {
+    pub name: String,
+    /// Percentage of each metric, in metric order.
+    pub percentages: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Score,
+    Metric(usize),
+}
+
+/// Which value of a row counts as the best one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Better {
+    Higher,
+    Lower,
+    Unknown,
+}
+
+/// Metrics where more is better when no score says otherwise. Every other
+/// metric is taken to be a penalty.
+const HIGHER_IS_BETTER: &[&str] = &["roll", "inroll", "outroll", "alt", "alternate"];
+
+impl Better {
+    /// The direction of a metric: the sign of its weight in the score, or,
+    /// if the score doesn't weigh it, what the metric usually means.
+    pub fn for_metric(short: &str, weight: f32) -> Self {
+        match Self::from_weight(weight) {
+            Better::Unknown if HIGHER_IS_BETTER.contains(&short) => Better::Higher,
+            Better::Unknown => Better::Lower,
+            better => better,
+        }
+    }
+
+    pub fn from_weight(weight: f32) -> Self {
+        if weight > 0.0 {
+            Better::Higher
+        } else if weight < 0.0 {
+            Better::Lower
+        } else {
+            Better::Unknown
+        }
+    }
+
+    /// Index of the best of `values`, if the direction is known.
+    pub fn best(self, values: impl Iterator<Item = f32>) -> Option<usize> {
+        let values = values.enumerate();
+        let cmp =
+            |a: &(usize, f32), b: &(usize, f32)| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal);
+        match self {
+            Better::Higher => values.max_by(cmp).map(|(i, _)| i),
+            Better::Lower => values.min_by(cmp).map(|(i, _)| i),
+            Better::Unknown => None,
+        }
+    }
+}
+
+impl Keymui {
+    pub fn compare_layout(
+        &self,
+        name: &str,
+        metrics: &MetricData,
+        corpus: &Corpus,
+    ) -> Result<ComparedLayout> {
+        let layout = self.layouts.get(name).context("layout doesn't exist")?;
+        let context = MetricContext::new(layout, metrics.clone(), corpus.clone())
+            .context("couldn't create metric context for layout")?;
+        let mut stats = vec![0.0; context.analyzer.data.metrics.len()];
+        context.analyzer.recalc_stats(&mut stats, &context.layout);
+        Ok(ComparedLayout {
+            name: name.to_string(),
+            percentages: Self::percentages(&context, &stats),
+        })
+    }
+
+    pub fn percentages(ctx: &MetricContext, stats: &[f32]) -> Vec<f32> {
+        let totals = ctx.layout.totals(&ctx.analyzer.corpus);
+        ctx.metrics
+            .iter()
+            .zip(stats.iter())
+            .map(|(m, s)| totals.percentage(*s, m.ngram_type))
+            .collect()
+    }
+
+    /// Analyzes every compared layout again, e.g. after the metrics or
+    /// corpus changed. The metrics and corpus are read once for all of
+    /// them. Layouts that fail to load are logged and dropped.
+    /// The browser starts over in the background.
+    pub fn refresh_comparison(&mut self) {
+        self.browser.invalidate();
+        if self.comparison.is_empty() {
+            return;
+        }
+        let data = match self
+            .read_metrics()
+            .and_then(|m| Ok((m, self.read_corpus()?)))
+        {
+            Ok(data) => data,
+            Err(e) => {
+                self.error("couldn't refresh the comparison", &e);
+                self.comparison.clear();
+                return;
+            }
+        };
+        let names: Vec<String> = self.comparison.iter().map(|c| c.name.clone()).collect();
+        let mut comparison = vec![];
+        for name in names {
+            match self.compare_layout(&name, &data.0, &data.1) {
+                Ok(layout) => comparison.push(layout),
+                Err(e) => self.error(format!("dropped {} from the comparison", name), &e),
+            }
+        }
+        self.comparison = comparison;
+    }
+
+    pub fn comparison_score(&self, percentages: &[f32]) -> Option<f32> {
+        let score = self.current_score()?;
+        let ctx = self.metric_context.as_ref()?;
+        Some(
+            score.evaluate(
+                ctx.metrics
+                    .iter()
+                    .map(|m| m.short.as_str())
+                    .zip(percentages.iter().copied()),
+            ),
+        )
+    }
+
+    /// The columns of the comparison table: the current layout, including
+    /// unsaved swaps, followed by every compared layout, in sort order.
+    pub fn comparison_columns(&self) -> Vec<ComparedLayout> {
+        let mut columns: Vec<ComparedLayout> = self
+            .metric_context
+            .as_ref()
+            .map(|ctx| ComparedLayout {
+                name: "(current)".to_string(),
+                percentages: Self::percentages(ctx, &self.layout_stats),
+            })
+            .into_iter()
+            .chain(self.comparison.iter().cloned())
+            .collect();
+
+        if let Some((key, descending)) = self.comparison_sort {
+            let value = |c: &ComparedLayout| match key {
+                SortKey::Score => self.comparison_score(&c.percentages),
+                SortKey::Metric(i) => c.percentages.get(i).copied(),
+            };
+            columns.sort_by(|a, b| {
+                let ord = value(a).partial_cmp(&value(b)).unwrap_or(Ordering::Equal);
+                if descending {
+                    ord.reverse()
+                } else {
+                    ord
+                }
+            });
+        }
+        columns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_decide_the_direction() {
+        assert_eq!(Better::for_metric("sfb", 1.0), Better::Higher);
+        assert_eq!(Better::for_metric("roll", -1.0), Better::Lower);
+    }
+
+    #[test]
+    fn unweighted_metrics_use_their_usual_direction() {
+        assert_eq!(Better::for_metric("sfb", 0.0), Better::Lower);
+        assert_eq!(Better::for_metric("redir", 0.0), Better::Lower);
+        assert_eq!(Better::for_metric("roll", 0.0), Better::Higher);
+        assert_eq!(Better::for_metric("alt", 0.0), Better::Higher);
+    }
+
+    #[test]
+    fn best_follows_the_direction() {
+        let values = [2.0, 1.0, 3.0];
+        assert_eq!(Better::Higher.best(values.into_iter()), Some(2));
+        assert_eq!(Better::Lower.best(values.into_iter()), Some(1));
+        assert_eq!(Better::Unknown.best(values.into_iter()), None);
+    }
+}
@@ -0,0 +1,538 @@
+use crate::charset::{self, Charset};
+use crate::preprocess::{Pipeline, Preprocessor};
+use crate::{Keymui, Message};
+use color_eyre::eyre::{anyhow, Context, ContextCompat, Result};
+use globset::GlobBuilder;
+use iced::futures::channel::mpsc;
+use iced::{Application, Command};
+use kc::Corpus;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use walkdir::WalkDir;
+
+/// How often import progress is sent back to the UI.
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The US keys, plus whatever else occurs in the files, so that shifted
+/// punctuation like `<` shares a key with `,`.
+pub const DEFAULT_CHARSET: &str = "english+auto";
+
+/// Numbers the scratch files of imports running at the same time.
+static NEXT_SCRATCH: AtomicUsize = AtomicUsize::new(0);
+
+/// What to build a corpus from. In the palette, this is written as
+/// `name=code charset=english+auto include=rs,py exclude=lock <paths or globs>`,
+/// plus any preprocessing options. It's saved next to the corpus as
+/// `<name>.pipeline.json` so the corpus can be rebuilt.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CorpusImport {
+    pub name: Option<String>,
+    pub charset: String,
+    /// Files, directories or glob patterns.
+    pub paths: Vec<String>,
+    /// Extensions to import. Empty means every extension.
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub pipeline: Pipeline,
+    /// Name of a pipeline in the data dir's `pipelines` folder to start
+    /// from. Resolved into `pipeline` before importing.
+    #[serde(skip)]
+    pipeline_file: Option<String>,
+    /// Preprocessing options given in the palette, applied on top of
+    /// `pipeline_file`.
+    #[serde(skip)]
+    steps: Vec<(String, String)>,
+}
+
+fn extensions(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+        .filter(|e| !e.is_empty())
+        .collect()
+}
+
+fn is_glob(s: &str) -> bool {
+    s.contains(['*', '?', '[', '{'])
+}
+
+impl CorpusImport {
+    pub fn parse(s: &str) -> Self {
+        let mut import = Self {
+            name: None,
+            charset: DEFAULT_CHARSET.to_string(),
+            paths: vec![],
+            include: vec![],
+            exclude: vec![],
+            pipeline: Pipeline::default(),
+            pipeline_file: None,
+            steps: vec![],
+        };
+        for token in s.split_whitespace() {
+            match token.split_once('=') {
+                Some(("name", v)) => import.name = Some(v.to_string()),
+                Some(("charset", v)) => import.charset = v.to_string(),
+                Some(("include", v)) => import.include = extensions(v),
+                Some(("exclude", v)) => import.exclude = extensions(v),
+                Some(("pipeline", v)) => import.pipeline_file = Some(v.to_string()),
+                Some((k, v)) if Pipeline::KEYS.contains(&k) => {
+                    import.steps.push((k.to_string(), v.to_string()))
+                }
+                _ => import.paths.push(token.to_string()),
+            }
+        }
+        import
+    }
+
+    fn accepts(&self, path: &Path) -> bool {
+        let ext = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        (self.include.is_empty() || self.include.contains(&ext)) && !self.exclude.contains(&ext)
+    }
+
+    /// Makes every path absolute, so that the import can be repeated from
+    /// anywhere.
+    fn absolute_paths(&mut self, home: &Path) -> Result<()> {
+        let cwd = env::current_dir()?;
+        for path in &mut self.paths {
+            *path = match path.strip_prefix("~/") {
+                Some(rest) => home.join(rest),
+                None => cwd.join(path.as_str()),
+            }
+            .to_string_lossy()
+            .to_string();
+        }
+        Ok(())
+    }
+
+    /// Every file the paths refer to, with directories walked recursively
+    /// and globs expanded, filtered by extension.
+    pub fn files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = vec![];
+        for path in self.paths.iter().cloned() {
+            if is_glob(&path) {
+                // walk from the deepest directory without glob characters
+                let base: PathBuf = Path::new(&path)
+                    .components()
+                    .take_while(|c| !is_glob(&c.as_os_str().to_string_lossy()))
+                    .collect();
+                let matcher = GlobBuilder::new(&path)
+                    .literal_separator(true)
+                    .build()
+                    .with_context(|| format!("invalid glob {}", path))?
+                    .compile_matcher();
+                files.extend(
+                    WalkDir::new(if base.as_os_str().is_empty() {
+                        Path::new(".")
+                    } else {
+                        &base
+                    })
+                    .into_iter()
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.file_type().is_file())
+                    .map(|e| e.into_path())
+                    .filter(|p| matcher.is_match(p)),
+                );
+            } else {
+                let path = PathBuf::from(path);
+                if path.is_dir() {
+                    files.extend(
+                        WalkDir::new(&path)
+                            .into_iter()
+                            .filter_map(|e| e.ok())
+                            .filter(|e| e.file_type().is_file())
+                            .map(|e| e.into_path()),
+                    );
+                } else if path.is_file() {
+                    files.push(path);
+                } else {
+                    return Err(anyhow!("{} doesn't exist", path.display()));
+                }
+            }
+        }
+        files.retain(|f| self.accepts(f));
+        files.sort();
+        files.dedup();
+        Ok(files)
+    }
+
+    /// The given name, or else the name of the first path.
+    fn corpus_name(&self) -> Result<String> {
+        if let Some(name) = &self.name {
+            return Ok(name.clone());
+        }
+        let first = self.paths.first().context("no files given")?;
+        let stem = Path::new(first)
+            .file_stem()
+            .context("couldn't get path stem")?;
+        Ok(stem.to_string_lossy().to_string())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportSummary {
+    pub name: String,
+    pub files: usize,
+    /// Files that couldn't be read, with the reason.
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ImportProgress {
+    Reading {
+        name: String,
+        done: usize,
+        total: usize,
+    },
+    Finished(Result<ImportSummary, String>),
+}
+
+struct ImportJob {
+    name: String,
+    files: Vec<PathBuf>,
+    charset: String,
+    charset_dir: PathBuf,
+    /// `None` when the pipeline doesn't change anything.
+    preprocessor: Option<Preprocessor>,
+    out: PathBuf,
+    recipe: CorpusImport,
+}
+
+impl ImportJob {
+    fn read(&self, file: &Path) -> Result<String> {
+        let text = fs::read_to_string(file)?;
+        Ok(match &self.preprocessor {
+            Some(p) => p.apply(&text),
+            None => text,
+        })
+    }
+
+    fn add_file(&self, corpus: &mut Corpus, file: &Path, scratch: &Path) -> Result<()> {
+        match self.preprocessor {
+            Some(_) => {
+                fs::write(scratch, self.read(file)?)?;
+                corpus.add_file(scratch)?;
+            }
+            None => corpus.add_file(file)?,
+        }
+        Ok(())
+    }
+
+    fn run(self, progress: &mut dyn FnMut(ImportProgress)) -> Result<ImportSummary> {
+        let charset = charset::resolve(&self.charset_dir, &self.charset, || {
+            let mut detected = Charset::default();
+            for file in &self.files {
+                if let Ok(text) = self.read(file) {
+                    detected.extend(Charset::detect(&text));
+                }
+            }
+            Ok(detected)
+        })?;
+        let mut corpus = Corpus::with_char_list(charset.groups.clone());
+
+        // preprocessed text goes through a scratch file, since corpora
+        // count files
+        let scratch = env::temp_dir().join(format!(
+            "keymui-import-{}-{}.txt",
+            process::id(),
+            NEXT_SCRATCH.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut skipped = vec![];
+        let mut last_report = Instant::now();
+        for (i, file) in self.files.iter().enumerate() {
+            if last_report.elapsed() > REPORT_INTERVAL {
+                progress(ImportProgress::Reading {
+                    name: self.name.clone(),
+                    done: i,
+                    total: self.files.len(),
+                });
+                last_report = Instant::now();
+            }
+            if let Err(e) = self.add_file(&mut corpus, file, &scratch) {
+                skipped.push(format!("{}: {}", file.display(), e));
+            }
+        }
+        let _ = fs::remove_file(&scratch);
+        if skipped.len() == self.files.len() {
+            return Err(anyhow!("none of the files could be read"));
+        }
+
+        let bin = rmp_serde::to_vec(&corpus)?;
+        fs::write(&self.out, bin)
+            .with_context(|| format!("couldn't write corpus to {}", &self.out.display()))?;
+        charset.write(&charset::beside(&self.out))?;
+        let recipe = self.out.with_extension("pipeline.json");
+        fs::write(&recipe, serde_json::to_string_pretty(&self.recipe)?)
+            .with_context(|| format!("couldn't write pipeline to {}", &recipe.display()))?;
+        Ok(ImportSummary {
+            name: self.name,
+            files: self.files.len(),
+            skipped,
+        })
+    }
+}
+
+impl Keymui {
+    /// Builds a corpus in the background, reporting progress through
+    /// [`Message::CorpusImportProgress`].
+    pub fn start_corpus_import(&mut self, mut import: CorpusImport) -> Result<Command<Message>> {
+        import.absolute_paths(self.base_dirs.home_dir())?;
+        let files = import.files()?;
+        if files.is_empty() {
+            return Err(anyhow!("no files matched"));
+        }
+        if let Some(file) = import.pipeline_file.take() {
+            import.pipeline = Pipeline::read(
+                &self
+                    .data_dir()
+                    .join("pipelines")
+                    .join(format!("{}.json", file)),
+            )?;
+        }
+        for (key, value) in std::mem::take(&mut import.steps) {
+            import.pipeline.set(&key, &value)?;
+        }
+        let preprocessor = match import.pipeline.is_empty() {
+            true => None,
+            false => Some(import.pipeline.compile()?),
+        };
+
+        let name = import.corpus_name()?;
+        import.name = Some(name.clone());
+        let cdir = self.corpora_dir();
+        fs::create_dir_all(&cdir)?;
+        let job = ImportJob {
+            out: cdir.join(format!("{}.corpus", name)),
+            name,
+            files,
+            charset: import.charset.clone(),
+            charset_dir: self.data_dir().join("charsets"),
+            preprocessor,
+            recipe: import,
+        };
+
+        if self.headless {
+            let result = job.run(&mut |_| ()).map_err(|e| format!("{:?}", e));
+            return Ok(
+                self.update(Message::CorpusImportProgress(ImportProgress::Finished(
+                    result,
+                ))),
+            );
+        }
+        let (sender, receiver) = mpsc::unbounded();
+        thread::spawn(move || {
+            let result = job
+                .run(&mut |p| {
+                    let _ = sender.unbounded_send(p);
+                })
+                .map_err(|e| format!("{:?}", e));
+            let _ = sender.unbounded_send(ImportProgress::Finished(result));
+        });
+        Ok(Command::run(receiver, Message::CorpusImportProgress))
+    }
+
+    /// Imports a corpus again from the files and pipeline it was built with.
+    pub fn rebuild_corpus(&mut self, name: &str) -> Result<Command<Message>> {
+        let path = self.corpora_dir().join(format!("{}.pipeline.json", name));
+        let s = fs::read_to_string(&path)
+            .with_context(|| format!("corpus {} has no saved pipeline", name))?;
+        let import: CorpusImport = serde_json::from_str(&s)
+            .with_context(|| format!("couldn't parse pipeline {}", &path.display()))?;
+        self.start_corpus_import(import)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty folder of its own for every test.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("keymui-import-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Creates `files` under `dir`, each containing its own path.
+    fn create(dir: &Path, files: &[&str]) {
+        for file in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, file).unwrap();
+        }
+    }
+
+    fn relative(dir: &Path, files: Vec<PathBuf>) -> Vec<String> {
+        files
+            .iter()
+            .map(|f| f.strip_prefix(dir).unwrap().to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn options_are_parsed_and_the_rest_are_paths() {
+        let import = CorpusImport::parse(
+            "name=code charset=german include=.RS,py, exclude=lock pipeline=clean \
+             strip=html src ~/notes/*.md",
+        );
+        assert_eq!(import.name.as_deref(), Some("code"));
+        assert_eq!(import.charset, "german");
+        assert_eq!(import.include, ["rs", "py"]);
+        assert_eq!(import.exclude, ["lock"]);
+        assert_eq!(import.pipeline_file.as_deref(), Some("clean"));
+        assert_eq!(import.steps, [("strip".to_string(), "html".to_string())]);
+        assert_eq!(import.paths, ["src", "~/notes/*.md"]);
+        assert_eq!(import.corpus_name().unwrap(), "code");
+
+        let import = CorpusImport::parse("books/moby-dick.txt other.txt");
+        assert_eq!(import.name, None);
+        assert_eq!(import.corpus_name().unwrap(), "moby-dick");
+        assert!(CorpusImport::parse("").corpus_name().is_err());
+    }
+
+    #[test]
+    fn globs_are_told_apart_from_paths() {
+        for glob in ["*.txt", "src/**/*.rs", "file?.md", "[ab].txt", "*.{rs,py}"] {
+            assert!(is_glob(glob), "{}", glob);
+        }
+        for path in ["notes.txt", "src/main.rs", "~/books", "a-b_c.d"] {
+            assert!(!is_glob(path), "{}", path);
+        }
+    }
+
+    #[test]
+    fn files_are_filtered_by_extension() {
+        let dir = scratch_dir("files");
+        create(
+            &dir,
+            &[
+                "a.rs",
+                "b.PY",
+                "Cargo.lock",
+                "notes.txt",
+                "src/c.rs",
+                "src/deep/d.rs",
+            ],
+        );
+        let files = |options: &str| {
+            let import = CorpusImport::parse(&format!("{} {}", options, dir.display()));
+            relative(&dir, import.files().unwrap())
+        };
+        assert_eq!(
+            files(""),
+            [
+                "Cargo.lock",
+                "a.rs",
+                "b.PY",
+                "notes.txt",
+                "src/c.rs",
+                "src/deep/d.rs"
+            ]
+        );
+        assert_eq!(
+            files("include=rs,py"),
+            ["a.rs", "b.PY", "src/c.rs", "src/deep/d.rs"]
+        );
+        assert_eq!(
+            files("exclude=lock,txt"),
+            ["a.rs", "b.PY", "src/c.rs", "src/deep/d.rs"]
+        );
+        assert_eq!(files("include=rs exclude=rs"), Vec::<String>::new());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn globs_and_files_are_expanded_once() {
+        let dir = scratch_dir("globs");
+        create(&dir, &["a.rs", "b.py", "src/c.rs", "src/deep/d.rs"]);
+        let files = |paths: &[&str]| {
+            let paths: Vec<String> = paths
+                .iter()
+                .map(|p| dir.join(p).to_string_lossy().to_string())
+                .collect();
+            relative(&dir, CorpusImport::parse(&paths.join(" ")).files().unwrap())
+        };
+        // `*` stays within one folder, `**` doesn't
+        assert_eq!(files(&["*.rs"]), ["a.rs"]);
+        assert_eq!(files(&["**/*.rs"]), ["a.rs", "src/c.rs", "src/deep/d.rs"]);
+        assert_eq!(
+            files(&["src/*/*.rs", "a.rs", "*.rs"]),
+            ["a.rs", "src/deep/d.rs"]
+        );
+        assert!(
+            CorpusImport::parse(&dir.join("missing.txt").to_string_lossy())
+                .files()
+                .is_err()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn imports_save_their_pipeline_and_charset() {
+        let dir = scratch_dir("run");
+        create(&dir, &["a.txt", "b.txt"]);
+        let mut recipe = CorpusImport::parse(&format!(
+            "name=notes charset=auto whitespace=collapse {}",
+            dir.join("*.txt").display()
+        ));
+        recipe.pipeline.set("whitespace", "collapse").unwrap();
+        let job = ImportJob {
+            name: "notes".to_string(),
+            files: vec![
+                dir.join("a.txt"),
+                dir.join("missing.txt"),
+                dir.join("b.txt"),
+            ],
+            charset: recipe.charset.clone(),
+            charset_dir: dir.join("charsets"),
+            preprocessor: Some(recipe.pipeline.compile().unwrap()),
+            out: dir.join("notes.corpus"),
+            recipe: recipe.clone(),
+        };
+        let summary = job.run(&mut |_| ()).unwrap();
+        assert_eq!(summary.name, "notes");
+        assert_eq!(summary.files, 3);
+        assert_eq!(summary.skipped.len(), 1);
+        assert!(summary.skipped[0].contains("missing.txt"));
+
+        assert!(dir.join("notes.corpus").is_file());
+        let saved: CorpusImport =
+            serde_json::from_str(&fs::read_to_string(dir.join("notes.pipeline.json")).unwrap())
+                .unwrap();
+        assert_eq!(saved.name.as_deref(), Some("notes"));
+        assert_eq!(saved.charset, "auto");
+        assert_eq!(saved.paths, recipe.paths);
+        assert_eq!(saved.pipeline, recipe.pipeline);
+        let charset = Charset::read(&dir.join("notes.charset.json")).unwrap();
+        assert!(charset.groups.contains(&vec!['a', 'A']));
+        assert!(charset.groups.contains(&vec!['.']));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn default_charset_keeps_shifted_punctuation_on_its_key() {
+        let import = CorpusImport::parse("notes.txt");
+        assert_eq!(import.charset, DEFAULT_CHARSET);
+
+        let dir = scratch_dir("charset").join("charsets");
+        charset::install_defaults(&dir).unwrap();
+        let charset = charset::resolve(&dir, &import.charset, || {
+            Ok(Charset::detect("Hi, <you> €5!"))
+        })
+        .unwrap();
+        assert!(charset.groups.contains(&vec![',', '<']));
+        assert!(charset.groups.contains(&vec!['5', '%']));
+        assert!(charset.groups.contains(&vec!['€']));
+        assert!(!charset.groups.contains(&vec!['<']));
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,287 @@
+use crate::Keymui;
+use kc::Corpus;
+use std::cmp::Reverse;
+
+/// How many of the most frequent n-grams of each kind are kept.
+const TOP: usize = 30;
+
+#[derive(Debug, Clone)]
+pub struct NgramList {
+    pub total: u64,
+    /// The most frequent n-grams with their counts, most frequent first.
+    pub top: Vec<(String, u32)>,
+}
+
+impl NgramList {
+    fn new(mut counts: Vec<(String, u32)>, total: u64) -> Self {
+        if counts.len() > TOP {
+            counts.select_nth_unstable_by(TOP, |a, b| b.1.cmp(&a.1));
+            counts.truncate(TOP);
+        }
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Self { total, top: counts }
+    }
+
+    pub fn percentage(&self, count: u32) -> f32 {
+        100.0 * count as f32 / self.total.max(1) as f32
+    }
+}
+
+/// Summary of a corpus for the Corpus pane. It walks every n-gram, so it's
+/// only computed once the pane shows it.
+#[derive(Debug, Clone)]
+pub struct CorpusStats {
+    pub name: String,
+    /// Every character that occurs, with its corpus index and count, most
+    /// frequent first.
+    pub chars: Vec<(char, usize, u32)>,
+    pub unigrams: NgramList,
+    pub bigrams: NgramList,
+    pub skipgrams: NgramList,
+    pub trigrams: NgramList,
+}
+
+/// Shows an n-gram with visible spaces.
+pub fn show_ngram(chars: impl Iterator<Item = char>) -> String {
+    chars.map(|c| if c == ' ' { '␣' } else { c }).collect()
+}
+
+impl CorpusStats {
+    pub fn new(name: &str, corpus: &Corpus) -> Self {
+        let mut chars: Vec<(char, usize, u32)> = corpus
+            .chars
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(i, count)| (corpus.uncorpus_unigram(i), i, *count))
+            .filter(|(c, _, _)| *c != '\0')
+            .collect();
+        chars.sort_by_key(|(_, _, count)| Reverse(*count));
+
+        let sum = |counts: &[u32]| counts.iter().map(|c| *c as u64).sum::<u64>();
+        let mut bigrams = vec![];
+        let mut skipgrams = vec![];
+        let mut trigrams = vec![];
+        for (a, ai, _) in &chars {
+            for (b, bi, _) in &chars {
+                let idx = corpus.bigram_idx(*ai, *bi);
+                let name = show_ngram([*a, *b].into_iter());
+                if corpus.bigrams[idx] > 0 {
+                    bigrams.push((name.clone(), corpus.bigrams[idx]));
+                }
+                if corpus.skipgrams[idx] > 0 {
+                    skipgrams.push((name, corpus.skipgrams[idx]));
+                }
+                for (c, ci, _) in &chars {
+                    let count = corpus.trigrams[corpus.trigram_idx(*ai, *bi, *ci)];
+                    if count > 0 {
+                        trigrams.push((show_ngram([*a, *b, *c].into_iter()), count));
+                    }
+                }
+            }
+        }
+
+        Self {
+            name: name.to_string(),
+            unigrams: NgramList::new(
+                chars
+                    .iter()
+                    .map(|(c, _, count)| (show_ngram(Some(*c).into_iter()), *count))
+                    .collect(),
+                sum(&corpus.chars),
+            ),
+            chars,
+            bigrams: NgramList::new(bigrams, sum(&corpus.bigrams)),
+            skipgrams: NgramList::new(skipgrams, sum(&corpus.skipgrams)),
+            trigrams: NgramList::new(trigrams, sum(&corpus.trigrams)),
+        }
+    }
+}
+
+/// Frequency of a searched n-gram.
+#[derive(Debug, PartialEq)]
+pub struct SearchResult {
+    pub ngram: String,
+    /// `(kind, count, percentage)` for each kind of n-gram the search
+    /// applies to.
+    pub counts: Vec<(&'static str, u32, f32)>,
+}
+
+impl CorpusStats {
+    /// How often `query` occurs as each kind of n-gram of its length.
+    pub fn search(&self, corpus: &Corpus, query: &str) -> Option<SearchResult> {
+        let idx: Vec<usize> = query.chars().map(|c| corpus.corpus_char(c)).collect();
+        let entry = |list: &NgramList, kind: &'static str, count: u32| {
+            (kind, count, list.percentage(count))
+        };
+        let counts = match idx[..] {
+            [a] => vec![entry(&self.unigrams, "unigram", corpus.chars[a])],
+            [a, b] => {
+                let i = corpus.bigram_idx(a, b);
+                vec![
+                    entry(&self.bigrams, "bigram", corpus.bigrams[i]),
+                    entry(&self.skipgrams, "skipgram", corpus.skipgrams[i]),
+                ]
+            }
+            [a, b, c] => vec![entry(
+                &self.trigrams,
+                "trigram",
+                corpus.trigrams[corpus.trigram_idx(a, b, c)],
+            )],
+            _ => return None,
+        };
+        Some(SearchResult {
+            ngram: show_ngram(query.chars()),
+            counts,
+        })
+    }
+
+    /// Characters that none of `keys` types, with their share of all
+    /// characters, most frequent first.
+    pub fn uncovered(&self, keys: &[usize]) -> Vec<(char, f32)> {
+        self.chars
+            .iter()
+            .filter(|(_, i, _)| !keys.contains(i))
+            .map(|(c, _, count)| (*c, self.unigrams.percentage(*count)))
+            .collect()
+    }
+}
+
+impl Keymui {
+    /// The summary of the loaded corpus, computed the first time it's asked
+    /// for.
+    pub fn corpus_stats(&self) -> Option<&CorpusStats> {
+        let (Some(ctx), Some(name)) = (&self.metric_context, &self.current_corpus) else {
+            return None;
+        };
+        Some(
+            self.corpus_stats
+                .get_or_init(|| CorpusStats::new(name, &ctx.analyzer.corpus)),
+        )
+    }
+
+    /// Drops the corpus summary if a different corpus was loaded.
+    pub fn refresh_corpus_stats(&mut self) {
+        if self
+            .corpus_stats
+            .get()
+            .is_some_and(|s| Some(&s.name) != self.current_corpus.as_ref())
+        {
+            self.corpus_stats.take();
+        }
+    }
+
+    pub fn search_corpus(&self, query: &str) -> Option<SearchResult> {
+        let ctx = self.metric_context.as_ref()?;
+        self.corpus_stats()?.search(&ctx.analyzer.corpus, query)
+    }
+
+    /// Characters of the corpus that no key of the current layout types,
+    /// with their share of all characters, most frequent first.
+    pub fn uncovered_chars(&self) -> Vec<(char, f32)> {
+        let (Some(ctx), Some(stats)) = (&self.metric_context, self.corpus_stats()) else {
+            return vec![];
+        };
+        stats.uncovered(&ctx.layout.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A corpus of `the cat` with some n-grams counted by hand.
+    fn corpus() -> Corpus {
+        let groups = ['t', 'h', 'e', ' ', 'c', 'a', 'x']
+            .map(|c| vec![c])
+            .to_vec();
+        let mut corpus = Corpus::with_char_list(groups);
+        for (c, count) in [('t', 2), ('h', 1), ('e', 1), (' ', 1), ('c', 1), ('a', 1)] {
+            let idx = corpus.corpus_char(c);
+            corpus.chars[idx] = count;
+        }
+        let bigram =
+            |corpus: &Corpus, a, b| corpus.bigram_idx(corpus.corpus_char(a), corpus.corpus_char(b));
+        for (a, b, count) in [('t', 'h', 1), ('h', 'e', 1), ('e', ' ', 1), ('c', 'a', 1)] {
+            let idx = bigram(&corpus, a, b);
+            corpus.bigrams[idx] = count;
+        }
+        let idx = bigram(&corpus, 't', 'e');
+        corpus.skipgrams[idx] = 3;
+        let [t, h, e] = ['t', 'h', 'e'].map(|c| corpus.corpus_char(c));
+        let idx = corpus.trigram_idx(t, h, e);
+        corpus.trigrams[idx] = 2;
+        corpus
+    }
+
+    #[test]
+    fn top_ngrams_are_sorted_by_count() {
+        let stats = CorpusStats::new("cat", &corpus());
+        assert_eq!(stats.name, "cat");
+        assert_eq!(stats.unigrams.total, 7);
+        assert_eq!(stats.unigrams.top[0], ("t".to_string(), 2));
+        // ties are sorted by name, and spaces are shown
+        let names: Vec<&str> = stats.unigrams.top.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["t", "a", "c", "e", "h", "␣"]);
+        assert_eq!(
+            stats.bigrams.top,
+            [
+                ("ca".to_string(), 1),
+                ("e␣".to_string(), 1),
+                ("he".to_string(), 1),
+                ("th".to_string(), 1)
+            ]
+        );
+        assert_eq!(stats.skipgrams.top, [("te".to_string(), 3)]);
+        assert_eq!(stats.trigrams.top, [("the".to_string(), 2)]);
+        // characters that don't occur aren't listed
+        assert!(stats.chars.iter().all(|(c, _, _)| *c != 'x'));
+    }
+
+    #[test]
+    fn only_the_top_ngrams_are_kept() {
+        let counts = (0..TOP as u32 + 10).map(|i| (i.to_string(), i)).collect();
+        let list = NgramList::new(counts, 1000);
+        assert_eq!(list.top.len(), TOP);
+        assert_eq!(list.top[0], ((TOP as u32 + 9).to_string(), TOP as u32 + 9));
+        assert_eq!(list.top[TOP - 1], ("10".to_string(), 10));
+        assert_eq!(list.percentage(10), 1.0);
+    }
+
+    #[test]
+    fn search_finds_every_kind_of_ngram() {
+        let corpus = corpus();
+        let stats = CorpusStats::new("cat", &corpus);
+        let counts = |query: &str| {
+            stats.search(&corpus, query).map(|r| {
+                r.counts
+                    .iter()
+                    .map(|(kind, count, _)| (*kind, *count))
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(counts("t"), Some(vec![("unigram", 2)]));
+        assert_eq!(counts("te"), Some(vec![("bigram", 0), ("skipgram", 3)]));
+        assert_eq!(counts("the"), Some(vec![("trigram", 2)]));
+        assert_eq!(counts(""), None);
+        assert_eq!(counts("then"), None);
+
+        let result = stats.search(&corpus, "e ").unwrap();
+        assert_eq!(result.ngram, "e␣");
+        assert_eq!(result.counts[0], ("bigram", 1, 25.0));
+    }
+
+    #[test]
+    fn uncovered_characters_have_no_key() {
+        let corpus = corpus();
+        let stats = CorpusStats::new("cat", &corpus);
+        let keys: Vec<usize> = ['t', 'h', 'e', 'c'].map(|c| corpus.corpus_char(c)).to_vec();
+        let uncovered = stats.uncovered(&keys);
+        let chars: Vec<char> = uncovered.iter().map(|(c, _)| *c).collect();
+        assert_eq!(chars, [' ', 'a']);
+        assert!((uncovered[0].1 - 100.0 / 7.0).abs() < 1e-4);
+        assert!(stats
+            .uncovered(&stats.chars.iter().map(|(_, i, _)| *i).collect::<Vec<_>>())
+            .is_empty());
+    }
+}
@@ -1,29 +1,327 @@
-use serde::Deserialize;
-use std::fs;
-use std::path::Path;
+use crate::log::Level;
+use crate::{Keymui, Message};
+use color_eyre::eyre::{anyhow, Context, ContextCompat, Result};
+use core::fmt;
+use iced::futures::channel::oneshot;
+use iced::{Application, Command};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::thread;
 
 #[derive(Deserialize, Debug)]
 struct GithubFileData {
     name: String,
-    download_url: String,
+    /// Git blob hash of the file.
+    sha: String,
+    size: u64,
+    download_url: Option<String>,
 }
 
 static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
 
-pub fn download_files(data_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
-    download_repo(
-        &data_dir.join("layouts"),
-        "https://api.github.com/repos/semilin/km_layouts/contents/",
-    )?;
-    download_repo(
-        &data_dir.join("metrics"),
-        "https://api.github.com/repos/semilin/km_metric_data/contents/",
-    )?;
-    download_repo(
-        &data_dir.join("corpora"),
-        "https://api.github.com/repos/semilin/km_corpora/contents/",
-    )?;
-    Ok(())
+/// Where the default data repositories are listed from.
+const DEFAULT_BASE_URL: &str = "https://api.github.com/repos/semilin/";
+
+/// Remembers the hash of every file a fetch wrote, to tell files changed
+/// by the user from ones that are just outdated.
+const MANIFEST: &str = ".fetched.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DataKind {
+    Layouts,
+    Metrics,
+    Corpora,
+}
+
+impl DataKind {
+    /// The folder this kind of data is kept in, both in the data dir and
+    /// in local copies to bootstrap from.
+    pub fn folder(self) -> &'static str {
+        match self {
+            DataKind::Layouts => "layouts",
+            DataKind::Metrics => "metrics",
+            DataKind::Corpora => "corpora",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "layouts" => Some(DataKind::Layouts),
+            "metrics" => Some(DataKind::Metrics),
+            "corpora" => Some(DataKind::Corpora),
+            _ => None,
+        }
+    }
+
+    /// Whether a file is data of this kind, so that READMEs and the like
+    /// aren't copied along.
+    fn accepts(self, name: &str) -> bool {
+        let ext = Path::new(name).extension().and_then(OsStr::to_str);
+        match self {
+            DataKind::Layouts => ext == Some("json"),
+            DataKind::Metrics => ext == Some("metrics"),
+            DataKind::Corpora => ext == Some("corpus"),
+        }
+    }
+}
+
+/// Where a data source lists its files.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Location {
+    /// A folder listed through GitHub's contents API, like
+    /// `https://api.github.com/repos/<owner>/<repo>/contents/<folder>`.
+    GitHub { url: String },
+    /// A file listing the data files, relative to the index itself. It's
+    /// either a JSON list of `{"name", "url", "sha", "size"}` objects, of
+    /// which only `name` is needed, or one file name per line.
+    Index { url: String },
+    /// A local folder with the data files.
+    Directory { path: PathBuf },
+}
+
+impl Location {
+    /// Tells apart GitHub contents URLs, other URLs and paths.
+    pub fn parse(s: &str, expand_path: impl FnOnce(&str) -> Result<PathBuf>) -> Result<Self> {
+        Ok(if s.starts_with("https://api.github.com/") {
+            Location::GitHub { url: s.to_string() }
+        } else if s.starts_with("http://") || s.starts_with("https://") {
+            Location::Index { url: s.to_string() }
+        } else {
+            Location::Directory {
+                path: expand_path(s)?,
+            }
+        })
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::GitHub { url } => write!(f, "GitHub {}", url),
+            Location::Index { url } => write!(f, "index {}", url),
+            Location::Directory { path } => write!(f, "folder {}", path.display()),
+        }
+    }
+}
+
+/// A place `fetch-data` gets one kind of data from.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DataSource {
+    pub name: String,
+    pub target: DataKind,
+    pub location: Location,
+}
+
+impl DataSource {
+    /// The repositories keymui has always downloaded from.
+    pub fn defaults() -> Vec<Self> {
+        [
+            ("layouts", DataKind::Layouts, "km_layouts"),
+            ("metrics", DataKind::Metrics, "km_metric_data"),
+            ("corpora", DataKind::Corpora, "km_corpora"),
+        ]
+        .into_iter()
+        .map(|(name, target, repo)| DataSource {
+            name: name.to_string(),
+            target,
+            location: Location::GitHub {
+                url: format!("{}{}/contents/", DEFAULT_BASE_URL, repo),
+            },
+        })
+        .collect()
+    }
+}
+
+/// Where to get data from.
+#[derive(Debug, Clone)]
+pub enum FetchSource {
+    /// Configured data sources.
+    Sources(Vec<DataSource>),
+    /// A directory with `layouts`, `metrics` and `corpora` folders.
+    Directory(PathBuf),
+    /// A zip archive with the same folders, possibly below a top folder.
+    Archive(PathBuf),
+}
+
+impl FetchSource {
+    pub fn local(path: PathBuf) -> Self {
+        match path.is_dir() {
+            true => FetchSource::Directory(path),
+            false => FetchSource::Archive(path),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FetchSummary {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub unchanged: usize,
+    /// Files that were changed locally since they were fetched, which are
+    /// only replaced when overwriting.
+    pub modified: Vec<String>,
+    /// Files or listings that couldn't be fetched, with the reason.
+    pub failed: Vec<String>,
+}
+
+impl FetchSummary {
+    pub fn fetched(&self) -> usize {
+        self.added.len() + self.updated.len()
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = vec![
+            format!("{} added", self.added.len()),
+            format!("{} updated", self.updated.len()),
+            format!("{} unchanged", self.unchanged),
+        ];
+        if !self.modified.is_empty() {
+            parts.push(format!("{} modified locally", self.modified.len()));
+        }
+        if !self.failed.is_empty() {
+            parts.push(format!("{} failed", self.failed.len()));
+        }
+        format!("fetched data: {}", parts.join(", "))
+    }
+
+    pub fn details(&self) -> Option<String> {
+        let mut sections = vec![];
+        let mut section = |title: &str, names: &[String]| {
+            if !names.is_empty() {
+                sections.push(format!("{}:\n  {}", title, names.join("\n  ")));
+            }
+        };
+        section("added", &self.added);
+        section("updated", &self.updated);
+        section(
+            "modified locally, kept (run fetch-data overwrite to replace them)",
+            &self.modified,
+        );
+        section("failed", &self.failed);
+        (!sections.is_empty()).then(|| sections.join("\n\n"))
+    }
+}
+
+/// The hash git gives a file's contents, which GitHub lists as `sha`.
+fn blob_sha(bytes: &[u8]) -> String {
+    let mut hasher = sha1_smol::Sha1::new();
+    hasher.update(format!("blob {}\0", bytes.len()).as_bytes());
+    hasher.update(bytes);
+    hasher.digest().to_string()
+}
+
+/// Writes through a temporary file, so that an interrupted fetch never
+/// leaves a half-written file behind.
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let name = path.file_name().context("path has no file name")?;
+    let tmp = path.with_file_name(format!(".{}.tmp", name.to_string_lossy()));
+    fs::write(&tmp, bytes).with_context(|| format!("couldn't write {}", &tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("couldn't replace {}", &path.display()))
+}
+
+/// A file offered by a source.
+struct Remote {
+    name: String,
+    sha: String,
+    size: u64,
+}
+
+impl Remote {
+    /// Describes a file that's already been read.
+    fn of(name: String, bytes: &[u8]) -> Self {
+        Remote {
+            name,
+            sha: blob_sha(bytes),
+            size: bytes.len() as u64,
+        }
+    }
+}
+
+/// An entry of a JSON index file.
+#[derive(Deserialize, Debug)]
+struct IndexEntry {
+    name: String,
+    url: Option<String>,
+    sha: Option<String>,
+    size: Option<u64>,
+}
+
+/// The files of one target directory and what the last fetch wrote there.
+struct Target<'a> {
+    dir: &'a Path,
+    manifest: BTreeMap<String, String>,
+}
+
+impl<'a> Target<'a> {
+    fn open(dir: &'a Path) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("couldn't create {}", &dir.display()))?;
+        let manifest = fs::read_to_string(dir.join(MANIFEST))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Ok(Self { dir, manifest })
+    }
+
+    fn save(&self) -> Result<()> {
+        write_atomic(
+            &self.dir.join(MANIFEST),
+            serde_json::to_string_pretty(&self.manifest)?.as_bytes(),
+        )
+    }
+
+    /// Brings one file up to date. `fetch` is only called when the local
+    /// file is missing or outdated.
+    fn install(
+        &mut self,
+        remote: &Remote,
+        overwrite: bool,
+        summary: &mut FetchSummary,
+        fetch: impl FnOnce() -> Result<Vec<u8>>,
+    ) {
+        let path = self.dir.join(&remote.name);
+        let local = fs::read(&path).ok();
+        if let Some(local) = &local {
+            let local_sha = blob_sha(local);
+            if local.len() as u64 == remote.size && local_sha == remote.sha {
+                self.manifest.insert(remote.name.clone(), local_sha);
+                summary.unchanged += 1;
+                return;
+            }
+            let untouched = self.manifest.get(&remote.name) == Some(&local_sha);
+            if !untouched && !overwrite {
+                summary.modified.push(remote.name.clone());
+                return;
+            }
+        }
+        let written = fetch().and_then(|bytes| {
+            if bytes.len() as u64 != remote.size || blob_sha(&bytes) != remote.sha {
+                return Err(anyhow!("downloaded contents don't match the listing"));
+            }
+            write_atomic(&path, &bytes)
+        });
+        match (written, local) {
+            (Ok(()), None) => summary.added.push(remote.name.clone()),
+            (Ok(()), Some(_)) => summary.updated.push(remote.name.clone()),
+            (Err(e), _) => {
+                summary.failed.push(format!("{}: {:#}", remote.name, e));
+                return;
+            }
+        }
+        self.manifest
+            .insert(remote.name.clone(), remote.sha.clone());
+    }
+}
+
+struct FetchJob {
+    source: FetchSource,
+    targets: Vec<(DataKind, PathBuf)>,
+    overwrite: bool,
 }
 
 fn get(url: &str) -> minreq::Request {
@@ -32,16 +330,606 @@ fn get(url: &str) -> minreq::Request {
         .with_timeout(8)
 }
 
-pub fn download_repo(directory: &Path, url: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let resp = get(url).send();
-    let data = resp?.json::<Vec<GithubFileData>>()?;
+/// Sends a GET request, treating error statuses as failures so that an
+/// error page is never taken for a data file.
+fn request(url: &str) -> Result<minreq::Response> {
+    let response = get(url)
+        .send()
+        .with_context(|| format!("couldn't reach {}", url))?;
+    match response.status_code {
+        200..=299 => Ok(response),
+        code => Err(anyhow!(
+            "{} answered {} {}",
+            url,
+            code,
+            response.reason_phrase
+        )),
+    }
+}
+
+fn download(url: &str) -> Result<Vec<u8>> {
+    Ok(request(url)?.into_bytes())
+}
+
+impl FetchJob {
+    fn run(&self) -> Result<FetchSummary> {
+        let mut summary = FetchSummary::default();
+        match &self.source {
+            FetchSource::Sources(sources) => {
+                for source in sources {
+                    self.fetch_into(
+                        source.target,
+                        &source.name,
+                        &mut summary,
+                        |target, summary| self.fetch_source(source, target, summary),
+                    )?;
+                }
+            }
+            FetchSource::Directory(path) | FetchSource::Archive(path) => {
+                let archive = matches!(self.source, FetchSource::Archive(_));
+                let mut found = false;
+                for (kind, _) in &self.targets {
+                    found |=
+                        self.fetch_into(*kind, kind.folder(), &mut summary, |target, summary| {
+                            match archive {
+                                true => self.extract_archive(path, *kind, target, summary),
+                                false => self.copy_folder(path, *kind, target, summary),
+                            }
+                        })?;
+                }
+                if !found {
+                    return Err(anyhow!(
+                        "{} has no layouts, metrics or corpora folder",
+                        path.display()
+                    ));
+                }
+            }
+        }
+        if summary.fetched() == 0 && summary.unchanged == 0 && !summary.failed.is_empty() {
+            return Err(anyhow!(
+                "nothing could be fetched:\n{}",
+                summary.failed.join("\n")
+            ));
+        }
+        Ok(summary)
+    }
+
+    /// Runs `fetch` against the directory of `kind` and saves what it
+    /// wrote. Failures of the whole source are added to the summary under
+    /// `label`.
+    fn fetch_into(
+        &self,
+        kind: DataKind,
+        label: &str,
+        summary: &mut FetchSummary,
+        fetch: impl FnOnce(&mut Target, &mut FetchSummary) -> Result<bool>,
+    ) -> Result<bool> {
+        let dir = self
+            .targets
+            .iter()
+            .find(|(k, _)| *k == kind)
+            .map(|(_, dir)| dir)
+            .context("no directory for this kind of data")?;
+        let mut target = Target::open(dir)?;
+        let found = fetch(&mut target, summary).unwrap_or_else(|e| {
+            summary.failed.push(format!("{}: {:#}", label, e));
+            false
+        });
+        target
+            .save()
+            .with_context(|| format!("couldn't save the fetch manifest of {}", &dir.display()))?;
+        Ok(found)
+    }
+
+    fn fetch_source(
+        &self,
+        source: &DataSource,
+        target: &mut Target,
+        summary: &mut FetchSummary,
+    ) -> Result<bool> {
+        match &source.location {
+            Location::GitHub { url } => self.fetch_github(url, source.target, target, summary)?,
+            Location::Index { url } => self.fetch_index(url, source.target, target, summary)?,
+            Location::Directory { path } => {
+                self.copy_files(path, source.target, target, summary)?
+            }
+        }
+        Ok(true)
+    }
+
+    fn fetch_github(
+        &self,
+        url: &str,
+        kind: DataKind,
+        target: &mut Target,
+        summary: &mut FetchSummary,
+    ) -> Result<()> {
+        let listing = request(url)?;
+        let files: Vec<GithubFileData> = listing.json().context("unexpected listing")?;
+        for file in files.iter().filter(|f| kind.accepts(&f.name)) {
+            let Some(url) = &file.download_url else {
+                continue;
+            };
+            let remote = Remote {
+                name: file.name.clone(),
+                sha: file.sha.clone(),
+                size: file.size,
+            };
+            target.install(&remote, self.overwrite, summary, || download(url));
+        }
+        Ok(())
+    }
+
+    fn fetch_index(
+        &self,
+        url: &str,
+        kind: DataKind,
+        target: &mut Target,
+        summary: &mut FetchSummary,
+    ) -> Result<()> {
+        let index = request(url)?;
+        let text = index.as_str().context("the index isn't text")?;
+        let entries: Vec<IndexEntry> = serde_json::from_str(text).unwrap_or_else(|_| {
+            text.lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(|name| IndexEntry {
+                    name: name.to_string(),
+                    url: None,
+                    sha: None,
+                    size: None,
+                })
+                .collect()
+        });
+        // file names are relative to the index
+        let base = &url[..url.rfind('/').map_or(url.len(), |i| i + 1)];
+        for entry in entries.into_iter().filter(|e| kind.accepts(&e.name)) {
+            let file_url = entry
+                .url
+                .unwrap_or_else(|| format!("{}{}", base, entry.name));
+            let name = Path::new(&entry.name)
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            match (entry.sha, entry.size) {
+                (Some(sha), Some(size)) => {
+                    let remote = Remote { name, sha, size };
+                    target.install(&remote, self.overwrite, summary, || download(&file_url));
+                }
+                // without a hash, the file has to be fetched to compare it
+                _ => match download(&file_url) {
+                    Ok(bytes) => {
+                        let remote = Remote::of(name, &bytes);
+                        target.install(&remote, self.overwrite, summary, || Ok(bytes));
+                    }
+                    Err(e) => summary.failed.push(format!("{}: {:#}", name, e)),
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies the folder of `kind` in a bootstrap directory, if it has one.
+    fn copy_folder(
+        &self,
+        path: &Path,
+        kind: DataKind,
+        target: &mut Target,
+        summary: &mut FetchSummary,
+    ) -> Result<bool> {
+        let src = path.join(kind.folder());
+        if !src.is_dir() {
+            return Ok(false);
+        }
+        self.copy_files(&src, kind, target, summary)?;
+        Ok(true)
+    }
 
-    for filedata in data {
-        println!("downloading {}", filedata.name);
-        if let Ok(contents) = get(&filedata.download_url).send() {
-            let result = fs::write(directory.join(filedata.name), contents.as_bytes());
-            println!("{:?}", result);
+    fn copy_files(
+        &self,
+        src: &Path,
+        kind: DataKind,
+        target: &mut Target,
+        summary: &mut FetchSummary,
+    ) -> Result<()> {
+        for entry in
+            fs::read_dir(src).with_context(|| format!("couldn't read {}", &src.display()))?
+        {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !kind.accepts(&name) {
+                continue;
+            }
+            match fs::read(entry.path()) {
+                Ok(bytes) => {
+                    let remote = Remote::of(name, &bytes);
+                    target.install(&remote, self.overwrite, summary, || Ok(bytes));
+                }
+                Err(e) => summary.failed.push(format!("{}: {}", name, e)),
+            }
+        }
+        Ok(())
+    }
+
+    fn extract_archive(
+        &self,
+        path: &Path,
+        kind: DataKind,
+        target: &mut Target,
+        summary: &mut FetchSummary,
+    ) -> Result<bool> {
+        let file =
+            File::open(path).with_context(|| format!("couldn't open {}", &path.display()))?;
+        let mut archive = zip::ZipArchive::new(file)
+            .with_context(|| format!("{} isn't a zip archive", &path.display()))?;
+        let mut found = false;
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            let Some(entry_path) = entry.enclosed_name().map(|p| p.to_path_buf()) else {
+                continue;
+            };
+            // the file's folder tells what kind of data it is
+            let folder = entry_path
+                .parent()
+                .and_then(|p| p.components().next_back())
+                .and_then(|c| match c {
+                    Component::Normal(name) => name.to_str(),
+                    _ => None,
+                });
+            if entry.is_dir() || folder != Some(kind.folder()) {
+                continue;
+            }
+            found = true;
+            let name = entry_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            if !kind.accepts(&name) {
+                continue;
+            }
+            let mut bytes = vec![];
+            match entry.read_to_end(&mut bytes) {
+                Ok(_) => {
+                    let remote = Remote::of(name, &bytes);
+                    target.install(&remote, self.overwrite, summary, || Ok(bytes));
+                }
+                Err(e) => summary.failed.push(format!("{}: {}", name, e)),
+            }
+        }
+        Ok(found)
+    }
+}
+
+impl Keymui {
+    /// The directory each kind of data is kept in.
+    pub fn data_targets(&self) -> Vec<(DataKind, PathBuf)> {
+        vec![
+            (DataKind::Layouts, self.layouts_dir()),
+            (DataKind::Metrics, self.metric_data_dir()),
+            (DataKind::Corpora, self.corpora_dir()),
+        ]
+    }
+
+    /// How many layouts, metric data sets and corpora are loaded.
+    pub fn data_status(&self) -> Vec<(DataKind, usize)> {
+        vec![
+            (DataKind::Layouts, self.layouts.len()),
+            (DataKind::Metrics, self.metric_lists.len()),
+            (DataKind::Corpora, self.corpora.len()),
+        ]
+    }
+
+    /// Explains what's missing to analyze layouts and how to get it, or
+    /// `None` when every kind of data is there.
+    pub fn missing_data_hint(&self) -> Option<String> {
+        let missing: Vec<&str> = self
+            .data_status()
+            .into_iter()
+            .filter(|(_, count)| *count == 0)
+            .map(|(kind, _)| kind.folder())
+            .collect();
+        if missing.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "no {} found. Run fetch-data to download them, or fetch-data <folder or .zip> \
+             to copy them from a local copy when offline.",
+            missing.join(" or ")
+        ))
+    }
+
+    /// Logs how much of each kind of data there is.
+    pub fn report_data_status(&mut self) {
+        let status = self
+            .data_status()
+            .into_iter()
+            .map(|(kind, count)| format!("{}: {}", kind.folder(), count))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let sources = self
+            .config
+            .data_sources
+            .iter()
+            .map(|s| format!("  {}: {} from {}", s.name, s.target.folder(), s.location))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let hint = self.missing_data_hint();
+        let level = match hint {
+            Some(_) => Level::Warning,
+            None => Level::Info,
         };
+        let details = match hint {
+            Some(hint) => format!("{}\n\ndata sources:\n{}", hint, sources),
+            None => format!("data sources:\n{}", sources),
+        };
+        self.log_entry(level, status, Some(details));
+    }
+
+    /// Fetches layouts, metrics and corpora in the background, reporting
+    /// back through [`Message::DataFetched`].
+    /// Files changed locally are only replaced with `overwrite`.
+    pub fn start_fetch(&mut self, source: FetchSource, overwrite: bool) -> Command<Message> {
+        let job = FetchJob {
+            source,
+            targets: self.data_targets(),
+            overwrite,
+        };
+        if self.headless {
+            let result = job.run().map_err(|e| format!("{:#}", e));
+            return self.update(Message::DataFetched(result));
+        }
+        let (sender, receiver) = oneshot::channel();
+        thread::spawn(move || {
+            let _ = sender.send(job.run().map_err(|e| format!("{:#}", e)));
+        });
+        Command::perform(
+            async move {
+                receiver
+                    .await
+                    .unwrap_or_else(|_| Err("the fetch was interrupted".to_string()))
+            },
+            Message::DataFetched,
+        )
+    }
+
+    /// Downloads the default data on first run, before anything is loaded.
+    pub fn fetch_defaults(&mut self) {
+        let result = FetchJob {
+            source: FetchSource::Sources(self.config.data_sources.clone()),
+            targets: self.data_targets(),
+            overwrite: false,
+        }
+        .run();
+        self.log_fetch(result.map_err(|e| format!("{:#}", e)));
+    }
+
+    fn log_fetch(&mut self, result: Result<FetchSummary, String>) {
+        match result {
+            Ok(summary) => {
+                let level = match summary.failed.is_empty() && summary.modified.is_empty() {
+                    true => Level::Info,
+                    false => Level::Warning,
+                };
+                self.log_entry(level, summary.describe(), summary.details());
+            }
+            Err(e) => self.log_entry(Level::Error, "couldn't fetch data", Some(e)),
+        }
+    }
+
+    /// Logs the outcome of a fetch and loads what it brought in.
+    pub fn data_fetched(&mut self, result: Result<FetchSummary, String>) {
+        self.log_fetch(result);
+        if let Err(e) = self.load_layouts() {
+            self.error("couldn't load layouts", &e);
+        }
+        if let Err(e) = self.set_metric_list() {
+            self.error("couldn't list metrics", &e);
+        }
+        if let Err(e) = self.set_corpus_list() {
+            self.error("couldn't list corpora", &e);
+        }
+        if self.metric_context.is_none() {
+            self.current_layout = self
+                .current_layout
+                .take()
+                .or_else(|| self.layouts.keys().next().cloned());
+            self.current_metrics = self
+                .current_metrics
+                .take()
+                .or_else(|| self.metric_lists.keys().next().cloned());
+            self.current_corpus = self
+                .current_corpus
+                .take()
+                .or_else(|| self.corpora.keys().next().cloned());
+            if let Err(e) = self.load_data() {
+                self.error("couldn't load the analysis data", &e);
+            }
+        }
+        self.refresh_comparison();
+        self.report_problems();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::process;
+
+    /// Serves `files` over HTTP on a free local port until the test ends
+    /// and returns the base URL. Other paths answer 404.
+    fn serve(files: Vec<(&'static str, Vec<u8>)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    return;
+                };
+                let mut request = vec![];
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    match stream.read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&request);
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+                let (status, body) = match files.iter().find(|(name, _)| path == *name) {
+                    Some((_, body)) => ("200 OK", body.clone()),
+                    None => ("404 Not Found", vec![]),
+                };
+                let head = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(&body);
+            }
+        });
+        url
+    }
+
+    /// An empty folder of its own for every test.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("keymui-fetch-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn fetch(url: &str, dir: &Path, overwrite: bool) -> Result<FetchSummary> {
+        FetchJob {
+            source: FetchSource::Sources(vec![DataSource {
+                name: "test".to_string(),
+                target: DataKind::Layouts,
+                location: Location::Index {
+                    url: format!("{}index", url),
+                },
+            }]),
+            targets: vec![(DataKind::Layouts, dir.to_path_buf())],
+            overwrite,
+        }
+        .run()
+    }
+
+    fn listing(name: &str, contents: &[u8]) -> Vec<u8> {
+        format!(
+            r#"[{{"name": "{}", "sha": "{}", "size": {}}}]"#,
+            name,
+            blob_sha(contents),
+            contents.len()
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn blob_sha_matches_git() {
+        // git hash-object of a file containing "hello\n"
+        assert_eq!(
+            blob_sha(b"hello\n"),
+            "ce013625030ba8dba906f756967f9e9ca394464a"
+        );
+    }
+
+    #[test]
+    fn new_files_are_installed() {
+        let dir = scratch_dir("install");
+        let url = serve(vec![
+            ("/index", b"# layouts\na.json\nREADME.md\n".to_vec()),
+            ("/a.json", b"{}".to_vec()),
+            ("/README.md", b"not data".to_vec()),
+        ]);
+        let summary = fetch(&url, &dir, false).unwrap();
+        assert_eq!(summary.added, ["a.json"]);
+        assert_eq!(fs::read(dir.join("a.json")).unwrap(), b"{}");
+        assert!(!dir.join("README.md").exists());
+
+        let summary = fetch(&url, &dir, false).unwrap();
+        assert!(summary.added.is_empty());
+        assert_eq!(summary.unchanged, 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn files_matching_the_listing_are_not_downloaded() {
+        let dir = scratch_dir("skip");
+        fs::write(dir.join("a.json"), b"{}").unwrap();
+        // a.json itself isn't served, so downloading it would fail
+        let url = serve(vec![("/index", listing("a.json", b"{}"))]);
+        let summary = fetch(&url, &dir, false).unwrap();
+        assert_eq!(summary.unchanged, 1);
+        assert!(summary.failed.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn outdated_files_are_updated() {
+        let dir = scratch_dir("update");
+        let old = serve(vec![
+            ("/index", b"a.json".to_vec()),
+            ("/a.json", b"1".to_vec()),
+        ]);
+        fetch(&old, &dir, false).unwrap();
+        let new = serve(vec![
+            ("/index", b"a.json".to_vec()),
+            ("/a.json", b"2".to_vec()),
+        ]);
+        let summary = fetch(&new, &dir, false).unwrap();
+        assert_eq!(summary.updated, ["a.json"]);
+        assert_eq!(fs::read(dir.join("a.json")).unwrap(), b"2");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn local_changes_are_kept_unless_overwriting() {
+        let dir = scratch_dir("keep");
+        let old = serve(vec![
+            ("/index", b"a.json".to_vec()),
+            ("/a.json", b"1".to_vec()),
+        ]);
+        fetch(&old, &dir, false).unwrap();
+        fs::write(dir.join("a.json"), b"mine").unwrap();
+
+        let new = serve(vec![
+            ("/index", b"a.json".to_vec()),
+            ("/a.json", b"2".to_vec()),
+        ]);
+        let summary = fetch(&new, &dir, false).unwrap();
+        assert_eq!(summary.modified, ["a.json"]);
+        assert_eq!(fs::read(dir.join("a.json")).unwrap(), b"mine");
+
+        let summary = fetch(&new, &dir, true).unwrap();
+        assert_eq!(summary.updated, ["a.json"]);
+        assert_eq!(fs::read(dir.join("a.json")).unwrap(), b"2");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn broken_downloads_are_not_installed() {
+        let dir = scratch_dir("broken");
+        let url = serve(vec![
+            ("/index", listing("a.json", b"{}")),
+            ("/a.json", b"{ truncated".to_vec()),
+        ]);
+        // nothing else was fetched, so the whole fetch fails
+        assert!(fetch(&url, &dir, false).is_err());
+        assert!(!dir.join("a.json").exists());
+
+        let url = serve(vec![
+            ("/index", b"a.json\nb.json".to_vec()),
+            ("/b.json", b"{}".to_vec()),
+        ]);
+        let summary = fetch(&url, &dir, false).unwrap();
+        assert_eq!(summary.added, ["b.json"]);
+        assert_eq!(summary.failed.len(), 1);
+        assert!(summary.failed[0].starts_with("a.json: "));
+        assert!(!dir.join("a.json").exists());
+        fs::remove_dir_all(&dir).unwrap();
     }
-    Ok(())
 }
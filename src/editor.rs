@@ -0,0 +1,496 @@
+use crate::layout_display::LayoutDisplay;
+use crate::layout_import::Import;
+use crate::log::Level;
+use crate::{Keymui, Message};
+use color_eyre::eyre::{anyhow, Context, ContextCompat, Result};
+use iced::theme;
+use iced::widget::{
+    button, column, container, pick_list, row, scrollable, text, text_input, Canvas,
+};
+use iced::{Application, Element, Length};
+use kc::{Corpus, Swap};
+use km::{LayoutData, MetricContext, MetricData};
+use serde_json::json;
+use std::fs;
+
+/// What a new layout starts out as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditorStart {
+    /// Every key empty.
+    Blank,
+    /// A copy of an existing layout.
+    Layout(String),
+}
+
+/// A layout being made in the editor. It isn't part of the loaded layouts
+/// until it's saved.
+pub struct Editor {
+    /// The metric data whose keyboard the layout is made for.
+    pub metrics: String,
+    pub name: String,
+    /// Comma separated, as typed.
+    pub authors: String,
+    /// The layout being imported, when the editor was opened for one.
+    pub import: Option<Import>,
+    context: MetricContext,
+    display: LayoutDisplay,
+    stats: Vec<f32>,
+    keyboard_size: usize,
+}
+
+impl Editor {
+    fn new(metrics: String, mut context: MetricContext, blank: bool, authors: String) -> Self {
+        context.keyboard.process_combo_indexes();
+        if blank {
+            let empty = context.analyzer.corpus.corpus_char('\0');
+            context.layout.0.iter_mut().for_each(|c| *c = empty);
+        }
+        let mut editor = Self {
+            metrics,
+            name: String::new(),
+            authors,
+            import: None,
+            keyboard_size: context.keyboard.keys.map.iter().flatten().count(),
+            display: LayoutDisplay::editor(&context),
+            stats: vec![0.0; context.analyzer.data.metrics.len()],
+            context,
+        };
+        editor.refresh();
+        editor
+    }
+
+    fn refresh(&mut self) {
+        self.stats.iter_mut().for_each(|s| *s = 0.0);
+        self.context
+            .analyzer
+            .recalc_stats(&mut self.stats, &self.context.layout);
+        self.display.update_keys(&self.context, 0);
+        self.display.redraw();
+    }
+
+    /// Puts a character on a layout position. A character that is already
+    /// on another key moves over, and the old character takes its place.
+    /// `'\0'` clears the position.
+    pub fn place(&mut self, position: usize, c: char) -> Result<()> {
+        let corpus = &self.context.analyzer.corpus;
+        let idx = corpus.corpus_char(c);
+        let empty = corpus.corpus_char('\0');
+        if c != '\0' && corpus.uncorpus_unigram(idx) == '\0' {
+            return Err(anyhow!("{} isn't in the corpus", c));
+        }
+        place_index(&mut self.context.layout.0, position, idx, empty)?;
+        self.refresh();
+        Ok(())
+    }
+
+    pub fn swap(&mut self, a: usize, b: usize) {
+        let len = self.context.layout.0.len();
+        if a < len && b < len {
+            self.context.layout.swap(&Swap::new(a, b));
+            self.refresh();
+        }
+    }
+
+    /// Sets the output of a combo from what was typed into its input. The
+    /// last character counts, so typing over the old output replaces it.
+    pub fn set_combo(&mut self, combo: usize, input: &str) -> Result<()> {
+        self.place(self.keyboard_size + combo, combo_char(input))
+    }
+
+    /// How many keys each row of the keyboard has.
+    pub fn row_widths(&self) -> Vec<usize> {
+        self.context
+            .keyboard
+            .keys
+            .map
+            .iter()
+            .map(|r| r.len())
+            .collect()
+    }
+
+    /// Puts the rows of the imported layout onto the keyboard with the
+    /// import's mapping, replacing every key. Returns the characters that
+    /// couldn't be placed.
+    pub fn apply_import(&mut self) -> Vec<char> {
+        let Some(import) = &self.import else {
+            return vec![];
+        };
+        let widths = self.row_widths();
+        let corpus = &self.context.analyzer.corpus;
+        let empty = corpus.corpus_char('\0');
+        let layout = &mut self.context.layout.0;
+        layout.iter_mut().for_each(|c| *c = empty);
+        let mut skipped = vec![];
+        for (position, c) in import.mapping.positions(&import.layout.rows, &widths) {
+            let idx = corpus.corpus_char(c);
+            match position {
+                Some(p) if corpus.uncorpus_unigram(idx) != '\0' && !layout.contains(&idx) => {
+                    layout[p] = idx
+                }
+                _ => skipped.push(c),
+            }
+        }
+        self.refresh();
+        skipped
+    }
+
+    /// Corpus characters making up at least `min_share` percent of the
+    /// corpus that have no key, commonest first.
+    pub fn missing_chars(&self, min_share: f32) -> Vec<(char, f32)> {
+        missing_chars(
+            &self.context.analyzer.corpus,
+            &self.context.layout.0,
+            min_share,
+        )
+    }
+
+    fn combo_output(&self, combo: usize) -> char {
+        let corpus = &self.context.analyzer.corpus;
+        corpus.uncorpus_unigram(self.context.layout.0[self.keyboard_size + combo])
+    }
+}
+
+/// Puts the corpus index `idx` on `position` of `keys`, swapping it with
+/// the key it was on, if any. Any number of keys can be `empty`.
+fn place_index(keys: &mut [usize], position: usize, idx: usize, empty: usize) -> Result<()> {
+    if position >= keys.len() {
+        return Err(anyhow!("there's no key at position {}", position));
+    }
+    match keys.iter().position(|x| *x == idx) {
+        Some(other) if idx != empty => keys.swap(position, other),
+        _ => keys[position] = idx,
+    }
+    Ok(())
+}
+
+/// The character a combo's input stands for: the last one typed, or
+/// `'\0'` when the input was cleared.
+fn combo_char(input: &str) -> char {
+    input.chars().last().unwrap_or('\0')
+}
+
+fn missing_chars(corpus: &Corpus, keys: &[usize], min_share: f32) -> Vec<(char, f32)> {
+    let total = corpus.chars.iter().sum::<u32>().max(1) as f32;
+    let mut missing: Vec<(char, f32)> = corpus
+        .chars
+        .iter()
+        .enumerate()
+        .map(|(i, count)| (i, corpus.uncorpus_unigram(i), 100.0 * *count as f32 / total))
+        .filter(|(i, c, share)| *c != '\0' && *share >= min_share && !keys.contains(i))
+        .map(|(_, c, share)| (c, share))
+        .collect();
+    missing.sort_by(|a, b| b.1.total_cmp(&a.1));
+    missing
+}
+
+/// Splits a comma separated list of authors.
+pub fn parse_authors(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// A layout with a key for every key and combo of the metric data's
+/// keyboard, all of them empty.
+fn blank_layout(data: &MetricData) -> Result<LayoutData> {
+    let keys: Vec<Vec<char>> = data
+        .keyboard
+        .keys
+        .map
+        .iter()
+        .map(|row| vec!['\0'; row.len()])
+        .collect();
+    let combos = vec!['\0'; data.keyboard.combos.len()];
+    serde_json::from_value(json!({
+        "name": "",
+        "authors": [],
+        "keys": keys,
+        "combos": combos,
+    }))
+    .context("couldn't make a blank layout")
+}
+
+/// Shows characters the way the Layout pane does, with visible spaces.
+fn show(c: char) -> String {
+    match c {
+        '\0' => String::new(),
+        ' ' => "␣".to_string(),
+        _ => c.to_string(),
+    }
+}
+
+impl Keymui {
+    /// Opens the editor for the keyboard of a metric data set, starting
+    /// from a copy of a layout that fits the keyboard, or from a layout with
+    /// as many empty keys as the keyboard has.
+    pub fn open_editor(&mut self, start: EditorStart, metrics: String) -> Result<()> {
+        let metrics_path = self
+            .metric_lists
+            .get(&metrics)
+            .context("metric data doesn't exist")?;
+        let metric_bytes = fs::read(metrics_path).context("couldn't read metrics file")?;
+        let metric_data: MetricData =
+            rmp_serde::from_slice(&metric_bytes).context("couldn't deserialize metrics")?;
+        let corpus = self.current_corpus.as_ref().context("no corpus selected")?;
+        let corpus_path = self.corpora.get(corpus).context("corpus doesn't exist")?;
+        let corpus_bytes = fs::read(corpus_path).context("couldn't read corpus file")?;
+        let corpus = rmp_serde::from_slice(&corpus_bytes).context("couldn't deserialize corpus")?;
+
+        let blank;
+        let layout = match &start {
+            EditorStart::Layout(name) => self.layouts.get(name).context("layout doesn't exist")?,
+            EditorStart::Blank => {
+                blank = blank_layout(&metric_data)?;
+                &blank
+            }
+        };
+        let context =
+            MetricContext::new(layout, metric_data, corpus).with_context(|| match &start {
+                EditorStart::Layout(name) => {
+                    format!("layout {} doesn't fit the keyboard of {}", name, metrics)
+                }
+                EditorStart::Blank => format!("couldn't lay out the keyboard of {}", metrics),
+            })?;
+
+        let authors = self.config.authors.join(", ");
+        self.editor = Some(Editor::new(
+            metrics,
+            context,
+            start == EditorStart::Blank,
+            authors,
+        ));
+        Ok(())
+    }
+
+    /// Checks the editor's layout and saves it to the data dir, then
+    /// closes the editor and opens the new layout.
+    pub fn save_editor(&mut self) -> Result<()> {
+        let editor = self.editor.as_ref().context("the editor isn't open")?;
+        let name = editor.name.trim();
+        if name.is_empty() {
+            return Err(anyhow!("the layout needs a name"));
+        }
+        let key = name.to_lowercase().replace(' ', "-");
+        if self.layouts.contains_key(&key) {
+            return Err(anyhow!("a layout named {} already exists", key));
+        }
+        let missing = editor.missing_chars(self.config.required_char_share);
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "{} characters have no key: {}",
+                missing.len(),
+                missing
+                    .iter()
+                    .map(|(c, _)| show(*c))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            ));
+        }
+
+        let data = editor
+            .context
+            .layout_data()
+            .name(name.to_string())
+            .authors(parse_authors(&editor.authors));
+        let path = self.layouts_dir().join(format!("{}.json", key));
+        let s = serde_json::to_string_pretty(&data)?;
+        fs::write(&path, s)
+            .with_context(|| format!("couldn't write layout to {}", path.display()))?;
+
+        let editor = self.editor.take().expect("checked above");
+        self.info(format!("saved layout to {}", path.display()));
+        self.load_layouts()?;
+        self.current_layout = Some(key);
+        self.current_metrics = Some(editor.metrics);
+        self.load_data()?;
+        self.refresh_comparison();
+        Ok(())
+    }
+
+    /// Takes the place of the Layout pane while the editor is open.
+    pub fn editor_view(&self) -> Element<Message> {
+        let Some(editor) = &self.editor else {
+            return container(text("the editor isn't open")).into();
+        };
+        let ctx = &editor.context;
+        let missing = editor.missing_chars(self.config.required_char_share);
+        let check = if missing.is_empty() {
+            text("every required character has a key")
+        } else {
+            text(format!(
+                "no key yet: {}",
+                missing
+                    .iter()
+                    .map(|(c, share)| format!("{} {:.2}%", show(*c), share))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ))
+            .style(
+                Level::Warning
+                    .color(&self.theme().palette())
+                    .unwrap_or_default(),
+            )
+        };
+        let combos = ctx
+            .keyboard
+            .combo_indexes
+            .iter()
+            .enumerate()
+            .map(|(idx, combo)| {
+                let keys: String = combo
+                    .iter()
+                    .map(|i| show(ctx.analyzer.corpus.uncorpus_unigram(ctx.layout.0[*i])))
+                    .collect();
+                Element::from(
+                    row![
+                        text(keys).font(self.monospaced_font()).width(Length::Fill),
+                        text_input("output", &show(editor.combo_output(idx)))
+                            .on_input(move |s| Message::EditorComboChanged(idx, s))
+                            .width(Length::Fill),
+                    ]
+                    .spacing(8),
+                )
+            });
+        let stats = ctx
+            .metrics
+            .iter()
+            .zip(Self::percentages(ctx, &editor.stats))
+            .map(|(m, p)| format!("{} {}", m.short, self.format_stat(&m.short, p)))
+            .collect::<Vec<String>>()
+            .join("  ");
+
+        column![
+            row![
+                text(match editor.import {
+                    Some(_) => "Imported layout",
+                    None => "New layout",
+                })
+                .size(18)
+                .width(Length::Fill),
+                button("save").on_press(Message::SaveEditor),
+                button("cancel")
+                    .on_press(Message::CloseEditor)
+                    .style(theme::Button::Secondary),
+            ]
+            .spacing(8),
+            row![
+                text_input("name", &editor.name).on_input(Message::EditorNameChanged),
+                text_input("authors, comma separated", &editor.authors)
+                    .on_input(Message::EditorAuthorsChanged),
+                pick_list(
+                    self.metric_lists
+                        .keys()
+                        .map(|s| s.to_string())
+                        .collect::<Vec<String>>(),
+                    Some(editor.metrics.clone()),
+                    Message::EditorKeyboardSelected
+                ),
+            ]
+            .spacing(8),
+        ]
+        .push_maybe(
+            editor
+                .import
+                .as_ref()
+                .filter(|import| import.mappings.len() > 1)
+                .map(|import| {
+                    row![
+                        text("place the rows on"),
+                        pick_list(
+                            import.mappings.clone(),
+                            Some(import.mapping),
+                            Message::EditorMappingSelected
+                        ),
+                    ]
+                    .spacing(8)
+                }),
+        )
+        .push(text("click a key and type to place characters, backspace clears keys").size(12))
+        .push(
+            Canvas::new(&editor.display)
+                .width(Length::Fill)
+                .height(Length::Fill),
+        )
+        .push(check.size(12))
+        .push(text(stats).size(12).font(self.monospaced_font()))
+        .push(text("Combos").size(18))
+        .push(scrollable(column(combos).spacing(2)).height(Length::Fill))
+        .spacing(4)
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corpus() -> Corpus {
+        let mut corpus = Corpus::with_char_list(vec![vec!['a'], vec!['b'], vec!['c'], vec!['d']]);
+        for (c, count) in [('a', 50), ('b', 30), ('c', 15), ('d', 5)] {
+            let i = corpus.corpus_char(c);
+            corpus.chars[i] = count;
+        }
+        corpus
+    }
+
+    /// The characters on `keys`, with `_` for empty ones.
+    fn chars(corpus: &Corpus, keys: &[usize]) -> String {
+        keys.iter()
+            .map(|i| match corpus.uncorpus_unigram(*i) {
+                '\0' => '_',
+                c => c,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn placing_a_placed_char_moves_or_swaps_it() {
+        let corpus = corpus();
+        let [a, b, empty] = ['a', 'b', '\0'].map(|c| corpus.corpus_char(c));
+        let mut keys = vec![a, b, empty];
+        place_index(&mut keys, 2, a, empty).unwrap();
+        assert_eq!(chars(&corpus, &keys), "_ba");
+        place_index(&mut keys, 1, a, empty).unwrap();
+        assert_eq!(chars(&corpus, &keys), "_ab");
+        assert!(place_index(&mut keys, 3, a, empty).is_err());
+        assert_eq!(chars(&corpus, &keys), "_ab");
+    }
+
+    #[test]
+    fn placing_nothing_clears_a_key() {
+        let corpus = corpus();
+        let [a, b, empty] = ['a', 'b', '\0'].map(|c| corpus.corpus_char(c));
+        let mut keys = vec![a, b, empty];
+        place_index(&mut keys, 0, empty, empty).unwrap();
+        assert_eq!(chars(&corpus, &keys), "_b_");
+    }
+
+    #[test]
+    fn combos_take_the_last_typed_char() {
+        assert_eq!(combo_char("e"), 'e');
+        assert_eq!(combo_char("eé"), 'é');
+        assert_eq!(combo_char(""), '\0');
+    }
+
+    #[test]
+    fn missing_chars_are_common_chars_without_a_key() {
+        let corpus = corpus();
+        let keys = ['a', '\0'].map(|c| corpus.corpus_char(c));
+        assert_eq!(
+            missing_chars(&corpus, &keys, 0.0),
+            [('b', 30.0), ('c', 15.0), ('d', 5.0)]
+        );
+        assert_eq!(
+            missing_chars(&corpus, &keys, 10.0),
+            [('b', 30.0), ('c', 15.0)]
+        );
+    }
+
+    #[test]
+    fn authors_are_split_at_commas() {
+        assert_eq!(parse_authors(" semi,  Alt Dude ,,"), ["semi", "Alt Dude"]);
+        assert!(parse_authors(" , ").is_empty());
+    }
+}
@@ -0,0 +1,832 @@
+use crate::Keymui;
+use color_eyre::eyre::{anyhow, Context, ContextCompat, Result};
+use core::fmt;
+use km::MetricContext;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Qmk,
+    Zmk,
+    Kanata,
+    Kmonad,
+    Xkb,
+    Keylayout,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 6] = [
+        ExportFormat::Qmk,
+        ExportFormat::Zmk,
+        ExportFormat::Kanata,
+        ExportFormat::Kmonad,
+        ExportFormat::Xkb,
+        ExportFormat::Keylayout,
+    ];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.to_string() == s)
+    }
+
+    pub fn file_name(self, layout: &str) -> String {
+        match self {
+            ExportFormat::Qmk => format!("{}_keymap.c", layout),
+            ExportFormat::Zmk => format!("{}.keymap", layout),
+            ExportFormat::Kanata => format!("{}.kanata.kbd", layout),
+            ExportFormat::Kmonad => format!("{}.kmonad.kbd", layout),
+            ExportFormat::Xkb => format!("{}.xkb", layout),
+            ExportFormat::Keylayout => format!("{}.keylayout", layout),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportFormat::Qmk => write!(f, "qmk"),
+            ExportFormat::Zmk => write!(f, "zmk"),
+            ExportFormat::Kanata => write!(f, "kanata"),
+            ExportFormat::Kmonad => write!(f, "kmonad"),
+            ExportFormat::Xkb => write!(f, "xkb"),
+            ExportFormat::Keylayout => write!(f, "keylayout"),
+        }
+    }
+}
+
+/// A layout as firmware sees it: the character of every key, by row of
+/// the keyboard's key map, and the combos. Empty keys are `'\0'`.
+pub struct ExportedLayout {
+    pub name: String,
+    pub rows: Vec<Vec<char>>,
+    /// The layout positions pressed together, and the output.
+    pub combos: Vec<(Vec<usize>, char)>,
+}
+
+impl ExportedLayout {
+    pub fn new(name: &str, ctx: &MetricContext) -> Self {
+        let corpus = &ctx.analyzer.corpus;
+        let mut positions = ctx.layout.0.iter().map(|c| corpus.uncorpus_unigram(*c));
+        let rows: Vec<Vec<char>> = ctx
+            .keyboard
+            .keys
+            .map
+            .iter()
+            .map(|row| positions.by_ref().take(row.len()).collect())
+            .collect();
+        // combo outputs follow the keys
+        let combos = ctx
+            .keyboard
+            .combo_indexes
+            .iter()
+            .cloned()
+            .zip(positions)
+            .collect();
+        Self {
+            name: name.to_string(),
+            rows,
+            combos,
+        }
+    }
+
+    fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.rows.iter().flatten().copied()
+    }
+
+    /// Every key with its row and column in the key map.
+    fn keys(&self) -> impl Iterator<Item = (usize, usize, char)> + '_ {
+        self.rows
+            .iter()
+            .enumerate()
+            .flat_map(|(r, row)| row.iter().enumerate().map(move |(c, ch)| (r, c, *ch)))
+    }
+
+    fn char_at(&self, position: usize) -> char {
+        self.chars().nth(position).unwrap_or('\0')
+    }
+}
+
+/// Unshifted characters of a US keyboard's keys and their names in QMK,
+/// ZMK and kanata/KMonad. Letters and digits are left out.
+const KEY_NAMES: [(char, &str, &str, &str); 12] = [
+    (' ', "KC_SPC", "SPACE", "spc"),
+    (',', "KC_COMM", "COMMA", ","),
+    ('.', "KC_DOT", "DOT", "."),
+    ('/', "KC_SLSH", "FSLH", "/"),
+    (';', "KC_SCLN", "SEMI", ";"),
+    ('\'', "KC_QUOT", "SQT", "'"),
+    ('-', "KC_MINS", "MINUS", "-"),
+    ('=', "KC_EQL", "EQUAL", "="),
+    ('[', "KC_LBRC", "LBKT", "["),
+    (']', "KC_RBRC", "RBKT", "]"),
+    ('\\', "KC_BSLS", "BSLH", "\\"),
+    ('`', "KC_GRV", "GRAVE", "grv"),
+];
+
+/// Characters typed with shift on a US keyboard, by their unshifted key.
+const SHIFTED: [(char, char); 21] = [
+    ('1', '!'),
+    ('2', '@'),
+    ('3', '#'),
+    ('4', '$'),
+    ('5', '%'),
+    ('6', '^'),
+    ('7', '&'),
+    ('8', '*'),
+    ('9', '('),
+    ('0', ')'),
+    ('-', '_'),
+    ('=', '+'),
+    ('[', '{'),
+    (']', '}'),
+    ('\\', '|'),
+    (';', ':'),
+    ('\'', '"'),
+    (',', '<'),
+    ('.', '>'),
+    ('/', '?'),
+    ('`', '~'),
+];
+
+/// The US key a character is typed with, and whether it needs shift.
+fn us_key(c: char) -> Option<(char, bool)> {
+    if c.is_ascii_lowercase() || c.is_ascii_digit() || KEY_NAMES.iter().any(|k| k.0 == c) {
+        Some((c, false))
+    } else if c.is_ascii_uppercase() {
+        Some((c.to_ascii_lowercase(), true))
+    } else {
+        SHIFTED
+            .iter()
+            .find(|(_, shifted)| *shifted == c)
+            .map(|(key, _)| (*key, true))
+    }
+}
+
+/// What shift turns a character into, on the same key. Characters
+/// without a single uppercase character, like `ß`, stay the same.
+fn shifted(c: char) -> char {
+    if let Some((_, shifted)) = SHIFTED.iter().find(|(key, _)| *key == c) {
+        return *shifted;
+    }
+    let mut upper = c.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(upper), None) => upper,
+        _ => c,
+    }
+}
+
+fn qmk_keycode(c: char) -> String {
+    let Some((key, shift)) = us_key(c) else {
+        return match c {
+            '\0' => "KC_NO".to_string(),
+            // needs UNICODE_ENABLE
+            _ => format!("UC(0x{:04X})", c as u32),
+        };
+    };
+    let name = match KEY_NAMES.iter().find(|k| k.0 == key) {
+        Some(k) => k.1.to_string(),
+        None => format!("KC_{}", key.to_ascii_uppercase()),
+    };
+    match shift {
+        true => format!("S({})", name),
+        false => name,
+    }
+}
+
+fn zmk_binding(c: char) -> String {
+    let Some((key, shift)) = us_key(c) else {
+        return match c {
+            '\0' => "&none".to_string(),
+            _ => format!("&none /* {} */", c),
+        };
+    };
+    let name = match KEY_NAMES.iter().find(|k| k.0 == key) {
+        Some(k) => k.2.to_string(),
+        None if key.is_ascii_digit() => format!("N{}", key),
+        None => key.to_ascii_uppercase().to_string(),
+    };
+    match shift {
+        true => format!("&kp LS({})", name),
+        false => format!("&kp {}", name),
+    }
+}
+
+/// Key names of kanata and KMonad, which agree on the basic keys.
+fn kbd_key(c: char) -> Option<String> {
+    let (key, shift) = us_key(c)?;
+    let name = match KEY_NAMES.iter().find(|k| k.0 == key) {
+        Some(k) => k.3.to_string(),
+        None => key.to_string(),
+    };
+    Some(match shift {
+        true => format!("S-{}", name),
+        false => name,
+    })
+}
+
+fn xkb_keysym(c: char) -> String {
+    const NAMES: [(char, &str); 33] = [
+        (' ', "space"),
+        ('!', "exclam"),
+        ('"', "quotedbl"),
+        ('#', "numbersign"),
+        ('$', "dollar"),
+        ('%', "percent"),
+        ('&', "ampersand"),
+        ('\'', "apostrophe"),
+        ('(', "parenleft"),
+        (')', "parenright"),
+        ('*', "asterisk"),
+        ('+', "plus"),
+        (',', "comma"),
+        ('-', "minus"),
+        ('.', "period"),
+        ('/', "slash"),
+        (':', "colon"),
+        (';', "semicolon"),
+        ('<', "less"),
+        ('=', "equal"),
+        ('>', "greater"),
+        ('?', "question"),
+        ('@', "at"),
+        ('[', "bracketleft"),
+        ('\\', "backslash"),
+        (']', "bracketright"),
+        ('^', "asciicircum"),
+        ('_', "underscore"),
+        ('`', "grave"),
+        ('{', "braceleft"),
+        ('|', "bar"),
+        ('}', "braceright"),
+        ('~', "asciitilde"),
+    ];
+    match NAMES.iter().find(|(ch, _)| *ch == c) {
+        Some((_, name)) => name.to_string(),
+        None if c.is_ascii_alphanumeric() => c.to_string(),
+        None => format!("U{:04X}", c as u32),
+    }
+}
+
+/// The letter rows of a US ANSI keyboard, which kanata, KMonad, XKB and
+/// macOS name keys by. Rows of the key map are matched to them from the
+/// top, and a fourth row gets the space bar.
+const ANSI_ROWS: [&str; 3] = ["qwertyuiop[]\\", "asdfghjkl;'", "zxcvbnm,./"];
+
+/// macOS virtual key codes of [`ANSI_ROWS`].
+const MAC_CODES: [&[u16]; 3] = [
+    &[12, 13, 14, 15, 17, 16, 32, 34, 31, 35, 33, 30, 42],
+    &[0, 1, 2, 3, 5, 4, 38, 40, 37, 41, 39],
+    &[6, 7, 8, 9, 11, 45, 46, 43, 47, 44],
+];
+
+/// The ANSI key at a row and column of the key map, as the character it
+/// types on a US keyboard.
+fn ansi_key(row: usize, col: usize) -> Option<char> {
+    match row {
+        0..=2 => ANSI_ROWS[row].chars().nth(col),
+        3 if col == 0 => Some(' '),
+        _ => None,
+    }
+}
+
+fn mac_code(row: usize, col: usize) -> Option<u16> {
+    match row {
+        0..=2 => MAC_CODES[row].get(col).copied(),
+        3 if col == 0 => Some(49),
+        _ => None,
+    }
+}
+
+fn xkb_key_name(row: usize, col: usize) -> Option<String> {
+    match (row, col) {
+        (0, 12) => Some("BKSL".to_string()),
+        (0, 0..=11) => Some(format!("AD{:02}", col + 1)),
+        (1, 0..=10) => Some(format!("AC{:02}", col + 1)),
+        (2, 0..=9) => Some(format!("AB{:02}", col + 1)),
+        (3, 0) => Some("SPCE".to_string()),
+        _ => None,
+    }
+}
+
+fn show(c: char) -> String {
+    match c {
+        '\0' => "(empty)".to_string(),
+        ' ' => "space".to_string(),
+        _ => c.to_string(),
+    }
+}
+
+fn describe_combo(layout: &ExportedLayout, keys: &[usize], output: char) -> String {
+    let keys: Vec<String> = keys.iter().map(|p| show(layout.char_at(*p))).collect();
+    format!("{} -> {}", keys.join(" + "), show(output))
+}
+
+/// Keys that have no place on an ANSI keyboard, for a comment.
+fn unplaced(layout: &ExportedLayout) -> Vec<String> {
+    layout
+        .keys()
+        .filter(|(r, c, ch)| *ch != '\0' && ansi_key(*r, *c).is_none())
+        .map(|(_, _, ch)| show(ch))
+        .collect()
+}
+
+/// A keymap for QMK's `keymap.c`. Keys are in the order of the keyboard's
+/// key map, which is the order `LAYOUT` macros take them in.
+fn qmk(layout: &ExportedLayout) -> String {
+    let mut lines = vec![
+        format!("// {}, exported from keymui", layout.name),
+        "// use the LAYOUT macro of your keyboard".to_string(),
+    ];
+    if layout.chars().any(|c| qmk_keycode(c).starts_with("UC(")) {
+        lines.push("// UC() needs UNICODE_ENABLE = yes in rules.mk".to_string());
+    }
+    let rows: Vec<String> = layout
+        .rows
+        .iter()
+        .map(|row| {
+            let keys: Vec<String> = row.iter().map(|c| qmk_keycode(*c)).collect();
+            format!("    {}", keys.join(", "))
+        })
+        .collect();
+    lines.push("[0] = LAYOUT(".to_string());
+    lines.push(rows.join(",\n"));
+    lines.push("),".to_string());
+
+    if !layout.combos.is_empty() {
+        lines.push(String::new());
+        lines.push("// combos need COMBO_ENABLE = yes in rules.mk".to_string());
+        for (i, (keys, _)) in layout.combos.iter().enumerate() {
+            let keys: Vec<String> = keys
+                .iter()
+                .map(|p| qmk_keycode(layout.char_at(*p)))
+                .collect();
+            lines.push(format!(
+                "const uint16_t PROGMEM combo{}[] = {{{}, COMBO_END}};",
+                i,
+                keys.join(", ")
+            ));
+        }
+        lines.push("combo_t key_combos[] = {".to_string());
+        for (i, (_, output)) in layout.combos.iter().enumerate() {
+            lines.push(format!("    COMBO(combo{}, {}),", i, qmk_keycode(*output)));
+        }
+        lines.push("};".to_string());
+    }
+    lines.join("\n") + "\n"
+}
+
+/// A ZMK `.keymap` with the layout as its only layer. Combos refer to key
+/// positions, which count keys in the order of the key map.
+fn zmk(layout: &ExportedLayout) -> String {
+    let mut lines = vec![
+        format!("// {}, exported from keymui", layout.name),
+        "#include <behaviors.dtsi>".to_string(),
+        "#include <dt-bindings/zmk/keys.h>".to_string(),
+        String::new(),
+        "/ {".to_string(),
+    ];
+    if !layout.combos.is_empty() {
+        lines.push("    combos {".to_string());
+        lines.push("        compatible = \"zmk,combos\";".to_string());
+        for (i, (keys, output)) in layout.combos.iter().enumerate() {
+            let positions: Vec<String> = keys.iter().map(|p| p.to_string()).collect();
+            lines.push(String::new());
+            lines.push(format!("        combo_{} {{", i));
+            lines.push("            timeout-ms = <50>;".to_string());
+            lines.push(format!(
+                "            key-positions = <{}>;",
+                positions.join(" ")
+            ));
+            lines.push(format!(
+                "            bindings = <{}>;",
+                zmk_binding(*output)
+            ));
+            lines.push("        };".to_string());
+        }
+        lines.push("    };".to_string());
+        lines.push(String::new());
+    }
+    lines.push("    keymap {".to_string());
+    lines.push("        compatible = \"zmk,keymap\";".to_string());
+    lines.push(String::new());
+    lines.push("        default_layer {".to_string());
+    lines.push("            bindings = <".to_string());
+    for row in &layout.rows {
+        let keys: Vec<String> = row.iter().map(|c| zmk_binding(*c)).collect();
+        lines.push(format!("                {}", keys.join(" ")));
+    }
+    lines.push("            >;".to_string());
+    lines.push("        };".to_string());
+    lines.push("    };".to_string());
+    lines.push("};".to_string());
+    lines.join("\n") + "\n"
+}
+
+/// `defsrc` and `deflayer` blocks for kanata or KMonad, which share them.
+/// Only kanata has combos, as chords.
+fn kbd(layout: &ExportedLayout, kanata: bool) -> String {
+    let mut lines = vec![format!(";; {}, exported from keymui", layout.name)];
+    if !kanata {
+        lines.push(";; add a defcfg block with your input and output devices".to_string());
+    }
+    let mut src = vec![];
+    let mut layer = vec![];
+    let mut missing = vec![];
+    for (r, row) in layout.rows.iter().enumerate() {
+        let mut src_row = vec![];
+        let mut layer_row = vec![];
+        for (c, ch) in row.iter().enumerate() {
+            let Some(key) = ansi_key(r, c).and_then(kbd_key) else {
+                continue;
+            };
+            src_row.push(key);
+            layer_row.push(match ch {
+                '\0' => "XX".to_string(),
+                _ => match (kbd_key(*ch), kanata) {
+                    (Some(key), _) => key,
+                    (None, true) => format!("(unicode {})", ch),
+                    (None, false) => {
+                        missing.push(show(*ch));
+                        "XX".to_string()
+                    }
+                },
+            });
+        }
+        if !src_row.is_empty() {
+            src.push(format!("  {}", src_row.join(" ")));
+            layer.push(format!("  {}", layer_row.join(" ")));
+        }
+    }
+    let unplaced = unplaced(layout);
+    if !unplaced.is_empty() {
+        lines.push(format!(
+            ";; these keys have no place on an ANSI keyboard: {}",
+            unplaced.join(" ")
+        ));
+    }
+    if !missing.is_empty() {
+        lines.push(format!(
+            ";; these characters need a compose sequence: {}",
+            missing.join(" ")
+        ));
+    }
+    lines.push("(defsrc".to_string());
+    lines.extend(src);
+    lines.push(")".to_string());
+    lines.push(String::new());
+    lines.push(format!(
+        "(deflayer {}",
+        layout.name.to_lowercase().replace(' ', "-")
+    ));
+    lines.extend(layer);
+    lines.push(")".to_string());
+
+    if !layout.combos.is_empty() {
+        lines.push(String::new());
+        if !kanata {
+            lines.push(";; KMonad has no combos:".to_string());
+            for (keys, output) in &layout.combos {
+                lines.push(format!(";; {}", describe_combo(layout, keys, *output)));
+            }
+            return lines.join("\n") + "\n";
+        }
+        let widths: Vec<usize> = layout.rows.iter().map(Vec::len).collect();
+        let src_key = |position: usize| {
+            let mut rest = position;
+            for (r, width) in widths.iter().enumerate() {
+                if rest < *width {
+                    return ansi_key(r, rest).and_then(kbd_key);
+                }
+                rest -= width;
+            }
+            None
+        };
+        lines.push(";; chords need concurrent-tap-hold yes in defcfg".to_string());
+        lines.push("(defchordsv2".to_string());
+        for (keys, output) in &layout.combos {
+            let src: Option<Vec<String>> = keys.iter().map(|p| src_key(*p)).collect();
+            match (src, kbd_key(*output)) {
+                (Some(src), Some(out)) => {
+                    lines.push(format!("  ({}) {} 50 all-released ()", src.join(" "), out))
+                }
+                _ => lines.push(format!(
+                    "  ;; can't be expressed: {}",
+                    describe_combo(layout, keys, *output)
+                )),
+            }
+        }
+        lines.push(")".to_string());
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Escapes the characters that would end an XKB string early.
+fn xkb_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// An XKB symbols file that changes the letter keys of the US layout.
+fn xkb(layout: &ExportedLayout) -> String {
+    let mut lines = vec![
+        format!("// {}, exported from keymui", layout.name),
+        "// copy to /usr/share/X11/xkb/symbols/ and load with setxkbmap".to_string(),
+    ];
+    let unplaced = unplaced(layout);
+    if !unplaced.is_empty() {
+        lines.push(format!(
+            "// these keys have no place on an ANSI keyboard: {}",
+            unplaced.join(" ")
+        ));
+    }
+    for (keys, output) in &layout.combos {
+        lines.push(format!(
+            "// XKB has no combos: {}",
+            describe_combo(layout, keys, *output)
+        ));
+    }
+    lines.push("default partial alphanumeric_keys".to_string());
+    lines.push("xkb_symbols \"basic\" {".to_string());
+    lines.push("    include \"us(basic)\"".to_string());
+    lines.push(format!(
+        "    name[Group1] = \"{}\";",
+        xkb_escape(&layout.name)
+    ));
+    lines.push(String::new());
+    for (r, c, ch) in layout.keys() {
+        if let (Some(key), true) = (xkb_key_name(r, c), ch != '\0') {
+            lines.push(format!(
+                "    key <{}> {{ [ {}, {} ] }};",
+                key,
+                xkb_keysym(ch),
+                xkb_keysym(shifted(ch))
+            ));
+        }
+    }
+    lines.push("};".to_string());
+    lines.join("\n") + "\n"
+}
+
+pub fn xml_escape(c: char) -> String {
+    match c {
+        '&' => "&amp;".to_string(),
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '"' => "&quot;".to_string(),
+        '\'' => "&apos;".to_string(),
+        _ if c.is_control() => format!("&#x{:04X};", c as u32),
+        _ => c.to_string(),
+    }
+}
+
+/// A macOS `.keylayout` with the layout on the letter keys, the US number
+/// row and the keys needed to get around.
+fn keylayout(layout: &ExportedLayout) -> String {
+    // the number row and control keys: code, output, shifted output
+    const FIXED: [(u16, char, char); 22] = [
+        (50, '`', '~'),
+        (18, '1', '!'),
+        (19, '2', '@'),
+        (20, '3', '#'),
+        (21, '4', '$'),
+        (23, '5', '%'),
+        (22, '6', '^'),
+        (26, '7', '&'),
+        (28, '8', '*'),
+        (25, '9', '('),
+        (29, '0', ')'),
+        (27, '-', '_'),
+        (24, '=', '+'),
+        (36, '\r', '\r'),
+        (76, '\u{3}', '\u{3}'),
+        (48, '\t', '\t'),
+        (51, '\u{8}', '\u{8}'),
+        (53, '\u{1b}', '\u{1b}'),
+        (123, '\u{1c}', '\u{1c}'),
+        (124, '\u{1d}', '\u{1d}'),
+        (125, '\u{1f}', '\u{1f}'),
+        (126, '\u{1e}', '\u{1e}'),
+    ];
+    let mut keys: Vec<(u16, char, char)> = FIXED.to_vec();
+    keys.extend(
+        layout
+            .keys()
+            .filter(|(_, _, ch)| *ch != '\0')
+            .filter_map(|(r, c, ch)| Some((mac_code(r, c)?, ch, shifted(ch)))),
+    );
+    // macOS wants a negative id that is unique among keyboard layouts
+    let id = -1 - (layout.name.bytes().map(u32::from).sum::<u32>() % 30000) as i32;
+    let name: String = layout.name.chars().map(xml_escape).collect();
+
+    let mut lines = vec![
+        "<?xml version=\"1.1\" encoding=\"UTF-8\"?>".to_string(),
+        "<!DOCTYPE keyboard SYSTEM \"file://localhost/System/Library/DTDs/KeyboardLayout.dtd\">"
+            .to_string(),
+        format!("<!-- {}, exported from keymui -->", name),
+    ];
+    let unplaced = unplaced(layout);
+    if !unplaced.is_empty() {
+        let unplaced: String = unplaced.join(" ").chars().map(xml_escape).collect();
+        lines.push(format!(
+            "<!-- these keys have no place on an ANSI keyboard: {} -->",
+            unplaced
+        ));
+    }
+    for (combo, output) in &layout.combos {
+        let combo: String = describe_combo(layout, combo, *output)
+            .chars()
+            .map(xml_escape)
+            .collect();
+        lines.push(format!("<!-- macOS has no combos: {} -->", combo));
+    }
+    lines.push(format!(
+        "<keyboard group=\"126\" id=\"{}\" name=\"{}\" maxout=\"1\">",
+        id, name
+    ));
+    lines.push("  <layouts>".to_string());
+    lines.push(
+        "    <layout first=\"0\" last=\"17\" modifiers=\"modifiers\" mapSet=\"ANSI\"/>".to_string(),
+    );
+    lines.push("  </layouts>".to_string());
+    lines.push("  <modifierMap id=\"modifiers\" defaultIndex=\"0\">".to_string());
+    lines.push("    <keyMapSelect mapIndex=\"0\">".to_string());
+    lines.push("      <modifier keys=\"\"/>".to_string());
+    lines.push("      <modifier keys=\"command anyShift? caps?\"/>".to_string());
+    lines.push("    </keyMapSelect>".to_string());
+    lines.push("    <keyMapSelect mapIndex=\"1\">".to_string());
+    lines.push("      <modifier keys=\"anyShift caps?\"/>".to_string());
+    lines.push("      <modifier keys=\"caps\"/>".to_string());
+    lines.push("    </keyMapSelect>".to_string());
+    lines.push("  </modifierMap>".to_string());
+    lines.push("  <keyMapSet id=\"ANSI\">".to_string());
+    for index in 0..2 {
+        lines.push(format!("    <keyMap index=\"{}\">", index));
+        for (code, output, shifted) in &keys {
+            let output = if index == 0 { output } else { shifted };
+            lines.push(format!(
+                "      <key code=\"{}\" output=\"{}\"/>",
+                code,
+                xml_escape(*output)
+            ));
+        }
+        lines.push("    </keyMap>".to_string());
+    }
+    lines.push("  </keyMapSet>".to_string());
+    lines.push("</keyboard>".to_string());
+    lines.join("\n") + "\n"
+}
+
+pub fn export(layout: &ExportedLayout, format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Qmk => qmk(layout),
+        ExportFormat::Zmk => zmk(layout),
+        ExportFormat::Kanata => kbd(layout, true),
+        ExportFormat::Kmonad => kbd(layout, false),
+        ExportFormat::Xkb => xkb(layout),
+        ExportFormat::Keylayout => keylayout(layout),
+    }
+}
+
+impl Keymui {
+    pub fn exports_dir(&self) -> PathBuf {
+        self.data_dir().join("exports")
+    }
+
+    /// Writes the current layout, including unsaved changes, in the given
+    /// formats. Files go into `dir`, or the data dir's `exports` folder.
+    /// Returns the paths written.
+    pub fn export_layout(
+        &self,
+        formats: &[ExportFormat],
+        dir: Option<&Path>,
+    ) -> Result<Vec<PathBuf>> {
+        let ctx = self.metric_context.as_ref().context("no layout loaded")?;
+        let key = self.current_layout.as_ref().context("no layout selected")?;
+        let name = self
+            .layouts
+            .get(key)
+            .map(|l| l.name.as_str())
+            .unwrap_or(key);
+        let layout = ExportedLayout::new(name, ctx);
+
+        let dir = dir.map_or_else(|| self.exports_dir(), Path::to_path_buf);
+        if dir.is_file() {
+            return Err(anyhow!("{} isn't a folder", dir.display()));
+        }
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("couldn't create folder {}", dir.display()))?;
+        formats
+            .iter()
+            .map(|format| {
+                let path = dir.join(format.file_name(key));
+                fs::write(&path, export(&layout, *format))
+                    .with_context(|| format!("couldn't write {}", path.display()))?;
+                Ok(path)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tiny keyboard with shifted, empty and non-ASCII keys, a space bar
+    /// and two combos, one of which no OS format can express.
+    fn small() -> ExportedLayout {
+        ExportedLayout {
+            name: "Small".to_string(),
+            rows: vec![
+                vec!['f', 'l', 'h', 'Q'],
+                vec!['s', 'r', '\0', '!'],
+                vec![',', 'ß', 'é', ';'],
+                vec![' '],
+            ],
+            combos: vec![(vec![0, 1], '/'), (vec![4, 5], 'é')],
+        }
+    }
+
+    fn check(format: ExportFormat, expected: &str) {
+        assert_eq!(
+            export(&small(), format),
+            expected,
+            "{} export changed",
+            format
+        );
+    }
+
+    #[test]
+    fn qmk() {
+        check(
+            ExportFormat::Qmk,
+            include_str!("../testdata/export/small_keymap.c"),
+        );
+    }
+
+    #[test]
+    fn zmk() {
+        check(
+            ExportFormat::Zmk,
+            include_str!("../testdata/export/small.keymap"),
+        );
+    }
+
+    #[test]
+    fn kanata() {
+        check(
+            ExportFormat::Kanata,
+            include_str!("../testdata/export/small.kanata.kbd"),
+        );
+    }
+
+    #[test]
+    fn kmonad() {
+        check(
+            ExportFormat::Kmonad,
+            include_str!("../testdata/export/small.kmonad.kbd"),
+        );
+    }
+
+    #[test]
+    fn xkb() {
+        check(
+            ExportFormat::Xkb,
+            include_str!("../testdata/export/small.xkb"),
+        );
+    }
+
+    #[test]
+    fn xkb_names_are_escaped() {
+        let layout = ExportedLayout {
+            name: r#"Semi "Quoted" \ Mak"#.to_string(),
+            rows: vec![vec!['a']],
+            combos: vec![],
+        };
+        assert_eq!(
+            export(&layout, ExportFormat::Xkb),
+            include_str!("../testdata/export/quoted.xkb")
+        );
+    }
+
+    #[test]
+    fn keylayout() {
+        check(
+            ExportFormat::Keylayout,
+            include_str!("../testdata/export/small.keylayout"),
+        );
+    }
+
+    #[test]
+    fn shift_keeps_characters_without_a_single_uppercase() {
+        assert_eq!(shifted('a'), 'A');
+        assert_eq!(shifted(';'), ':');
+        assert_eq!(shifted('é'), 'É');
+        assert_eq!(shifted('ß'), 'ß');
+    }
+
+    #[test]
+    fn formats_round_trip_through_their_names() {
+        for format in ExportFormat::ALL {
+            assert_eq!(ExportFormat::parse(&format.to_string()), Some(format));
+        }
+    }
+}
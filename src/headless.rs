@@ -0,0 +1,187 @@
+use crate::log::Level;
+use crate::Keymui;
+use color_eyre::eyre::{anyhow, Context, Result};
+use iced::Application;
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+/// Where the commands of a headless run come from.
+pub enum ScriptSource {
+    /// Command line arguments, with commands separated by `;` or `--`.
+    Args(Vec<String>),
+    File(PathBuf),
+    Stdin,
+}
+
+impl ScriptSource {
+    /// Recognizes `keymui run "<command>; <command>"` and
+    /// `keymui --headless [script]`. Returns `None` when the GUI should be
+    /// started instead.
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        match args.first()?.as_str() {
+            "run" => Some(Self::Args(args[1..].to_vec())),
+            "--headless" => match args.get(1).map(|s| s.as_str()) {
+                None | Some("-") => Some(Self::Stdin),
+                Some(path) => Some(Self::File(PathBuf::from(path))),
+            },
+            _ => None,
+        }
+    }
+
+    fn command_lines(self) -> Result<Vec<String>> {
+        let script = match self {
+            Self::Args(args) => return Ok(arg_commands(&args)),
+            Self::File(path) => fs::read_to_string(&path)
+                .with_context(|| format!("couldn't read script {}", &path.display()))?,
+            Self::Stdin => {
+                let mut s = String::new();
+                io::stdin()
+                    .read_to_string(&mut s)
+                    .context("couldn't read script from stdin")?;
+                s
+            }
+        };
+        Ok(script_lines(&script).map(str::to_string).collect())
+    }
+}
+
+/// Splits command line arguments into commands, like
+/// `keymui run "select-layout semimak; stats"`. Commands end at a `;` that
+/// is followed by another word, at the end of a word or on its own, and at
+/// `--`. A `;` that ends the last word of a command is the key, so
+/// `swap l ;` swaps with `;` and `swap l ;; stats` runs `stats` after it.
+pub fn arg_commands(args: &[String]) -> Vec<String> {
+    let words: Vec<&str> = args.iter().flat_map(|arg| arg.split_whitespace()).collect();
+    let mut commands = vec![];
+    let mut command: Vec<&str> = vec![];
+    for (i, word) in words.iter().enumerate() {
+        let followed = words.get(i + 1).is_some_and(|next| *next != "--");
+        let end = match word.strip_suffix(';') {
+            _ if *word == "--" => true,
+            Some(rest) if followed => {
+                command.extend((!rest.is_empty()).then_some(rest));
+                true
+            }
+            _ => {
+                command.push(word);
+                false
+            }
+        };
+        if end && !command.is_empty() {
+            commands.push(command.join(" "));
+            command.clear();
+        }
+    }
+    if !command.is_empty() {
+        commands.push(command.join(" "));
+    }
+    commands
+}
+
+/// Splits a script into commands, one per line. Lines starting with `#`
+/// are ignored.
+pub fn script_lines(script: &str) -> impl Iterator<Item = &str> {
+    script
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+}
+
+/// Runs palette commands against the same state the GUI would load,
+/// printing everything they log. Warnings and errors go to stderr.
+pub fn run(source: ScriptSource) -> Result<()> {
+    let lines = source.command_lines()?;
+    let (mut keymui, _) = Keymui::new(());
+    keymui.headless = true;
+    for entry in keymui.log.entries().filter(|e| e.level > Level::Info) {
+        eprintln!("{}: {}", entry.level, entry.message);
+    }
+    for line in &lines {
+        let start = keymui.log.next_id();
+        let command = keymui
+            .execute_line(line)
+            .with_context(|| format!("command failed: {}", line))?;
+        if command.is_none() {
+            return Err(anyhow!("unknown command: {}", line));
+        }
+        for entry in keymui.log.since(start) {
+            let mut out = entry.message.clone();
+            if let Some(details) = &entry.details {
+                out = format!("{}\n{}", out, details);
+            }
+            match entry.level {
+                Level::Info => println!("{}", out),
+                Level::Warning | Level::Error => eprintln!("{}: {}", entry.level, out),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn arguments_are_split_at_double_dashes() {
+        assert_eq!(
+            arg_commands(&args(&["select-layout", "semimak", "--", "stats"])),
+            ["select-layout semimak", "stats"]
+        );
+        assert_eq!(
+            arg_commands(&args(&["select-layout semimak", "--", "nstrokes sfb 10"])),
+            ["select-layout semimak", "nstrokes sfb 10"]
+        );
+    }
+
+    #[test]
+    fn arguments_are_split_at_semicolons() {
+        assert_eq!(
+            arg_commands(&args(&["select-layout semimak; stats; nstrokes sfb 10"])),
+            ["select-layout semimak", "stats", "nstrokes sfb 10"]
+        );
+        assert_eq!(
+            arg_commands(&args(&["select-layout", "semimak", ";", "stats"])),
+            ["select-layout semimak", "stats"]
+        );
+        assert_eq!(
+            arg_commands(&args(&["select-layout semimak;", "stats"])),
+            ["select-layout semimak", "stats"]
+        );
+    }
+
+    #[test]
+    fn semicolons_ending_a_command_are_keys() {
+        assert_eq!(arg_commands(&args(&["swap l ;"])), ["swap l ;"]);
+        assert_eq!(arg_commands(&args(&["swap", "p", ";"])), ["swap p ;"]);
+        assert_eq!(
+            arg_commands(&args(&["swap l ;; stats"])),
+            ["swap l ;", "stats"]
+        );
+        assert_eq!(
+            arg_commands(&args(&["swap l ;", "--", "stats"])),
+            ["swap l ;", "stats"]
+        );
+    }
+
+    #[test]
+    fn empty_commands_are_skipped() {
+        assert!(arg_commands(&[]).is_empty());
+        assert_eq!(arg_commands(&args(&["--", "stats", "--", "--"])), ["stats"]);
+        assert_eq!(arg_commands(&args(&[";", "stats"])), ["stats"]);
+    }
+
+    #[test]
+    fn scripts_have_one_command_per_line() {
+        let script = "# compare\nselect-layout semimak\n\n  swap ; p  \nswap l ;\n";
+        assert_eq!(
+            script_lines(script).collect::<Vec<_>>(),
+            ["select-layout semimak", "swap ; p", "swap l ;"]
+        );
+    }
+}
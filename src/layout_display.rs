@@ -1,15 +1,20 @@
+use crate::export::xml_escape;
+use crate::render::ImageOptions;
 use crate::Message;
 use core::fmt;
 use iced::alignment::{Horizontal, Vertical};
+use iced::keyboard::{self, key::Named, Key};
 use iced::widget::canvas::{self, Text};
-use iced::widget::canvas::{Cache, Geometry};
-use iced::{mouse, Color, Point, Rectangle, Renderer, Size, Theme};
+use iced::widget::canvas::{Cache, Frame, Geometry};
+use iced::{mouse, Color, Point, Rectangle, Renderer, Size, Theme, Vector};
 use km::{self, KeyCoord, MetricContext};
 
 #[derive(Debug, Clone)]
 pub struct KeyData {
     letter: char,
     frequency: f32,
+    /// Percentage of the corpus's characters typed on this key.
+    share: f32,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Copy)]
@@ -48,9 +53,24 @@ pub struct LayoutDisplay {
     lowest_x: f32,
     highest_y: f32,
     pub style: ColorStyle,
+    mode: Mode,
     cache: Cache,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Only draws the keys and leaves clicks to the parent.
+    Thumbnail,
+    /// Keys are swapped by dragging or clicking them.
+    Swap,
+    /// Clicking a key selects it and typing places characters on it.
+    Edit,
+}
+
+/// The keys of a layout as drawn by [`LayoutDisplay`], computed without
+/// touching the UI so it can be done in the background.
+pub type DisplayKeys = Vec<(KeyCoord, Option<KeyData>)>;
+
 fn color_from_finger(finger: km::Finger) -> Color {
     let kind = match finger.kind() {
         km::FingerKind::Pinky => 0.4,
@@ -68,15 +88,76 @@ fn color_from_finger(finger: km::Finger) -> Color {
     Color::from_rgb(r, 0.1 + kind, b)
 }
 
+/// Color of a key in the frequency and metric heatmaps.
+fn heat_color(style: ColorStyle, f: f32) -> Color {
+    match style {
+        ColorStyle::Metric => Color::from_rgb(f / 1.2, f / 1.5, f / 1.0),
+        _ => Color::from_rgb(f / 1.5, f / 1.5, f),
+    }
+}
+
+fn hex(color: Color) -> String {
+    let [r, g, b, _] = color.into_rgba8();
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn finger_name(finger: km::Finger) -> String {
+    let hand = match finger.hand() {
+        km::Hand::Left => "left",
+        km::Hand::Right => "right",
+    };
+    let kind = match finger.kind() {
+        km::FingerKind::Pinky => "pinky",
+        km::FingerKind::Ring => "ring",
+        km::FingerKind::Middle => "middle",
+        km::FingerKind::Index => "index",
+        km::FingerKind::Thumb => "thumb",
+    };
+    format!("{} {}", hand, kind)
+}
+
+fn show_letter(letter: char) -> String {
+    match letter {
+        ' ' => "␣".to_string(),
+        '\0' => "".to_string(),
+        _ => letter.to_string(),
+    }
+}
+
+/// Mouse interaction with the keys, by index into the display's keys.
+#[derive(Debug, Default)]
+pub struct DragState {
+    /// Key chosen by a click, waiting for a second click to swap with.
+    selected: Option<usize>,
+    /// Key the left button went down on, for dragging.
+    pressed: Option<usize>,
+    hovered: Option<usize>,
+    cursor: Option<Point>,
+}
+
+/// Where the keys are drawn within the canvas bounds.
+struct Placement {
+    x_offset: f32,
+    y_offset: f32,
+    scale: f32,
+    key_size: f32,
+}
+
+impl Placement {
+    fn key_origin(&self, key: &KeyCoord) -> Point {
+        Point::new(
+            self.x_offset + self.scale * key.x,
+            self.y_offset + self.scale * key.y,
+        )
+    }
+}
+
 impl LayoutDisplay {
-    fn keys(
-        ctx: &MetricContext,
-        style: ColorStyle,
-        metric: usize,
-    ) -> Vec<(KeyCoord, Option<KeyData>)> {
+    pub fn keys(ctx: &MetricContext, style: ColorStyle, metric: usize) -> DisplayKeys {
         let kb = &ctx.keyboard;
         let l = &ctx.layout;
         let corpus = &ctx.analyzer.corpus;
+        let total_chars = corpus.chars.iter().sum::<u32>().max(1) as f32;
         let freqs: Vec<f32> = match style {
             ColorStyle::Frequency => {
                 let max_freq = l.0.iter().map(|c| corpus.chars[*c]).max().unwrap();
@@ -141,6 +222,7 @@ impl LayoutDisplay {
                             ColorStyle::Metric => freqs[i],
                             _ => 0.0,
                         },
+                        share: 100.0 * corpus.chars[*c] as f32 / total_chars,
                     }),
                 )
             })
@@ -150,113 +232,389 @@ impl LayoutDisplay {
         self.keys = Self::keys(ctx, self.style, metric);
     }
     pub fn new(ctx: &MetricContext, style: ColorStyle, metric: usize) -> Self {
-        let kb = &ctx.keyboard;
-        let lowest_y = kb
-            .keys
-            .map
-            .iter()
-            .flatten()
-            .map(|kc| (kc.y * 100.0).ceil() as i32)
-            .min()
-            .unwrap() as f32
-            / 100.0;
-        let highest_y = kb
-            .keys
-            .map
-            .iter()
-            .flatten()
-            .map(|kc| (kc.y * 100.0).ceil() as i32)
-            .max()
-            .unwrap() as f32
-            / 100.0;
-        let highest_x = kb
-            .keys
-            .map
-            .iter()
-            .flatten()
-            .map(|kc| (kc.x * 100.0).ceil() as i32)
-            .max()
-            .unwrap() as f32
-            / 100.0;
-        // TODO this sucks
-        let lowest_x = kb
-            .keys
-            .map
-            .iter()
-            .flatten()
-            .map(|kc| (kc.x * 100.0).ceil() as i32)
-            .min()
-            .unwrap() as f32
-            / 100.0;
+        Self::from_keys(Self::keys(ctx, style, metric), Mode::Swap)
+    }
+
+    /// A small preview that can't be edited.
+    pub fn thumbnail(keys: DisplayKeys) -> Self {
+        Self::from_keys(keys, Mode::Thumbnail)
+    }
+
+    /// A display for the layout editor, which places characters instead of
+    /// swapping them.
+    pub fn editor(ctx: &MetricContext) -> Self {
+        Self::from_keys(Self::keys(ctx, ColorStyle::Frequency, 0), Mode::Edit)
+    }
+
+    fn from_keys(keys: DisplayKeys, mode: Mode) -> Self {
+        let bound = |coord: fn(&KeyCoord) -> f32, max: bool| {
+            let rounded = keys.iter().map(|(kc, _)| (coord(kc) * 100.0).ceil() as i32);
+            let bound = if max { rounded.max() } else { rounded.min() };
+            bound.unwrap_or_default() as f32 / 100.0
+        };
         Self {
-            keys: Self::keys(ctx, style, metric),
-            lowest_y,
-            highest_y,
-            highest_x,
-            lowest_x,
+            lowest_y: bound(|kc| kc.y, false),
+            highest_y: bound(|kc| kc.y, true),
+            highest_x: bound(|kc| kc.x, true),
+            lowest_x: bound(|kc| kc.x, false),
+            keys,
             style: ColorStyle::Frequency,
+            mode,
             cache: Cache::default(),
         }
     }
 
+    fn key_color(&self, key: &KeyCoord, data: &Option<KeyData>) -> Color {
+        match (self.style, data) {
+            (ColorStyle::None, _) => Color::from_rgb(0.8, 0.8, 0.8),
+            (ColorStyle::Fingers, _) => color_from_finger(key.finger),
+            (style, Some(data)) => heat_color(style, data.frequency),
+            (_, None) => Color::from_rgb(0.3, 0.3, 0.3),
+        }
+    }
+
+    /// Renders the keys like the canvas does, as an SVG image. The legend
+    /// adds the title above the keys and explains the colors below them.
+    pub fn svg(&self, options: &ImageOptions, title: &str, heatmap: &str) -> String {
+        const MARGIN: f32 = 0.4;
+        const FONT: &str = "DejaVu Sans, Arial, Helvetica, sans-serif";
+        let palette = options.theme.iced_theme().palette();
+        let keys_width = 0.9 + self.highest_x - self.lowest_x;
+        let keys_height = 0.9 + self.highest_y - self.lowest_y;
+        let scale = options.width as f32 / (keys_width + 2.0 * MARGIN);
+        let top = if options.legend { MARGIN + 0.8 } else { MARGIN };
+
+        // left hand first, then by name
+        let mut fingers: Vec<(bool, String, Color)> = vec![];
+        for (key, _) in &self.keys {
+            let name = finger_name(key.finger);
+            if !fingers.iter().any(|(_, n, _)| *n == name) {
+                let right = matches!(key.finger.hand(), km::Hand::Right);
+                fingers.push((right, name, color_from_finger(key.finger)));
+            }
+        }
+        fingers.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+        let per_line = ((keys_width / 2.8) as usize).max(1);
+        let legend_height = match (options.legend, self.style) {
+            (false, _) | (true, ColorStyle::None) => 0.0,
+            (true, ColorStyle::Fingers) => 0.5 * fingers.len().div_ceil(per_line) as f32 + 0.3,
+            (true, _) => 1.1,
+        };
+        let width = options.width as f32;
+        let height = scale * (top + keys_height + legend_height + MARGIN);
+        let escape = |s: &str| s.chars().map(xml_escape).collect::<String>();
+        let text = |x: f32, y: f32, size: f32, anchor: &str, color: Color, content: &str| {
+            format!(
+                "  <text x=\"{:.1}\" y=\"{:.1}\" font-size=\"{:.1}\" text-anchor=\"{}\" dominant-baseline=\"central\" fill=\"{}\">{}</text>",
+                scale * x,
+                scale * y,
+                scale * size,
+                anchor,
+                hex(color),
+                escape(content)
+            )
+        };
+        let rect = |x: f32, y: f32, w: f32, h: f32, fill: &str| {
+            format!(
+                "  <rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" rx=\"{:.1}\" fill=\"{}\"/>",
+                scale * x,
+                scale * y,
+                scale * w,
+                scale * h,
+                scale * 0.08,
+                fill
+            )
+        };
+
+        let mut lines = vec![
+            format!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" viewBox=\"0 0 {:.0} {:.0}\" font-family=\"{}\">",
+                width, height, width, height, FONT
+            ),
+            format!(
+                "  <rect width=\"100%\" height=\"100%\" fill=\"{}\"/>",
+                hex(palette.background)
+            ),
+        ];
+        if options.legend {
+            lines.push(text(
+                MARGIN,
+                MARGIN + 0.3,
+                0.45,
+                "start",
+                palette.text,
+                title,
+            ));
+        }
+        for (key, data) in &self.keys {
+            let x = MARGIN + key.x - self.lowest_x;
+            let y = top + key.y - self.lowest_y;
+            lines.push(rect(x, y, 0.9, 0.9, &hex(self.key_color(key, data))));
+            if let Some(data) = data {
+                let letter = show_letter(data.letter);
+                if !letter.is_empty() {
+                    lines.push(text(
+                        x + 0.45,
+                        y + 0.45,
+                        0.5,
+                        "middle",
+                        Color::BLACK,
+                        &letter,
+                    ));
+                }
+            }
+        }
+
+        let legend_top = top + keys_height + 0.3;
+        match (options.legend, self.style) {
+            (false, _) | (_, ColorStyle::None) => {}
+            (true, ColorStyle::Fingers) => {
+                for (i, (_, name, color)) in fingers.iter().enumerate() {
+                    let x = MARGIN + 2.8 * (i % per_line) as f32;
+                    let y = legend_top + 0.5 * (i / per_line) as f32;
+                    lines.push(rect(x, y, 0.35, 0.35, &hex(*color)));
+                    lines.push(text(x + 0.5, y + 0.175, 0.3, "start", palette.text, name));
+                }
+            }
+            (true, style) => {
+                // the lowest and highest values `keys` produces
+                let low = if style == ColorStyle::Metric {
+                    0.2
+                } else {
+                    0.3
+                };
+                let bar = keys_width.min(5.0);
+                lines.push("  <defs>".to_string());
+                lines.push("    <linearGradient id=\"heat\">".to_string());
+                lines.push(format!(
+                    "      <stop offset=\"0\" stop-color=\"{}\"/>",
+                    hex(heat_color(style, low))
+                ));
+                lines.push(format!(
+                    "      <stop offset=\"1\" stop-color=\"{}\"/>",
+                    hex(heat_color(style, low + 1.0))
+                ));
+                lines.push("    </linearGradient>".to_string());
+                lines.push("  </defs>".to_string());
+                lines.push(text(
+                    MARGIN,
+                    legend_top + 0.15,
+                    0.3,
+                    "start",
+                    palette.text,
+                    heatmap,
+                ));
+                lines.push(rect(MARGIN, legend_top + 0.4, bar, 0.3, "url(#heat)"));
+                lines.push(text(
+                    MARGIN,
+                    legend_top + 0.9,
+                    0.25,
+                    "start",
+                    palette.text,
+                    "less",
+                ));
+                lines.push(text(
+                    MARGIN + bar,
+                    legend_top + 0.9,
+                    0.25,
+                    "end",
+                    palette.text,
+                    "more",
+                ));
+            }
+        }
+        lines.push("</svg>".to_string());
+        lines.join("\n") + "\n"
+    }
+
     pub fn redraw(&mut self) {
         self.cache.clear();
     }
+
+    fn placement(&self, bounds: Rectangle) -> Placement {
+        let width = 1.0 + self.highest_x - self.lowest_x;
+        let height = 1.0 + self.highest_y - self.lowest_y;
+        let width_provided = (0.95 * bounds.width).min(500.0);
+        let height_provided = (0.95 * bounds.height).min(500.0);
+        let scale = (width_provided / width).min(height_provided / height);
+        Placement {
+            x_offset: (bounds.width - width_provided) / 2.0,
+            y_offset: (bounds.height - height_provided) / 2.0,
+            scale,
+            key_size: scale * 0.9,
+        }
+    }
+
+    /// Index of the key under a point relative to the canvas bounds.
+    fn key_at(&self, bounds: Rectangle, point: Point) -> Option<usize> {
+        let placement = self.placement(bounds);
+        self.keys.iter().position(|(key, data)| {
+            data.is_some()
+                && Rectangle::new(
+                    placement.key_origin(key),
+                    Size::new(placement.key_size, placement.key_size),
+                )
+                .contains(point)
+        })
+    }
+
+    fn swap_message(&self, a: usize, b: usize) -> Option<Message> {
+        if self.mode == Mode::Edit {
+            // blank keys all look the same, so the editor swaps positions
+            return (a != b).then_some(Message::EditorSwap(a, b));
+        }
+        match (&self.keys.get(a)?.1, &self.keys.get(b)?.1) {
+            (Some(a), Some(b)) if a.letter != b.letter => {
+                Some(Message::SwapKeys(a.letter, b.letter))
+            }
+            _ => None,
+        }
+    }
+
+    /// Places a typed character on the selected key and selects the next
+    /// one, so a layout can be typed out row by row. Backspace and delete
+    /// clear keys, and the arrow keys move the selection.
+    fn key_typed(
+        &self,
+        state: &mut DragState,
+        event: keyboard::Event,
+    ) -> (canvas::event::Status, Option<Message>) {
+        let (
+            Some(selected),
+            keyboard::Event::KeyPressed {
+                key,
+                modifiers,
+                text,
+                ..
+            },
+        ) = (state.selected, event)
+        else {
+            return (canvas::event::Status::Ignored, None);
+        };
+        if modifiers.control() || modifiers.alt() || modifiers.logo() {
+            return (canvas::event::Status::Ignored, None);
+        }
+        let next = (selected + 1).min(self.keys.len().saturating_sub(1));
+        let previous = selected.saturating_sub(1);
+        let (message, selection) = match key {
+            Key::Named(Named::Escape) => (None, None),
+            Key::Named(Named::Backspace) => {
+                (Some(Message::EditorPlace(selected, '\0')), Some(previous))
+            }
+            Key::Named(Named::Delete) => {
+                (Some(Message::EditorPlace(selected, '\0')), Some(selected))
+            }
+            Key::Named(Named::ArrowLeft) => (None, Some(previous)),
+            Key::Named(Named::ArrowRight) => (None, Some(next)),
+            _ => match text
+                .and_then(|t| t.chars().next())
+                .filter(|c| !c.is_control())
+            {
+                Some(c) => (Some(Message::EditorPlace(selected, c)), Some(next)),
+                None => return (canvas::event::Status::Ignored, None),
+            },
+        };
+        state.selected = selection;
+        (canvas::event::Status::Captured, message)
+    }
+
+    /// Outlines the selected, dragged and hovered keys, and shows a
+    /// tooltip for the hovered one.
+    fn draw_overlay(
+        &self,
+        state: &DragState,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+    ) -> Geometry {
+        let placement = self.placement(bounds);
+        let palette = theme.palette();
+        let mut frame = Frame::new(renderer, bounds.size());
+        let outline = |frame: &mut Frame, idx: usize, color: Color| {
+            if let Some((key, _)) = self.keys.get(idx) {
+                frame.stroke(
+                    &canvas::Path::rectangle(
+                        placement.key_origin(key),
+                        Size::new(placement.key_size, placement.key_size),
+                    ),
+                    canvas::Stroke::default().with_color(color).with_width(3.0),
+                );
+            }
+        };
+
+        if let Some(hovered) = state.hovered {
+            outline(&mut frame, hovered, palette.text);
+        }
+        for idx in state.selected.iter().chain(state.pressed.iter()) {
+            outline(&mut frame, *idx, palette.primary);
+        }
+
+        if let (Some(hovered), Some(cursor)) = (state.hovered, state.cursor) {
+            if let Some((key, Some(data))) = self.keys.get(hovered) {
+                let lines = [
+                    format!("key: {}", show_letter(data.letter)),
+                    format!("finger: {}", finger_name(key.finger)),
+                    format!("frequency: {:.2}%", data.share),
+                ];
+                let text_size = 14.0;
+                let size = Size::new(
+                    text_size
+                        * 0.6
+                        * lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as f32
+                        + 12.0,
+                    text_size * 1.3 * lines.len() as f32 + 8.0,
+                );
+                // keep the tooltip inside the canvas
+                let origin = Point::new(
+                    (cursor.x + 16.0).min(bounds.width - size.width).max(0.0),
+                    (cursor.y + 16.0).min(bounds.height - size.height).max(0.0),
+                );
+                frame.fill_rectangle(origin, size, palette.background);
+                frame.stroke(
+                    &canvas::Path::rectangle(origin, size),
+                    canvas::Stroke::default().with_color(palette.text),
+                );
+                for (i, line) in lines.into_iter().enumerate() {
+                    let mut text = Text::from(line);
+                    text.position = origin + Vector::new(6.0, 4.0 + i as f32 * text_size * 1.3);
+                    text.size = iced::Pixels(text_size);
+                    text.color = palette.text;
+                    frame.fill_text(text);
+                }
+            }
+        }
+        frame.into_geometry()
+    }
 }
 
 impl canvas::Program<Message> for LayoutDisplay {
-    type State = ();
+    type State = DragState;
 
     fn draw(
         &self,
-        _state: &Self::State,
+        state: &Self::State,
         renderer: &Renderer,
-        _theme: &Theme,
+        theme: &Theme,
         bounds: Rectangle,
         _cursor: mouse::Cursor,
     ) -> Vec<Geometry> {
-        let width = 1.0 + self.highest_x - self.lowest_x;
-        let height = 1.0 + self.highest_y - self.lowest_y;
-        let width_provided = (0.95 * bounds.width).min(500.0);
-        let height_provided = (0.95 * bounds.height).min(500.0);
-        let x_offset = (bounds.width - width_provided) / 2.0;
-        let y_offset = (bounds.height - height_provided) / 2.0;
-        let scale = (width_provided / width).min(height_provided / height);
-        let key_size = scale * 0.9;
+        let Placement {
+            x_offset,
+            y_offset,
+            scale,
+            key_size,
+        } = self.placement(bounds);
 
         let display = self.cache.draw(renderer, bounds.size(), |frame| {
             for (key, data) in &self.keys {
-                let color = match self.style {
-                    ColorStyle::None => Color::from_rgb(0.8, 0.8, 0.8),
-                    ColorStyle::Frequency => {
-                        if let Some(data) = &data {
-                            let f = data.frequency;
-                            Color::from_rgb(f / 1.5, f / 1.5, f)
-                        } else {
-                            Color::from_rgb(0.3, 0.3, 0.3)
-                        }
-                    }
-                    ColorStyle::Metric => {
-                        if let Some(data) = &data {
-                            let f = data.frequency;
-                            Color::from_rgb(f / 1.2, f / 1.5, f / 1.0)
-                        } else {
-                            Color::from_rgb(0.3, 0.3, 0.3)
-                        }
-                    }
-                    ColorStyle::Fingers => color_from_finger(key.finger),
-                };
+                let color = self.key_color(key, data);
                 frame.fill_rectangle(
                     Point::new(x_offset + scale * key.x, y_offset + scale * key.y),
                     Size::new(key_size, key_size),
                     color,
                 );
                 if let Some(data) = data {
-                    let mut text = Text::from(match data.letter {
-                        ' ' => "␣".to_string(),
-                        '\0' => "".to_string(),
-                        _ => data.letter.to_string(),
-                    });
+                    let mut text = Text::from(show_letter(data.letter));
                     let bx = x_offset + key.x * scale;
                     let by = y_offset + key.y * scale;
                     text.position =
@@ -268,29 +626,182 @@ impl canvas::Program<Message> for LayoutDisplay {
                 }
             }
         });
-        vec![display]
+        if self.mode == Mode::Thumbnail {
+            return vec![display];
+        }
+        vec![display, self.draw_overlay(state, renderer, theme, bounds)]
     }
 
     fn update(
         &self,
-        _state: &mut Self::State,
-        _event: canvas::Event,
-        _bounds: Rectangle,
-        _cursor: mouse::Cursor,
+        state: &mut Self::State,
+        event: canvas::Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
     ) -> (canvas::event::Status, Option<Message>) {
-        (canvas::event::Status::Ignored, None)
+        let event = match event {
+            canvas::Event::Keyboard(event) if self.mode == Mode::Edit => {
+                return self.key_typed(state, event);
+            }
+            canvas::Event::Mouse(event) if self.mode != Mode::Thumbnail => event,
+            _ => return (canvas::event::Status::Ignored, None),
+        };
+        state.cursor = cursor.position_in(bounds);
+        let under_cursor = state.cursor.and_then(|p| self.key_at(bounds, p));
+        match event {
+            mouse::Event::CursorMoved { .. } => {
+                state.hovered = under_cursor;
+                (canvas::event::Status::Ignored, None)
+            }
+            mouse::Event::CursorLeft => {
+                state.hovered = None;
+                state.pressed = None;
+                (canvas::event::Status::Ignored, None)
+            }
+            mouse::Event::ButtonPressed(mouse::Button::Left) if under_cursor.is_some() => {
+                state.pressed = under_cursor;
+                (canvas::event::Status::Captured, None)
+            }
+            // clicking elsewhere, like into a text input, ends the typing
+            mouse::Event::ButtonPressed(mouse::Button::Left) if self.mode == Mode::Edit => {
+                state.selected = None;
+                (canvas::event::Status::Ignored, None)
+            }
+            mouse::Event::ButtonPressed(mouse::Button::Right) => {
+                state.selected = None;
+                (canvas::event::Status::Captured, None)
+            }
+            mouse::Event::ButtonReleased(mouse::Button::Left) => {
+                let Some(pressed) = state.pressed.take() else {
+                    return (canvas::event::Status::Ignored, None);
+                };
+                let message = match under_cursor {
+                    // dropped onto another key
+                    Some(released) if released != pressed => {
+                        state.selected = None;
+                        self.swap_message(pressed, released)
+                    }
+                    // a click in the editor selects the key to type onto
+                    Some(_) if self.mode == Mode::Edit => {
+                        state.selected = (state.selected != Some(pressed)).then_some(pressed);
+                        None
+                    }
+                    // a click: select the key, or swap it with the selected one
+                    Some(_) => match state.selected.take() {
+                        Some(selected) if selected != pressed => {
+                            self.swap_message(selected, pressed)
+                        }
+                        Some(_) => None,
+                        None => {
+                            state.selected = Some(pressed);
+                            None
+                        }
+                    },
+                    None => None,
+                };
+                (canvas::event::Status::Captured, message)
+            }
+            _ => (canvas::event::Status::Ignored, None),
+        }
     }
 
     fn mouse_interaction(
         &self,
-        _state: &Self::State,
+        state: &Self::State,
         bounds: Rectangle,
         cursor: mouse::Cursor,
     ) -> mouse::Interaction {
-        if cursor.is_over(bounds) {
+        if self.mode == Mode::Thumbnail {
+            mouse::Interaction::default()
+        } else if state.pressed.is_some() {
+            mouse::Interaction::Grabbing
+        } else if cursor
+            .position_in(bounds)
+            .and_then(|p| self.key_at(bounds, p))
+            .is_some()
+        {
             mouse::Interaction::Grab
         } else {
             mouse::Interaction::default()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeymuiTheme;
+
+    /// One row of keys typed by the left index finger.
+    fn display(letters: &str) -> LayoutDisplay {
+        let keys = letters
+            .chars()
+            .enumerate()
+            .map(|(i, letter)| {
+                (
+                    KeyCoord {
+                        x: i as f32,
+                        y: 0.0,
+                        finger: km::Finger::LI,
+                    },
+                    Some(KeyData {
+                        letter,
+                        frequency: 0.5,
+                        share: 10.0,
+                    }),
+                )
+            })
+            .collect();
+        LayoutDisplay::thumbnail(keys)
+    }
+
+    fn options(legend: bool) -> ImageOptions {
+        ImageOptions {
+            width: 400,
+            theme: KeymuiTheme::Light,
+            legend,
+        }
+    }
+
+    /// The contents of every `<text>` element.
+    fn texts(svg: &str) -> Vec<&str> {
+        svg.lines()
+            .filter_map(|l| l.split_once("\">")?.1.strip_suffix("</text>"))
+            .collect()
+    }
+
+    #[test]
+    fn svg_labels_keys_and_explains_the_colors() {
+        let svg = display("a< \0").svg(&options(true), "Semi & Co", "key frequency");
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"400\""));
+        assert!(svg.ends_with("</svg>\n"));
+        // empty keys are drawn without a label
+        assert_eq!(svg.matches("<rect").count(), 1 + 4 + 1);
+        assert_eq!(
+            texts(&svg),
+            [
+                "Semi &amp; Co",
+                "a",
+                "&lt;",
+                "␣",
+                "key frequency",
+                "less",
+                "more"
+            ]
+        );
+    }
+
+    #[test]
+    fn svg_legends_can_be_left_out() {
+        let svg = display("ab").svg(&options(false), "Semimak", "key frequency");
+        assert_eq!(texts(&svg), ["a", "b"]);
+    }
+
+    #[test]
+    fn finger_legends_name_each_finger_once() {
+        let mut display = display("abc");
+        display.style = ColorStyle::Fingers;
+        let svg = display.svg(&options(true), "Semimak", "key frequency");
+        assert_eq!(texts(&svg), ["Semimak", "a", "b", "c", "left index"]);
+    }
+}
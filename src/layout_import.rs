@@ -0,0 +1,627 @@
+use crate::editor::EditorStart;
+use crate::log::Level;
+use crate::Keymui;
+use color_eyre::eyre::{anyhow, Context, ContextCompat, Result};
+use core::fmt;
+use serde_json::Value;
+use std::cmp::Reverse;
+use std::fs;
+use std::path::Path;
+
+/// A layout read from another analyzer's format, as rows of characters.
+/// Empty cells are `'\0'`.
+#[derive(Debug, Clone)]
+pub struct ImportedLayout {
+    pub name: String,
+    pub authors: Vec<String>,
+    pub rows: Vec<Vec<char>>,
+}
+
+/// An imported layout being placed in the editor.
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub layout: ImportedLayout,
+    /// Every way the rows fit onto the keyboard, best first.
+    pub mappings: Vec<Mapping>,
+    pub mapping: Mapping,
+}
+
+/// Where the rows of an imported layout go on a keyboard: consecutive
+/// keyboard rows from `first_row`, each starting `offset` keys in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mapping {
+    pub first_row: usize,
+    pub offset: usize,
+    rows: usize,
+}
+
+impl fmt::Display for Mapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "rows {}-{}, from key {}",
+            self.first_row + 1,
+            self.first_row + self.rows,
+            self.offset + 1
+        )
+    }
+}
+
+impl Mapping {
+    /// Every placement of `grid` onto keyboard rows of the given widths,
+    /// best first: the fewest characters that don't fit, then the rows
+    /// closest in width, centered, and the lowest rows, since number rows
+    /// are on top.
+    pub fn options(grid: &[Vec<char>], widths: &[usize]) -> Vec<Mapping> {
+        let rows = grid.len();
+        let mut options = vec![];
+        for first_row in 0..=widths.len().saturating_sub(rows) {
+            let row_widths = &widths[first_row..widths.len().min(first_row + rows)];
+            let slack = row_widths
+                .iter()
+                .zip(grid)
+                .map(|(w, row)| w.saturating_sub(row.len()));
+            let max_offset = slack.max().unwrap_or(0);
+            let center = max_offset / 2;
+            for offset in 0..=max_offset {
+                let mapping = Mapping {
+                    first_row,
+                    offset,
+                    rows,
+                };
+                let cost = (
+                    mapping.overflow(grid, widths),
+                    row_widths.iter().sum::<usize>(),
+                    offset.abs_diff(center),
+                    Reverse(first_row),
+                );
+                options.push((cost, mapping));
+            }
+        }
+        options.sort_by_key(|(cost, _)| *cost);
+        options.into_iter().map(|(_, mapping)| mapping).collect()
+    }
+
+    /// The layout position of every character in `grid`, or `None` where
+    /// it falls off the keyboard.
+    pub fn positions(&self, grid: &[Vec<char>], widths: &[usize]) -> Vec<(Option<usize>, char)> {
+        let mut positions = vec![];
+        for (i, row) in grid.iter().enumerate() {
+            let kb_row = self.first_row + i;
+            let start: usize = widths.iter().take(kb_row).sum();
+            for (j, c) in row.iter().enumerate().filter(|(_, c)| **c != '\0') {
+                let column = self.offset + j;
+                let fits = widths.get(kb_row).is_some_and(|w| column < *w);
+                positions.push((fits.then_some(start + column), *c));
+            }
+        }
+        positions
+    }
+
+    fn overflow(&self, grid: &[Vec<char>], widths: &[usize]) -> usize {
+        self.positions(grid, widths)
+            .iter()
+            .filter(|(p, _)| p.is_none())
+            .count()
+    }
+}
+
+/// Reads a layout in any of the supported formats, telling them apart by
+/// extension and content.
+pub fn read(path: &Path) -> Result<ImportedLayout> {
+    let s = fs::read_to_string(path)
+        .with_context(|| format!("couldn't read file {}", path.display()))?;
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // QMK keymaps are all called keymap.c, their folder names them
+    let name = match (stem.as_str(), path.parent().and_then(|p| p.file_name())) {
+        ("keymap", Some(folder)) => folder.to_string_lossy().to_string(),
+        _ => stem,
+    };
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let layout = match extension.as_str() {
+        "json" => {
+            let json: Value = serde_json::from_str(&s)
+                .with_context(|| format!("couldn't parse JSON file {}", path.display()))?;
+            parse_json(name, json)?
+        }
+        "c" | "h" => parse_qmk(name, &s)?,
+        _ if s.contains("LAYOUT") && s.contains("KC_") => parse_qmk(name, &s)?,
+        _ => parse_text(name, &s)?,
+    };
+    if layout.rows.iter().flatten().all(|c| *c == '\0') {
+        return Err(anyhow!("no keys found in {}", path.display()));
+    }
+    Ok(layout)
+}
+
+fn single_char(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// Plain grids and oxeylyzer `.kb` files have a row of keys per line,
+/// with or without spaces between them. Genkey layouts have their name on
+/// the first line and finger numbers below the keys, which are dropped.
+fn parse_text(name: String, s: &str) -> Result<ImportedLayout> {
+    let lines: Vec<&str> = s
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with("//") && !l.starts_with('#'))
+        .collect();
+    let spaced = |line: &str| {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        (tokens.len() > 1)
+            .then(|| {
+                tokens
+                    .into_iter()
+                    .map(single_char)
+                    .collect::<Option<Vec<char>>>()
+            })
+            .flatten()
+    };
+    let mut name = name;
+    let mut rows: Vec<Vec<char>> = vec![];
+    if lines.iter().any(|l| spaced(l).is_some()) {
+        for line in lines {
+            match spaced(line) {
+                Some(row) => rows.push(row),
+                None if rows.is_empty() => name = line.to_string(),
+                None => return Err(anyhow!("couldn't read row: {}", line)),
+            }
+        }
+    } else {
+        rows = lines.iter().map(|l| l.chars().collect()).collect();
+    }
+
+    let digits = |row: &Vec<char>| row.iter().all(char::is_ascii_digit);
+    if let Some(split) = rows.iter().position(digits) {
+        if split > 0 && rows.len() == 2 * split && rows[split..].iter().all(digits) {
+            rows.truncate(split);
+        }
+    }
+    Ok(ImportedLayout {
+        name,
+        authors: vec![],
+        rows,
+    })
+}
+
+fn parse_json(name: String, json: Value) -> Result<ImportedLayout> {
+    match json {
+        Value::Object(object) if object.get("keys").is_some_and(Value::is_object) => {
+            parse_cmini(name, &object)
+        }
+        Value::Array(rows) => parse_kle(name, &rows),
+        Value::Object(object)
+            if serde_json::from_value::<km::LayoutData>(Value::Object(object.clone())).is_ok() =>
+        {
+            Err(anyhow!(
+                "this is already a keymui layout, copy it into a layout directory instead"
+            ))
+        }
+        _ => Err(anyhow!("unknown JSON layout format")),
+    }
+}
+
+fn authors_of(object: &serde_json::Map<String, Value>) -> Vec<String> {
+    match object.get("authors").or_else(|| object.get("author")) {
+        Some(Value::String(author)) => vec![author.clone()],
+        Some(Value::Array(authors)) => authors
+            .iter()
+            .filter_map(|a| a.as_str().map(str::to_string))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// cmini (and genkey exports in its format) keep each key with its row
+/// and column: `{"name": .., "keys": {"a": {"row": 1, "col": 0}, ..}}`.
+fn parse_cmini(name: String, object: &serde_json::Map<String, Value>) -> Result<ImportedLayout> {
+    let keys = object["keys"].as_object().expect("checked by the caller");
+    let mut rows: Vec<Vec<char>> = vec![];
+    for (key, pos) in keys {
+        let c = single_char(key).with_context(|| format!("key {} isn't a character", key))?;
+        let coord = |field: &str| {
+            pos.get(field)
+                .and_then(Value::as_u64)
+                .map(|n| n as usize)
+                .with_context(|| format!("key {} has no {}", key, field))
+        };
+        let (row, col) = (coord("row")?, coord("col")?);
+        if rows.len() <= row {
+            rows.resize(row + 1, vec![]);
+        }
+        if rows[row].len() <= col {
+            rows[row].resize(col + 1, '\0');
+        }
+        rows[row][col] = c;
+    }
+    Ok(ImportedLayout {
+        name: object
+            .get("name")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or(name),
+        authors: authors_of(object),
+        rows,
+    })
+}
+
+/// keyboard-layout-editor.com's JSON: an optional object with the name
+/// and author, followed by an array of legends per row. Objects between
+/// the legends only change the look of the next key.
+fn parse_kle(name: String, json: &[Value]) -> Result<ImportedLayout> {
+    let meta = json.first().and_then(Value::as_object);
+    let rows = json
+        .iter()
+        .filter_map(Value::as_array)
+        .map(|row| {
+            row.iter()
+                .filter_map(Value::as_str)
+                .map(|legend| {
+                    // the unshifted character is the lower legend
+                    let c = legend.split('\n').filter_map(single_char).next_back()?;
+                    Some(c.to_lowercase().next().unwrap_or(c))
+                })
+                .collect()
+        })
+        .collect();
+    Ok(ImportedLayout {
+        name: meta
+            .and_then(|m| m.get("name"))
+            .and_then(Value::as_str)
+            .filter(|n| !n.is_empty())
+            .map(str::to_string)
+            .unwrap_or(name),
+        authors: meta.map(authors_of).unwrap_or_default(),
+        rows: alpha_area(rows),
+    })
+}
+
+fn strip_comments(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("//") {
+            rest = after.find('\n').map_or("", |i| &after[i..]);
+        } else if let Some(after) = rest.strip_prefix("/*") {
+            rest = after.find("*/").map_or("", |i| &after[i + 2..]);
+        } else {
+            let c = rest.chars().next().expect("not empty");
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    out
+}
+
+/// The character a QMK keycode types. Mod-taps and layer-taps like
+/// `LSFT_T(KC_A)` or `LT(1, KC_SPC)` type their last argument.
+fn keycode_char(keycode: &str) -> Option<char> {
+    let tapped = keycode
+        .rsplit(['(', ','])
+        .next()?
+        .trim_end_matches(')')
+        .trim();
+    let code = tapped.strip_prefix("KC_")?;
+    if let Some(c) = single_char(code).filter(char::is_ascii_alphanumeric) {
+        return Some(c.to_ascii_lowercase());
+    }
+    Some(match code {
+        "SPC" | "SPACE" => ' ',
+        "COMM" | "COMMA" => ',',
+        "DOT" => '.',
+        "SLSH" | "SLASH" => '/',
+        "SCLN" | "SCOLON" | "SEMICOLON" => ';',
+        "QUOT" | "QUOTE" => '\'',
+        "MINS" | "MINUS" => '-',
+        "EQL" | "EQUAL" => '=',
+        "LBRC" | "LEFT_BRACKET" => '[',
+        "RBRC" | "RIGHT_BRACKET" => ']',
+        "BSLS" | "BACKSLASH" => '\\',
+        "GRV" | "GRAVE" => '`',
+        _ => return None,
+    })
+}
+
+/// Takes the base layer of a QMK `keymap.c`: the first `LAYOUT` macro,
+/// with a row per line of source like keymaps are usually written.
+fn parse_qmk(name: String, s: &str) -> Result<ImportedLayout> {
+    let s = strip_comments(s);
+    let start = s.find("LAYOUT").context("no LAYOUT macro found")?;
+    let args = &s[start..];
+    let args = &args[args.find('(').context("no LAYOUT macro found")? + 1..];
+
+    let mut rows: Vec<Vec<Option<char>>> = vec![];
+    let mut row = vec![];
+    let mut keycode = String::new();
+    let mut depth = 0;
+    for c in args.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => break,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                row.push(keycode_char(&keycode));
+                keycode.clear();
+                continue;
+            }
+            '\n' if depth == 0 => {
+                if !keycode.trim().is_empty() {
+                    row.push(keycode_char(&keycode));
+                    keycode.clear();
+                }
+                if !row.is_empty() {
+                    rows.push(std::mem::take(&mut row));
+                }
+                continue;
+            }
+            _ => (),
+        }
+        keycode.push(c);
+    }
+    if !keycode.trim().is_empty() {
+        row.push(keycode_char(&keycode));
+    }
+    if !row.is_empty() {
+        rows.push(row);
+    }
+    Ok(ImportedLayout {
+        name,
+        authors: vec![],
+        rows: alpha_area(rows),
+    })
+}
+
+/// Cuts the alpha area out of a whole keyboard: the three neighbouring
+/// rows with the most letters, each from its first to its last character
+/// key. Spaces are left out, since they're on thumb keys.
+fn alpha_area(rows: Vec<Vec<Option<char>>>) -> Vec<Vec<char>> {
+    let letters = |row: &Vec<Option<char>>| {
+        row.iter()
+            .filter(|c| c.is_some_and(char::is_alphabetic))
+            .count()
+    };
+    let window = 3.min(rows.len());
+    let first = (0..=rows.len() - window)
+        .max_by_key(|i| {
+            (
+                rows[*i..*i + window].iter().map(letters).sum::<usize>(),
+                Reverse(*i),
+            )
+        })
+        .unwrap_or(0);
+    rows[first..first + window]
+        .iter()
+        .map(|row| {
+            let is_key = |c: &Option<char>| c.is_some_and(|c| c != ' ');
+            let start = row.iter().position(is_key).unwrap_or(0);
+            let end = row.iter().rposition(is_key).map_or(0, |i| i + 1);
+            row[start..end.max(start)]
+                .iter()
+                .map(|c| c.filter(|c| *c != ' ').unwrap_or('\0'))
+                .collect()
+        })
+        .collect()
+}
+
+impl Keymui {
+    /// Reads a layout from another format and opens it in the editor for
+    /// the selected metrics' keyboard, where it can be checked and saved.
+    pub fn import_layout(&mut self, path: &Path) -> Result<()> {
+        let layout = read(path)?;
+        let metrics = self
+            .current_metrics
+            .clone()
+            .context("no metrics selected")?;
+        self.start_import(layout, metrics)
+    }
+
+    pub fn start_import(&mut self, layout: ImportedLayout, metrics: String) -> Result<()> {
+        self.open_editor(EditorStart::Blank, metrics)?;
+        let editor = self.editor.as_mut().expect("the editor was just opened");
+        editor.name.clone_from(&layout.name);
+        if !layout.authors.is_empty() {
+            editor.authors = layout.authors.join(", ");
+        }
+        let mappings = Mapping::options(&layout.rows, &editor.row_widths());
+        let mapping = *mappings.first().context("the keyboard has no keys")?;
+        let name = layout.name.clone();
+        editor.import = Some(Import {
+            layout,
+            mappings,
+            mapping,
+        });
+        self.info(format!(
+            "imported {}, check it in the editor and save it",
+            name
+        ));
+        self.apply_import();
+        Ok(())
+    }
+
+    /// Places the imported rows with the editor's mapping, reporting
+    /// characters that couldn't be placed and whether another mapping
+    /// would fit as well.
+    pub fn apply_import(&mut self) {
+        let Some(editor) = &mut self.editor else {
+            return;
+        };
+        let skipped = editor.apply_import();
+        let widths = editor.row_widths();
+        let fitting = editor.import.as_ref().map_or(0, |import| {
+            import
+                .mappings
+                .iter()
+                .filter(|m| m.overflow(&import.layout.rows, &widths) == 0)
+                .count()
+        });
+        if fitting > 1 {
+            self.warn(format!(
+                "the rows fit {} ways on this keyboard, pick one in the editor",
+                fitting
+            ));
+        }
+        if !skipped.is_empty() {
+            self.log_entry(
+                Level::Warning,
+                format!("{} keys couldn't be placed", skipped.len()),
+                Some(format!(
+                    "{}\n\nthey don't fit the keyboard, aren't in the corpus or are duplicates",
+                    skipped.iter().collect::<String>()
+                )),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(rows: &[&str]) -> Vec<Vec<char>> {
+        rows.iter().map(|r| r.chars().collect()).collect()
+    }
+
+    #[test]
+    fn text_grids_with_and_without_spaces() {
+        let layout = parse_text("plain".to_string(), "qwfpb\narstg\n\nzxcdv\n").unwrap();
+        assert_eq!(layout.name, "plain");
+        assert_eq!(layout.rows, rows(&["qwfpb", "arstg", "zxcdv"]));
+
+        let layout = parse_text("kb".to_string(), "// oxeylyzer\nq w f\na r s\n").unwrap();
+        assert_eq!(layout.rows, rows(&["qwf", "ars"]));
+    }
+
+    #[test]
+    fn genkey_names_and_fingers() {
+        let genkey = "Semimak\nf l h v z\ns r n t k\n0 1 2 3 3\n0 1 2 3 3\n";
+        let layout = parse_text("semimak-file".to_string(), genkey).unwrap();
+        assert_eq!(layout.name, "Semimak");
+        assert_eq!(layout.rows, rows(&["flhvz", "srntk"]));
+    }
+
+    #[test]
+    fn broken_text_rows_are_rejected() {
+        assert!(parse_text("x".to_string(), "a b c\nabc def\n").is_err());
+    }
+
+    #[test]
+    fn qmk_base_layer() {
+        let keymap = "
+const uint16_t PROGMEM keymaps[][MATRIX_ROWS][MATRIX_COLS] = {
+    [0] = LAYOUT( // base
+        KC_1, KC_2, KC_3, KC_4,
+        KC_Q, KC_W, KC_F, KC_P,
+        LSFT_T(KC_A), KC_R, KC_S, LT(1, KC_T),
+        KC_Z, KC_X, KC_C, KC_COMM,
+        /* thumbs */ KC_LCTL, KC_SPC
+    ),
+    [1] = LAYOUT(KC_TRNS)
+};
+";
+        let layout = parse_qmk("colemak".to_string(), keymap).unwrap();
+        assert_eq!(layout.name, "colemak");
+        assert_eq!(layout.rows, rows(&["qwfp", "arst", "zxc,"]));
+        assert!(parse_qmk("x".to_string(), "int main() {}").is_err());
+    }
+
+    #[test]
+    fn qmk_keycodes() {
+        assert_eq!(keycode_char("KC_A"), Some('a'));
+        assert_eq!(keycode_char(" KC_SCLN "), Some(';'));
+        assert_eq!(keycode_char("LCTL_T(KC_QUOT)"), Some('\''));
+        assert_eq!(keycode_char("LT(2, KC_SPC)"), Some(' '));
+        assert_eq!(keycode_char("KC_LSFT"), None);
+        assert_eq!(keycode_char("MO(1)"), None);
+    }
+
+    #[test]
+    fn kle_legends_and_metadata() {
+        let json: Value = serde_json::from_str(
+            r##"[
+                {"name": "Test", "author": "someone"},
+                ["!\n1", "@\n2", "#\n3"],
+                ["Q", {"w": 1.5}, "W", "E"],
+                ["A", "S", ":\n;"],
+                ["Z", "X", "C"],
+                [{"w": 6}, ""]
+            ]"##,
+        )
+        .unwrap();
+        let layout = parse_json("file".to_string(), json).unwrap();
+        assert_eq!(layout.name, "Test");
+        assert_eq!(layout.authors, ["someone"]);
+        assert_eq!(layout.rows, rows(&["qwe", "as;", "zxc"]));
+    }
+
+    #[test]
+    fn cmini_keys() {
+        let json: Value = serde_json::from_str(
+            r#"{"name": "Mini", "authors": ["a", "b"], "keys": {
+                "q": {"row": 0, "col": 0}, "w": {"row": 0, "col": 1},
+                "a": {"row": 1, "col": 0}, "s": {"row": 1, "col": 2}
+            }}"#,
+        )
+        .unwrap();
+        let layout = parse_json("file".to_string(), json).unwrap();
+        assert_eq!(layout.name, "Mini");
+        assert_eq!(layout.authors, ["a", "b"]);
+        assert_eq!(layout.rows, vec![vec!['q', 'w'], vec!['a', '\0', 's']]);
+    }
+
+    fn mapping(first_row: usize, offset: usize, rows: usize) -> Mapping {
+        Mapping {
+            first_row,
+            offset,
+            rows,
+        }
+    }
+
+    #[test]
+    fn mappings_prefer_lower_rows() {
+        let grid = rows(&["qwfpbjluy;", "arstgmneio", "zxcdvkh,./"]);
+        assert_eq!(
+            Mapping::options(&grid, &[10, 10, 10, 10]),
+            [mapping(1, 0, 3), mapping(0, 0, 3)]
+        );
+    }
+
+    #[test]
+    fn mappings_prefer_centered_rows() {
+        let grid = rows(&["ab"]);
+        assert_eq!(
+            Mapping::options(&grid, &[4]),
+            [mapping(0, 1, 1), mapping(0, 0, 1), mapping(0, 2, 1)]
+        );
+    }
+
+    #[test]
+    fn mappings_prefer_rows_that_fit() {
+        let grid = rows(&["abc"]);
+        assert_eq!(
+            Mapping::options(&grid, &[2, 4]),
+            [mapping(1, 0, 1), mapping(1, 1, 1), mapping(0, 0, 1)]
+        );
+    }
+
+    #[test]
+    fn positions_skip_empty_cells() {
+        let grid = vec![vec!['a', '\0', 'b']];
+        let mapping = mapping(1, 1, 1);
+        assert_eq!(
+            mapping.positions(&grid, &[3, 3]),
+            [(Some(4), 'a'), (None, 'b')]
+        );
+        assert_eq!(mapping.to_string(), "rows 2-2, from key 2");
+    }
+}
@@ -0,0 +1,179 @@
+use crate::Keymui;
+use chrono::{DateTime, Local};
+use color_eyre::eyre::Report;
+use iced::theme::Palette;
+use iced::Color;
+use std::collections::VecDeque;
+use std::fmt;
+
+/// How many entries are kept before the oldest are dropped.
+const MAX_ENTRIES: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Level {
+    pub fn color(self, palette: &Palette) -> Option<Color> {
+        match self {
+            Level::Info => None,
+            Level::Warning => Some(Color::from_rgb(0.9, 0.6, 0.1)),
+            Level::Error => Some(palette.danger),
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Level::Info => write!(f, "info"),
+            Level::Warning => write!(f, "warning"),
+            Level::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub id: usize,
+    pub time: DateTime<Local>,
+    pub level: Level,
+    pub message: String,
+    pub details: Option<String>,
+    /// Progress updates are replaced by the next entry instead of piling
+    /// up in the log.
+    transient: bool,
+}
+
+impl Entry {
+    pub fn timestamp(&self) -> String {
+        self.time.format("%H:%M:%S").to_string()
+    }
+}
+
+/// Everything that was reported to the user, oldest first.
+#[derive(Debug, Default)]
+pub struct Log {
+    entries: VecDeque<Entry>,
+    next_id: usize,
+}
+
+impl Log {
+    fn push(&mut self, level: Level, message: String, details: Option<String>, transient: bool) {
+        if self.entries.back().is_some_and(|e| e.transient) {
+            self.entries.pop_back();
+        }
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Entry {
+            id: self.next_id,
+            time: Local::now(),
+            level,
+            message,
+            details,
+            transient,
+        });
+        self.next_id += 1;
+    }
+
+    pub fn latest(&self) -> Option<&Entry> {
+        self.entries.back()
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Entry> {
+        self.entries.iter().find(|e| e.id == id)
+    }
+
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    /// The id the next entry will get, to find what was logged since.
+    pub fn next_id(&self) -> usize {
+        self.next_id
+    }
+
+    pub fn since(&self, id: usize) -> impl Iterator<Item = &Entry> {
+        self.entries.iter().filter(move |e| e.id >= id)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Every error of a report's chain, outermost first, numbered the way
+/// `color_eyre` prints them.
+pub fn report_chain(e: &Report) -> String {
+    e.chain()
+        .enumerate()
+        .map(|(i, cause)| format!("{:>4}: {}", i, cause))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+impl Keymui {
+    pub fn log_entry(&mut self, level: Level, message: impl Into<String>, details: Option<String>) {
+        self.log.push(level, message.into(), details, false);
+    }
+
+    pub fn info(&mut self, message: impl Into<String>) {
+        self.log_entry(Level::Info, message, None);
+    }
+
+    pub fn warn(&mut self, message: impl Into<String>) {
+        self.log_entry(Level::Warning, message, None);
+    }
+
+    /// Logs an error with its whole chain of causes as the details.
+    pub fn error(&mut self, message: impl Into<String>, e: &Report) {
+        self.log_entry(Level::Error, message, Some(report_chain(e)));
+    }
+
+    /// Shows how far along background work is. Only the latest update is
+    /// kept in the log.
+    pub fn progress(&mut self, message: impl Into<String>) {
+        self.log.push(Level::Info, message.into(), None, true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(log: &Log) -> Vec<&str> {
+        log.entries().map(|e| e.message.as_str()).collect()
+    }
+
+    #[test]
+    fn progress_is_replaced_by_the_next_entry() {
+        let mut log = Log::default();
+        log.push(Level::Info, "started".to_string(), None, false);
+        log.push(Level::Info, "1/3".to_string(), None, true);
+        log.push(Level::Info, "2/3".to_string(), None, true);
+        assert_eq!(messages(&log), ["started", "2/3"]);
+        log.push(Level::Warning, "done".to_string(), None, false);
+        assert_eq!(messages(&log), ["started", "done"]);
+        // replaced entries still use up their ids
+        assert_eq!(log.latest().unwrap().id, 3);
+        assert!(log.get(2).is_none());
+    }
+
+    #[test]
+    fn only_the_newest_entries_are_kept() {
+        let mut log = Log::default();
+        for i in 0..MAX_ENTRIES + 10 {
+            log.push(Level::Info, i.to_string(), None, false);
+        }
+        assert_eq!(log.entries().count(), MAX_ENTRIES);
+        assert_eq!(log.entries().next().unwrap().message, "10");
+        assert!(log.get(9).is_none());
+        assert_eq!(log.get(10).unwrap().message, "10");
+        assert_eq!(log.next_id(), MAX_ENTRIES + 10);
+        assert_eq!(log.since(log.next_id() - 2).count(), 2);
+    }
+}
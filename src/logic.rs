@@ -1,27 +1,39 @@
+use crate::charset;
 use crate::layout_display::{ColorStyle, LayoutDisplay};
-use crate::Keymui;
-use crate::{download, NstrokeSortMethod};
+use crate::log::Level;
+use crate::optimize::{self, Job};
+use crate::score::Score;
+use crate::{Config, DisplayStyle, NstrokeSortMethod};
+use crate::{Keymui, Message};
 use color_eyre::eyre::{anyhow, Context, ContextCompat, Result};
-use directories::BaseDirs;
-use kc::Corpus;
+use iced::{Application, Command};
+use kc::{Corpus, Swap};
 use km::{self, MetricContext};
 use std::ffi::OsStr;
 use std::fs;
-use std::path::PathBuf;
-
-pub fn initial_setup() {
-    let base_dirs = BaseDirs::new().unwrap();
-    let data_dir = base_dirs.data_dir().join("keymeow");
-    if data_dir.exists() {
-        return;
-    }
-    fs::create_dir_all(data_dir.join("layouts")).unwrap();
-    fs::create_dir_all(data_dir.join("corpora")).unwrap();
-    fs::create_dir_all(data_dir.join("metrics")).unwrap();
-    let _ = download::download_files(&data_dir);
-}
+use std::io;
+use std::path::{Path, PathBuf};
+use std::{env, iter};
 
 impl Keymui {
+    /// Creates the data dir and downloads the default data on first run.
+    pub fn initial_setup(&mut self) -> Result<()> {
+        let data_dir = self.data_dir();
+        let first_run = !data_dir.exists();
+        if first_run {
+            fs::create_dir_all(data_dir.join("layouts"))?;
+            fs::create_dir_all(data_dir.join("corpora"))?;
+            fs::create_dir_all(data_dir.join("metrics"))?;
+        }
+        // charsets were added later than the rest of the data dir
+        charset::install_defaults(&data_dir.join("charsets"))
+            .context("couldn't install the default charsets")?;
+        if first_run {
+            self.fetch_defaults();
+        }
+        Ok(())
+    }
+
     pub fn config_dir(&self) -> PathBuf {
         self.base_dirs.config_dir().join("keymeow")
     }
@@ -30,15 +42,61 @@ impl Keymui {
         self.base_dirs.data_dir().join("keymeow")
     }
 
+    /// Where new layouts are saved.
+    pub fn layouts_dir(&self) -> PathBuf {
+        self.data_dir().join("layouts")
+    }
+
+    /// Every directory layouts are loaded from. When two layouts have the
+    /// same name, the one from the earlier directory is used.
+    pub fn layout_dirs(&self) -> Vec<PathBuf> {
+        iter::once(self.layouts_dir())
+            .chain(self.config.layout_directories.iter().cloned())
+            .collect()
+    }
+
+    pub fn corpora_dir(&self) -> PathBuf {
+        self.config
+            .corpora_directory
+            .clone()
+            .unwrap_or_else(|| self.data_dir().join("corpora"))
+    }
+
+    pub fn metric_data_dir(&self) -> PathBuf {
+        self.config
+            .metric_data_directory
+            .clone()
+            .unwrap_or_else(|| self.data_dir().join("metrics"))
+    }
+
+    /// Expands `~/` and makes a path given in the palette absolute.
+    pub fn expand_path(&self, path: &str) -> Result<PathBuf> {
+        Ok(match path.strip_prefix("~/") {
+            Some(rest) => self.base_dirs.home_dir().join(rest),
+            None => env::current_dir()?.join(path),
+        })
+    }
+
+    /// Reads the config file. Before the first save there is none, and the
+    /// defaults are used.
     pub fn load_config(&mut self) -> Result<()> {
         let cdir = self.config_dir();
         fs::create_dir_all(&cdir)?;
         let path = cdir.join("config.json");
-        self.config = serde_json::from_str(&fs::read_to_string(&path).context(format!(
-            "couldn't read config file from path {}",
-            &path.display()
-        ))?)
-        .context("couldn't parse config file")?;
+        let s = match fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.config = Config::default();
+                return Ok(());
+            }
+            Err(e) => {
+                return Err(e).context(format!(
+                    "couldn't read config file from path {}",
+                    &path.display()
+                ))
+            }
+        };
+        self.config = serde_json::from_str(&s).context("couldn't parse config file")?;
         Ok(())
     }
 
@@ -51,65 +109,114 @@ impl Keymui {
         Ok(())
     }
 
-    pub fn load_layouts(&mut self) -> Result<()> {
-        let ldir = self.data_dir().join("layouts");
-        fs::create_dir_all(&ldir)?;
-        for entry in fs::read_dir(ldir).context("couldn't read layouts directory")? {
-            let path = entry?.path();
-            let s = fs::read_to_string(&path)
-                .with_context(|| format!("couldn't read file {}", &path.display()))?;
-            let layout: km::LayoutData = serde_json::from_str(&s)
-                .with_context(|| format!("couldn't parse layout file {}", &path.display()))?;
-            self.layouts
-                .insert(layout.name.clone().to_lowercase().replace(' ', "-"), layout);
+    /// Replaces the problems recorded for the files in `dir`.
+    fn set_problems(&mut self, dir: &Path, found: Vec<(PathBuf, String)>) {
+        self.problems.retain(|path, _| !path.starts_with(dir));
+        self.problems.extend(found);
+    }
+
+    /// Logs every file that couldn't be loaded, with the reason.
+    /// Returns `false` if there are none.
+    pub fn report_problems(&mut self) -> bool {
+        if self.problems.is_empty() {
+            return false;
         }
-        Ok(())
+        let details = self
+            .problems
+            .iter()
+            .map(|(path, e)| format!("{}\n    {}", path.display(), e))
+            .collect::<Vec<String>>()
+            .join("\n\n");
+        self.log_entry(
+            Level::Warning,
+            format!("{} files couldn't be loaded", self.problems.len()),
+            Some(details),
+        );
+        true
     }
 
-    pub fn import_corpus(&mut self, file: PathBuf) -> Result<()> {
-        let cdir = self.data_dir().join("corpora");
-        fs::create_dir_all(&cdir)?;
+    /// The files of a data directory, skipping hidden ones. Entries that
+    /// can't be read are recorded in `problems`.
+    fn dir_files(dir: &Path, problems: &mut Vec<(PathBuf, String)>) -> Result<Vec<PathBuf>> {
+        fs::create_dir_all(dir)?;
+        let mut files = vec![];
+        for entry in
+            fs::read_dir(dir).with_context(|| format!("couldn't read {}", &dir.display()))?
+        {
+            match entry {
+                Ok(entry) if entry.file_name().to_string_lossy().starts_with('.') => (),
+                Ok(entry) => files.push(entry.path()),
+                Err(e) => problems.push((dir.to_path_buf(), e.to_string())),
+            }
+        }
+        Ok(files)
+    }
+
+    pub fn read_layout(path: &Path) -> Result<km::LayoutData> {
+        let s = fs::read_to_string(path)
+            .with_context(|| format!("couldn't read file {}", &path.display()))?;
+        serde_json::from_str(&s)
+            .with_context(|| format!("couldn't parse layout file {}", &path.display()))
+    }
 
-        // TODO generalize
-        let mut char_list = "abcdefghijklmnopqrstuvwxyz"
-            .chars()
-            .map(|c| vec![c, c.to_uppercase().next().unwrap()])
-            .collect::<Vec<Vec<char>>>();
-        char_list.extend(vec![
-            vec![' '],
-            vec![',', '<'],
-            vec!['.', '>'],
-            vec!['/', '?'],
-            vec!['\'', '"'],
-            vec![';', ':'],
-            vec!['1', '!'],
-            vec!['2', '@'],
-            vec!['3', '#'],
-            vec!['4', '$'],
-            vec!['5', '%'],
-            vec!['6', '^'],
-            vec!['7', '&'],
-            vec!['8', '*'],
-            vec!['9', '('],
-            vec!['0', ')'],
-            vec!['-', '_'],
-            vec!['=', '+'],
-            vec!['[', '{'],
-            vec![']', '}'],
-            vec!['\\', '|'],
-            vec!['`', '~'],
-        ]);
-        let mut corpus = Corpus::with_char_list(char_list);
-
-        corpus.add_file(&file)?;
-
-        let bin = rmp_serde::to_vec(&corpus)?;
-        let mut new_path = cdir.join(file.file_stem().ok_or(anyhow!("couldn't get path stem"))?);
-        new_path.set_extension("corpus");
-        fs::write(new_path, bin)?;
+    /// Loads every layout that parses from all layout directories,
+    /// recording the others and name conflicts in `problems`.
+    pub fn load_layouts(&mut self) -> Result<()> {
+        self.layouts.clear();
+        self.layout_sources.clear();
+        for (i, ldir) in self.layout_dirs().into_iter().enumerate() {
+            let mut problems = vec![];
+            // only the data dir's layouts folder is created when missing
+            let files = match i > 0 && !ldir.is_dir() {
+                true => Err(anyhow!("layout directory doesn't exist")),
+                false => Self::dir_files(&ldir, &mut problems),
+            };
+            let files = files.unwrap_or_else(|e| {
+                problems.push((ldir.clone(), format!("{:#}", e)));
+                vec![]
+            });
+            for path in files {
+                let layout = match Self::read_layout(&path) {
+                    Ok(layout) => layout,
+                    Err(e) => {
+                        problems.push((path, format!("{:#}", e)));
+                        continue;
+                    }
+                };
+                let name = layout.name.to_lowercase().replace(' ', "-");
+                if let Some(other) = self.layout_sources.get(&name) {
+                    let conflict =
+                        format!("layout {} is already loaded from {}", name, other.display());
+                    problems.push((path, conflict));
+                    continue;
+                }
+                self.layout_sources.insert(name.clone(), path);
+                self.layouts.insert(name, layout);
+            }
+            self.set_problems(&ldir, problems);
+        }
         Ok(())
     }
 
+    /// Saves the config and reloads layouts after a layout directory was
+    /// added or removed.
+    pub fn layout_directories_changed(&mut self) {
+        if let Err(e) = self.save_config() {
+            self.error("couldn't save the config", &e);
+        }
+        if let Err(e) = self.load_layouts() {
+            self.error("couldn't load layouts", &e);
+        }
+        let current = self.current_layout.as_ref();
+        if !current.is_some_and(|l| self.layouts.contains_key(l)) {
+            self.current_layout = self.layouts.keys().next().cloned();
+            if let Err(e) = self.load_data() {
+                self.error("couldn't load the analysis data", &e);
+            }
+        }
+        self.refresh_comparison();
+    }
+
     pub fn import_metrics(&mut self) -> Result<()> {
         let mut added = false;
         let path: &PathBuf = self
@@ -134,7 +241,7 @@ impl Keymui {
                     self.metric_lists.insert(name.clone(), path.clone());
                     added = true;
 
-                    let mdir = self.base_dirs.data_dir().join("keymeow").join("metrics");
+                    let mdir = self.metric_data_dir();
                     let newpath =
                         mdir.join(path.file_name().ok_or(anyhow!("couldn't get filename"))?);
                     let b = fs::read(&path).with_context(|| {
@@ -156,22 +263,38 @@ impl Keymui {
     }
 
     pub fn set_corpus_list(&mut self) -> Result<()> {
-        let cdir = self.data_dir().join("corpora");
-        fs::create_dir_all(&cdir)?;
-
-        for entry in fs::read_dir(cdir).context("couldn't read corpus directory")? {
-            let path = entry?.path();
-            let name = path
-                .file_stem()
-                .ok_or(anyhow!("couldn't get path stem"))?
-                .to_string_lossy()
-                .to_string();
-            self.corpora.insert(name, path);
+        let cdir = self.corpora_dir();
+        let mut problems = vec![];
+        // rebuilt from scratch so deleted files disappear
+        self.corpora.clear();
+        let files = match Self::dir_files(&cdir, &mut problems) {
+            Ok(files) => files,
+            Err(e) => {
+                // whatever was wrong with the folder's files no longer applies
+                self.set_problems(&cdir, problems);
+                return Err(e);
+            }
+        };
+        for path in files {
+            // the pipelines and charsets saved next to corpora aren't
+            // corpora themselves
+            let path_str = path.to_string_lossy();
+            if path_str.ends_with(".pipeline.json") || path_str.ends_with(".charset.json") {
+                continue;
+            }
+            match (path.extension(), path.file_stem()) {
+                (Some(ext), Some(stem)) if ext == OsStr::new("corpus") => {
+                    self.corpora
+                        .insert(stem.to_string_lossy().to_string(), path.clone());
+                }
+                _ => problems.push((path, "not a .corpus file".to_string())),
+            }
         }
+        self.set_problems(&cdir, problems);
         Ok(())
     }
 
-    pub fn load_data(&mut self) -> Result<()> {
+    pub fn read_metrics(&self) -> Result<km::MetricData> {
         let path = self
             .metric_lists
             .get(
@@ -182,18 +305,26 @@ impl Keymui {
             )
             .context("metric data doesn't exist")?;
         let b = fs::read(path).context("couldn't read metrics file")?;
-        let metrics: km::MetricData =
-            rmp_serde::from_slice(&b).context("couldn't deserialize metrics")?;
+        rmp_serde::from_slice(&b).context("couldn't deserialize metrics")
+    }
 
+    pub fn read_corpus(&self) -> Result<Corpus> {
         let corpus = self.current_corpus.clone().context("no corpus selected")?;
         let path = self.corpora.get(&corpus).context("corpus doesn't exist")?;
         let b = fs::read(path).context("couldn't read corpus file")?;
-        let corpus: Corpus = rmp_serde::from_slice(&b).context("couldn't deserialize corpus")?;
+        rmp_serde::from_slice(&b).context("couldn't deserialize corpus")
+    }
 
+    pub fn load_data(&mut self) -> Result<()> {
+        if let Some(optimizer) = self.optimizer.take() {
+            optimizer.cancel();
+        }
+        let metrics = self.read_metrics()?;
+        let corpus = self.read_corpus()?;
+
+        let layout = self.current_layout.clone().context("no layout selected")?;
         let mut context = MetricContext::new(
-            self.layouts
-                .get(&self.current_layout.clone().context("no layout selected")?)
-                .context("layout doesn't exist")?,
+            self.layouts.get(&layout).context("layout doesn't exist")?,
             metrics,
             corpus,
         )
@@ -216,81 +347,296 @@ impl Keymui {
             self.nstrokes_metric,
         ));
         self.metric_context = Some(context);
+        self.restore_tree(&layout);
+        self.refresh_corpus_stats();
 
-        self.set_nstroke_list();
-        self.sort_nstroke_list();
+        self.refresh_layout_views();
 
         Ok(())
     }
 
+    /// Swaps the keys at two layout positions, updating `layout_stats`
+    /// incrementally. Returns the per-metric deltas the swap produced.
+    pub fn swap_positions(&mut self, a: usize, b: usize) -> Vec<f32> {
+        if let Some(ctx) = &mut self.metric_context {
+            let swap = Swap::new(a, b);
+            let mut diffs = vec![0.0; ctx.analyzer.data.metrics.len()];
+            ctx.analyzer.swap_diff(&mut diffs, &ctx.layout, &swap);
+            ctx.layout.swap(&swap);
+            self.layout_stats
+                .iter_mut()
+                .zip(diffs.iter())
+                .for_each(|(v, diff)| *v += diff);
+            diffs
+        } else {
+            vec![]
+        }
+    }
+
+    pub fn start_optimizer(&mut self, iterations: usize) -> Result<Command<Message>> {
+        if self.optimizer.is_some() {
+            return Err(anyhow!("the optimizer is already running"));
+        }
+        let ctx = self.metric_context.as_ref().context("no layout loaded")?;
+        let score = self
+            .current_score()
+            .context("no score set, use set-score or set-weight first")?;
+        let totals = ctx.layout.totals(&ctx.analyzer.corpus);
+        // scores weigh percentages, so scale the weights to raw stats
+        let weights: Vec<f32> = ctx
+            .metrics
+            .iter()
+            .map(|m| score.weight(&m.short) * totals.percentage(1.0, m.ngram_type))
+            .collect();
+        if weights.iter().all(|w| *w == 0.0) {
+            return Err(anyhow!("the score doesn't use any loaded metric"));
+        }
+        let pinned: Vec<usize> = self
+            .pinned
+            .iter()
+            .map(|c| ctx.analyzer.corpus.corpus_char(*c))
+            .collect();
+        let movable: Vec<usize> = ctx
+            .layout
+            .0
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !pinned.contains(*c))
+            .map(|(i, _)| i)
+            .collect();
+
+        let layout = self.current_layout.clone().context("no layout selected")?;
+        let mut context = MetricContext::new(
+            self.layouts.get(&layout).context("layout doesn't exist")?,
+            self.read_metrics()?,
+            self.read_corpus()?,
+        )
+        .context("couldn't create metric context for the optimizer")?;
+        context.layout.0.clone_from(&ctx.layout.0);
+
+        let job = Job {
+            context,
+            stats: self.layout_stats.clone(),
+            weights,
+            movable,
+            iterations,
+        };
+        self.info("optimizer started");
+        if self.headless {
+            // a script's next command needs the result, so don't return
+            // before the search is done
+            let (optimizer, finished) = optimize::run(job);
+            self.optimizer = Some(optimizer);
+            return Ok(self.update(Message::OptimizerProgress(finished)));
+        }
+        let (optimizer, progress) = optimize::start(job);
+        self.optimizer = Some(optimizer);
+        Ok(Command::run(progress, Message::OptimizerProgress))
+    }
+
+    /// The parsed score of the selected metric data, if one is set.
+    pub fn current_score(&self) -> Option<Score> {
+        let score: Score = self
+            .config
+            .scores
+            .get(self.current_metrics.as_ref()?)?
+            .parse()
+            .ok()?;
+        (!score.is_empty()).then_some(score)
+    }
+
+    pub fn set_score(&mut self, score: Score) {
+        if let Some(metrics) = &self.current_metrics {
+            let unknown: Vec<&str> = score
+                .terms
+                .iter()
+                .map(|(_, short)| short.as_str())
+                .filter(|short| {
+                    self.metric_context
+                        .as_ref()
+                        .is_some_and(|ctx| !ctx.metrics.iter().any(|m| m.short == *short))
+                })
+                .collect();
+            if unknown.is_empty() {
+                self.info(format!("score set to {}", score));
+            } else {
+                self.log_entry(
+                    Level::Warning,
+                    format!("score set to {}", score),
+                    Some(format!("unknown metrics: {}", unknown.join(", "))),
+                );
+            }
+            self.config
+                .scores
+                .insert(metrics.clone(), score.to_string());
+        } else {
+            self.warn("no metrics selected");
+        }
+    }
+
+    /// Evaluates the current score for stats computed with `ctx`.
+    pub fn score_of(&self, ctx: &MetricContext, stats: &[f32]) -> Option<f32> {
+        let score = self.current_score()?;
+        let totals = ctx.layout.totals(&ctx.analyzer.corpus);
+        Some(
+            score.evaluate(
+                ctx.metrics
+                    .iter()
+                    .zip(stats.iter())
+                    .map(|(m, s)| (m.short.as_str(), totals.percentage(*s, m.ngram_type))),
+            ),
+        )
+    }
+
+    pub fn score_of_current(&self) -> Option<f32> {
+        self.score_of(self.metric_context.as_ref()?, &self.layout_stats)
+    }
+
+    pub fn refresh_layout_views(&mut self) {
+        if let (Some(ctx), Some(display)) = (&self.metric_context, &mut self.layout_display) {
+            display.update_keys(ctx, self.nstrokes_metric);
+            display.redraw();
+        }
+        self.set_nstroke_list();
+        self.sort_nstroke_list();
+    }
+
     pub fn set_metric_list(&mut self) -> Result<()> {
-        let mdir = self.data_dir().join("metrics");
-        fs::create_dir_all(&mdir)?;
-
-        for entry in fs::read_dir(mdir)? {
-            let path = entry?.path();
-            let name = path
-                .file_stem()
-                .ok_or(anyhow!("couldn't get path stem"))?
-                .to_string_lossy()
-                .to_string();
-            self.metric_lists.insert(name, path);
+        let mdir = self.metric_data_dir();
+        let mut problems = vec![];
+        self.metric_lists.clear();
+        let files = match Self::dir_files(&mdir, &mut problems) {
+            Ok(files) => files,
+            Err(e) => {
+                self.set_problems(&mdir, problems);
+                return Err(e);
+            }
+        };
+        for path in files {
+            match (path.extension(), path.file_stem()) {
+                (Some(ext), Some(stem)) if ext == OsStr::new("metrics") => {
+                    self.metric_lists
+                        .insert(stem.to_string_lossy().to_string(), path.clone());
+                }
+                _ => problems.push((path, "not a .metrics file".to_string())),
+            }
         }
+        self.set_problems(&mdir, problems);
         Ok(())
     }
 
     pub fn set_nstroke_list(&mut self) {
         if let Some(ctx) = &self.metric_context {
-            self.nstrokes_list = Vec::with_capacity(ctx.analyzer.data.strokes.len() / 3);
-            let totals = ctx.layout.totals(&ctx.analyzer.corpus);
-            for (i, stroke) in ctx.analyzer.data.strokes.iter().enumerate() {
-                let amount = stroke
-                    .amounts
-                    .iter()
-                    .find(|m| m.metric == self.nstrokes_metric);
-                if let Some(amt) = amount {
-                    let count = ctx.layout.frequency(
-                        &ctx.analyzer.corpus,
-                        &stroke.nstroke,
-                        Some(ctx.analyzer.data.metrics[self.nstrokes_metric]),
-                    );
-                    let freq_display =
-                        totals.percentage(count as f32, ctx.analyzer.data.metrics[amt.metric]);
-                    let nstroke = ctx.layout.nstroke_chars(&stroke.nstroke);
-
-                    if !nstroke.iter().any(|c| *c == 0) {
-                        self.nstrokes_list.push((
-                            i,
-                            nstroke
-                                .iter()
-                                .map(|c| ctx.analyzer.corpus.uncorpus_unigram(*c))
-                                .map(|c| match c {
-                                    ' ' => '␣',
-                                    _ => c,
-                                })
-                                .collect::<String>(),
-                            freq_display,
-                            amt.amount,
-                        ))
-                    };
-                }
+            self.nstrokes_list = Self::metric_nstrokes(ctx, self.nstrokes_metric);
+        }
+    }
+
+    /// Every nstroke a metric counts on the current layout: its stroke
+    /// index, characters, frequency and amount, in stroke order.
+    pub fn metric_nstrokes(ctx: &MetricContext, metric: usize) -> Vec<(usize, String, f32, f32)> {
+        let mut list = Vec::with_capacity(ctx.analyzer.data.strokes.len() / 3);
+        let totals = ctx.layout.totals(&ctx.analyzer.corpus);
+        for (i, stroke) in ctx.analyzer.data.strokes.iter().enumerate() {
+            let amount = stroke.amounts.iter().find(|m| m.metric == metric);
+            if let Some(amt) = amount {
+                let count = ctx.layout.frequency(
+                    &ctx.analyzer.corpus,
+                    &stroke.nstroke,
+                    Some(ctx.analyzer.data.metrics[metric]),
+                );
+                let freq_display =
+                    totals.percentage(count as f32, ctx.analyzer.data.metrics[amt.metric]);
+                let nstroke = ctx.layout.nstroke_chars(&stroke.nstroke);
+
+                if !nstroke.iter().any(|c| *c == 0) {
+                    list.push((
+                        i,
+                        nstroke
+                            .iter()
+                            .map(|c| ctx.analyzer.corpus.uncorpus_unigram(*c))
+                            .map(|c| match c {
+                                ' ' => '␣',
+                                _ => c,
+                            })
+                            .collect::<String>(),
+                        freq_display,
+                        amt.amount,
+                    ))
+                };
             }
         }
+        list
+    }
+
+    /// Formats a metric's percentage according to its configured
+    /// `DisplayStyle`.
+    pub fn format_stat(&self, short: &str, percentage: f32) -> String {
+        match self
+            .config
+            .metric_display_styles
+            .get(short)
+            .map(|x| &x.display_style)
+            .unwrap_or(&DisplayStyle::Ratio)
+        {
+            DisplayStyle::Ratio => format!(
+                "{}/{:.0}",
+                self.config.stat_precision,
+                self.config.stat_precision as f32 / (percentage / 100.)
+            ),
+            DisplayStyle::Percentage => format!("{:.2}%", percentage),
+        }
+    }
+
+    pub fn stats_table(&self) -> Option<String> {
+        let ctx = self.metric_context.as_ref()?;
+        let totals = ctx.layout.totals(&ctx.analyzer.corpus);
+        let width = ctx.metrics.iter().map(|m| m.name.len()).max().unwrap_or(0);
+        Some(
+            ctx.metrics
+                .iter()
+                .zip(self.layout_stats.iter())
+                .map(|(m, stat)| {
+                    format!(
+                        "{:width$}  {}",
+                        m.name,
+                        self.format_stat(&m.short, totals.percentage(*stat, m.ngram_type)),
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join("\n"),
+        )
+    }
+
+    pub fn nstrokes_table(&self, count: usize) -> String {
+        self.nstrokes_list
+            .iter()
+            .take(count)
+            .map(|(_, nstroke, freq, amount)| format!("{}  {:.2}%  {:.3}", nstroke, freq, amount))
+            .collect::<Vec<String>>()
+            .join("\n")
     }
 
     pub fn sort_nstroke_list(&mut self) {
         if let Some(ctx) = &self.metric_context {
-            let method = self
-                .config
-                .metric_display_styles
-                .get(&ctx.metrics[self.nstrokes_metric].short)
-                .map(|x| x.nstroke_sort_method)
-                .unwrap_or_default();
-            self.nstrokes_list.sort_by(|a, b| match method {
-                NstrokeSortMethod::Frequency => a.2.partial_cmp(&b.2).unwrap(),
-                NstrokeSortMethod::Value => a.3.partial_cmp(&b.3).unwrap(),
-            });
-            self.nstrokes_list.reverse();
+            let method = self.nstroke_sort_method(&ctx.metrics[self.nstrokes_metric].short);
+            Self::sort_nstrokes(&mut self.nstrokes_list, method);
         }
     }
+
+    pub fn nstroke_sort_method(&self, short: &str) -> NstrokeSortMethod {
+        self.config
+            .metric_display_styles
+            .get(short)
+            .map(|x| x.nstroke_sort_method)
+            .unwrap_or_default()
+    }
+
+    /// Sorts nstrokes from the highest frequency or amount down.
+    pub fn sort_nstrokes(list: &mut [(usize, String, f32, f32)], method: NstrokeSortMethod) {
+        list.sort_by(|a, b| match method {
+            NstrokeSortMethod::Frequency => a.2.partial_cmp(&b.2).unwrap(),
+            NstrokeSortMethod::Value => a.3.partial_cmp(&b.3).unwrap(),
+        });
+        list.reverse();
+    }
 }
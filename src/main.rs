@@ -1,10 +1,34 @@
+mod blend;
+mod browser;
+mod charset;
 mod commands;
+mod comparison;
+mod corpus_import;
+mod corpus_stats;
 mod download;
+mod editor;
+mod export;
+mod headless;
 mod layout_display;
+mod layout_import;
+mod log;
 mod logic;
+mod optimize;
+mod preprocess;
+mod render;
+mod report;
+mod score;
+mod tree;
+mod watch;
+use browser::{Analysis, Browser, KeyboardFilter, Summary, MAX_SHOWN};
 use commands::{commonest_completion, UserCommand};
+use comparison::{Better, ComparedLayout, SortKey};
 use core::fmt;
+use corpus_import::{CorpusImport, ImportProgress};
+use corpus_stats::{show_ngram, CorpusStats, NgramList};
 use directories::BaseDirs;
+use download::{DataSource, FetchSource, FetchSummary};
+use editor::{Editor, EditorStart};
 use iced::event::{self, Event};
 use iced::theme;
 use iced::widget::pane_grid::{self, Axis, PaneGrid};
@@ -16,18 +40,31 @@ use iced::{
     alignment, executor, Application, Command, Element, Font, Length, Settings, Subscription, Theme,
 };
 use iced_aw::{modal, Card};
-use kc::Swap;
 use km::{LayoutData, MetricContext};
 use layout_display::{ColorStyle, LayoutDisplay};
+use layout_import::Mapping;
+use log::{Level, Log};
+use optimize::{Optimizer, Progress};
 use rfd::FileDialog;
+use score::Score;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap};
-use std::iter;
+use std::cell::OnceCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::path::PathBuf;
+use std::{env, iter, process};
+use tree::LayoutTree;
+use watch::Watched;
 
 pub fn main() -> iced::Result {
     color_eyre::install().unwrap();
-    logic::initial_setup();
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some(source) = headless::ScriptSource::from_args(&args) {
+        if let Err(e) = headless::run(source) {
+            eprintln!("{:?}", e);
+            process::exit(1);
+        }
+        return Ok(());
+    }
     Keymui::run(Settings {
         antialiasing: true,
         window: iced::window::Settings {
@@ -61,6 +98,17 @@ pub enum KeymuiTheme {
     CatppuccinMocha,
 }
 
+impl KeymuiTheme {
+    pub fn iced_theme(self) -> Theme {
+        match self {
+            KeymuiTheme::Light => Theme::Light,
+            KeymuiTheme::Dark => Theme::Dark,
+            KeymuiTheme::TokyoNight => Theme::TokyoNight,
+            KeymuiTheme::CatppuccinMocha => Theme::CatppuccinMocha,
+        }
+    }
+}
+
 impl fmt::Display for KeymuiTheme {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -80,10 +128,25 @@ impl fmt::Display for KeymuiTheme {
 #[serde(default)]
 pub struct Config {
     metrics_directory: Option<PathBuf>,
+    /// Layouts are loaded from these as well as from the data dir.
+    layout_directories: Vec<PathBuf>,
+    /// Overrides the data dir's `corpora` folder.
+    corpora_directory: Option<PathBuf>,
+    /// Overrides the data dir's `metrics` folder.
+    metric_data_directory: Option<PathBuf>,
+    /// Where `fetch-data` gets layouts, metrics and corpora from.
+    data_sources: Vec<DataSource>,
     metric_display_styles: HashMap<String, MetricDisplayConfig>,
     stat_precision: u32,
     use_monospace: bool,
     theme: KeymuiTheme,
+    /// Scoring expression per metric data set.
+    scores: HashMap<String, String>,
+    /// Credited in the layouts that are saved.
+    authors: Vec<String>,
+    /// Percentage of the corpus a character must make up for the editor
+    /// to require a key for it.
+    required_char_share: f32,
 }
 
 #[derive(Serialize, Deserialize, Default)]
@@ -97,6 +160,10 @@ impl Default for Config {
     fn default() -> Self {
         Config {
             metrics_directory: None,
+            layout_directories: vec![],
+            corpora_directory: None,
+            metric_data_directory: None,
+            data_sources: DataSource::defaults(),
             metric_display_styles: HashMap::from([
                 (
                     "roll".to_string(),
@@ -130,13 +197,20 @@ impl Default for Config {
             stat_precision: 1,
             use_monospace: true,
             theme: Default::default(),
+            scores: HashMap::new(),
+            authors: vec![],
+            required_char_share: 0.01,
         }
     }
 }
 
 pub struct Keymui {
-    notification: (String, Option<String>),
-    show_notif_modal: bool,
+    log: Log,
+    /// The log entry whose details are shown in a modal.
+    shown_entry: Option<usize>,
+    /// Set when running without a window, so that background work runs to
+    /// completion instead.
+    headless: bool,
     panes: pane_grid::State<Pane>,
     commands: Vec<UserCommand>,
     command_input: String,
@@ -152,11 +226,27 @@ pub struct Keymui {
     layout_stats: Vec<f32>,
     metric_lists: BTreeMap<String, PathBuf>,
     layouts: BTreeMap<String, LayoutData>,
+    /// The file each layout was loaded from.
+    layout_sources: BTreeMap<String, PathBuf>,
     corpora: BTreeMap<String, PathBuf>,
 
     nstrokes_metric: usize,
     nstrokes_list: Vec<(usize, String, f32, f32)>,
     keyboard_size: usize,
+    trees: HashMap<String, LayoutTree>,
+    tree_diff: Option<usize>,
+    pinned: BTreeSet<char>,
+    optimizer: Option<Optimizer>,
+    comparison: Vec<ComparedLayout>,
+    comparison_sort: Option<(SortKey, bool)>,
+    /// Filled in when the Corpus pane first shows the loaded corpus.
+    corpus_stats: OnceCell<CorpusStats>,
+    corpus_search: String,
+    browser: Browser,
+    /// The new layout being made, shown instead of the current one.
+    editor: Option<Editor>,
+    /// Files that couldn't be loaded, with the reason.
+    problems: BTreeMap<PathBuf, String>,
 
     config: Config,
 }
@@ -193,20 +283,124 @@ impl Application for Keymui {
             Pane::new(PaneKind::Nstrokes),
         );
 
+        panes.split(
+            Axis::Vertical,
+            *panes
+                .panes
+                .iter()
+                .find(|p| matches!(p.1.kind, PaneKind::Nstrokes))
+                .unwrap()
+                .0,
+            Pane::new(PaneKind::History),
+        );
+
+        panes.split(
+            Axis::Horizontal,
+            *panes
+                .panes
+                .iter()
+                .find(|p| matches!(p.1.kind, PaneKind::History))
+                .unwrap()
+                .0,
+            Pane::new(PaneKind::Tree),
+        );
+
+        panes.split(
+            Axis::Vertical,
+            *panes
+                .panes
+                .iter()
+                .find(|p| matches!(p.1.kind, PaneKind::Metrics))
+                .unwrap()
+                .0,
+            Pane::new(PaneKind::Comparison),
+        );
+
+        panes.split(
+            Axis::Horizontal,
+            *panes
+                .panes
+                .iter()
+                .find(|p| matches!(p.1.kind, PaneKind::Comparison))
+                .unwrap()
+                .0,
+            Pane::new(PaneKind::Corpus),
+        );
+
+        panes.split(
+            Axis::Horizontal,
+            *panes
+                .panes
+                .iter()
+                .find(|p| matches!(p.1.kind, PaneKind::Corpus))
+                .unwrap()
+                .0,
+            Pane::new(PaneKind::Log),
+        );
+
+        panes.split(
+            Axis::Horizontal,
+            *panes
+                .panes
+                .iter()
+                .find(|p| matches!(p.1.kind, PaneKind::Layout))
+                .unwrap()
+                .0,
+            Pane::new(PaneKind::Browser),
+        );
+
         let commands = vec![
             UserCommand::SetMetricsDirectory,
+            UserCommand::SetLayoutDirectory,
+            UserCommand::RemoveLayoutDirectory,
             UserCommand::Reload,
+            UserCommand::FetchData,
+            UserCommand::DataStatus,
+            UserCommand::AddDataSource,
+            UserCommand::RemoveDataSource,
             UserCommand::ImportCorpus,
+            UserCommand::ImportCorpusDir,
+            UserCommand::ImportCorpusFrom,
+            UserCommand::RebuildCorpus,
+            UserCommand::BlendCorpora,
             UserCommand::ViewNotification,
+            UserCommand::Problems,
             UserCommand::Swap,
             UserCommand::Precision,
             UserCommand::NgramFrequency,
             UserCommand::SaveLayout,
+            UserCommand::NewLayout,
+            UserCommand::ImportLayout,
+            UserCommand::ExportLayout,
+            UserCommand::ExportReport,
+            UserCommand::RenderLayout,
+            UserCommand::SetAuthors,
+            UserCommand::Undo,
+            UserCommand::Redo,
+            UserCommand::GotoNode,
+            UserCommand::RenameNode,
+            UserCommand::DeleteNode,
+            UserCommand::DiffNode,
+            UserCommand::SelectLayout,
+            UserCommand::SelectMetrics,
+            UserCommand::SelectCorpus,
+            UserCommand::Stats,
+            UserCommand::Nstrokes,
+            UserCommand::Optimize,
+            UserCommand::StopOptimize,
+            UserCommand::SetWeight,
+            UserCommand::SetScore,
+            UserCommand::Pin,
+            UserCommand::Unpin,
+            UserCommand::Compare,
+            UserCommand::Uncompare,
+            UserCommand::ClearComparison,
         ];
 
         let mut keymui = Self {
-            notification: ("started".to_string(), None),
-            show_notif_modal: false,
+            log: Log::default(),
+            shown_entry: None,
+            headless: false,
             panes,
             commands,
             command_input: "".to_string(),
@@ -221,30 +415,54 @@ impl Application for Keymui {
             base_dirs: BaseDirs::new().unwrap(),
             metric_lists: BTreeMap::new(),
             layouts: BTreeMap::new(),
+            layout_sources: BTreeMap::new(),
             corpora: BTreeMap::new(),
 
             nstrokes_metric: 0,
             nstrokes_list: vec![],
 
             keyboard_size: 0,
+            trees: HashMap::new(),
+            tree_diff: None,
+            pinned: BTreeSet::new(),
+            optimizer: None,
+            comparison: vec![],
+            comparison_sort: None,
+            corpus_stats: OnceCell::new(),
+            corpus_search: String::new(),
+            browser: Browser::default(),
+            editor: None,
+            problems: BTreeMap::new(),
 
             config: Config::default(),
         };
-        let e = keymui.load_layouts();
-        if let Err(e) = e {
-            println!("{:?}", e);
+        keymui.info("started");
+        // the config can move the data directories, so it's loaded first
+        if let Err(e) = keymui.load_config() {
+            keymui.error("couldn't load the config", &e);
+        }
+        if let Err(e) = keymui.initial_setup() {
+            keymui.error("couldn't set up the data directory", &e);
+        }
+        if let Err(e) = keymui.load_layouts() {
+            keymui.error("couldn't load layouts", &e);
+        }
+        if let Err(e) = keymui.set_corpus_list() {
+            keymui.error("couldn't list corpora", &e);
+        }
+        if let Err(e) = keymui.set_metric_list() {
+            keymui.error("couldn't list metrics", &e);
         }
-        let _ = keymui.set_corpus_list();
-        let _ = keymui.set_metric_list();
         keymui.current_layout = keymui.layouts.keys().next().cloned();
         keymui.current_metrics = keymui.metric_lists.keys().next().cloned();
         keymui.current_corpus = keymui.corpora.keys().next().cloned();
         if let Err(e) = keymui.load_data() {
-            println!("{:?}", e);
+            keymui.error("couldn't load the analysis data", &e);
         }
-        if let Err(e) = keymui.load_config() {
-            println!("{:?}", e);
+        if keymui.missing_data_hint().is_some() {
+            keymui.report_data_status();
         }
+        keymui.report_problems();
         keymui.input_options = keymui
             .commands
             .iter()
@@ -266,6 +484,7 @@ impl Application for Keymui {
         let pane_grid = PaneGrid::new(&self.panes, |_, pane, _| {
             pane_grid::Content::new(responsive(|_| {
                 match pane.kind {
+                    PaneKind::Layout if self.editor.is_some() => self.editor_view(),
                     PaneKind::Layout => {
                         // Layout view
                         column![
@@ -277,6 +496,14 @@ impl Application for Keymui {
                                 self.current_layout.clone(),
                                 Message::LayoutSelected
                             ),
+                            text(
+                                self.current_layout
+                                    .as_ref()
+                                    .and_then(|l| self.layout_sources.get(l))
+                                    .map(|p| p.display().to_string())
+                                    .unwrap_or_default()
+                            )
+                            .size(12),
                             if let Some(display) = &self.layout_display {
                                 container(
                                     column![
@@ -368,6 +595,11 @@ impl Application for Keymui {
                             ))
                             .width(Length::FillPortion(1)),
                         ],
+                        text(match self.score_of_current() {
+                            Some(score) => format!("score: {:.3}", score),
+                            None => "no score set".to_string(),
+                        })
+                        .size(18),
                         if let Some(context) = &self.metric_context {
                             let totals = context.layout.totals(&context.analyzer.corpus);
                             scrollable(column(
@@ -385,32 +617,13 @@ impl Application for Keymui {
                                             )
                                             .width(Length::FillPortion(3)),
                                             container(
-                                                button(text(
-                                                    match self
-                                                        .config
-                                                        .metric_display_styles
-                                                        .get(&context.metrics[i].short)
-                                                        .unwrap_or(&MetricDisplayConfig::default())
-                                                        .display_style
-                                                    {
-                                                        DisplayStyle::Ratio => format!(
-                                                            "{}/{:.0}",
-                                                            self.config.stat_precision,
-                                                            self.config.stat_precision as f32
-                                                                / (totals.percentage(
-                                                                    self.layout_stats[i],
-                                                                    m.ngram_type
-                                                                ) / 100.)
-                                                        ),
-                                                        DisplayStyle::Percentage => format!(
-                                                            "{:.2}%",
-                                                            totals.percentage(
-                                                                self.layout_stats[i],
-                                                                m.ngram_type
-                                                            )
-                                                        ),
-                                                    }
-                                                ))
+                                                button(text(self.format_stat(
+                                                    &m.short,
+                                                    totals.percentage(
+                                                        self.layout_stats[i],
+                                                        m.ngram_type
+                                                    )
+                                                )))
                                                 .on_press(Message::ToggleDisplayStyle(
                                                     context.metrics[i].short.clone()
                                                 ))
@@ -423,7 +636,10 @@ impl Application for Keymui {
                                     .collect::<Vec<_>>(),
                             ))
                         } else {
-                            scrollable(text("no metrics available!"))
+                            scrollable(text(
+                                self.missing_data_hint()
+                                    .unwrap_or_else(|| "no metrics available!".to_string()),
+                            ))
                         }
                     ]
                     .spacing(5)
@@ -466,6 +682,390 @@ impl Application for Keymui {
                             container(text("no nstrokes available")).into()
                         }
                     }
+                    PaneKind::History => {
+                        if let (Some(ctx), Some(tree)) = (&self.metric_context, self.current_tree())
+                        {
+                            let percentages = |id: usize| {
+                                tree.node(id)
+                                    .map(|n| Self::percentages(ctx, &n.stats))
+                                    .unwrap_or_default()
+                            };
+                            let entries = tree.path().into_iter().filter_map(|id| {
+                                let node = tree.node(id)?;
+                                let deltas = match node.parent {
+                                    Some(parent) => percentages(id)
+                                        .iter()
+                                        .zip(percentages(parent).iter())
+                                        .zip(ctx.metrics.iter())
+                                        .filter(|((p, q), _)| (*p - *q).abs() > f32::EPSILON)
+                                        .map(|((p, q), m)| format!("{} {:+.2}%", m.short, p - q))
+                                        .collect::<Vec<String>>()
+                                        .join("  "),
+                                    None => String::new(),
+                                };
+                                Some(Element::from(
+                                    button(column![
+                                        text(node.title()).font(self.monospaced_font()),
+                                        text(deltas).size(12),
+                                    ])
+                                    .on_press(Message::JumpToNode(id))
+                                    .style(if id == tree.current() {
+                                        theme::Button::Primary
+                                    } else {
+                                        theme::Button::Text
+                                    })
+                                    .padding(0),
+                                ))
+                            });
+                            column![
+                                text("History").size(18),
+                                scrollable(column(entries).spacing(4)),
+                            ]
+                            .spacing(5)
+                            .into()
+                        } else {
+                            container(text("no history available")).into()
+                        }
+                    }
+                    PaneKind::Tree => {
+                        if let (Some(ctx), Some(tree)) = (&self.metric_context, self.current_tree())
+                        {
+                            let nodes = tree.depth_first().into_iter().filter_map(|(id, depth)| {
+                                let node = tree.node(id)?;
+                                let style = if id == tree.current() {
+                                    theme::Button::Primary
+                                } else {
+                                    theme::Button::Text
+                                };
+                                let delete = node.parent.map(|_| {
+                                    button(text("×"))
+                                        .on_press(Message::DeleteNode(id))
+                                        .style(theme::Button::Text)
+                                        .padding(0)
+                                });
+                                Some(Element::from(
+                                    row![
+                                        text(format!("{:>3} {}", id, "  ".repeat(depth)))
+                                            .font(self.monospaced_font()),
+                                        button(text(node.title()).font(self.monospaced_font()))
+                                            .on_press(Message::JumpToNode(id))
+                                            .style(style)
+                                            .padding(0),
+                                        button(text("diff").size(12))
+                                            .on_press(Message::DiffNode(id))
+                                            .style(theme::Button::Text)
+                                            .padding(0),
+                                    ]
+                                    .push_maybe(delete)
+                                    .spacing(5),
+                                ))
+                            });
+
+                            let diff = self.tree_diff.and_then(|id| {
+                                let other = tree.node(id)?;
+                                let current = tree.current_node();
+                                let keys = current
+                                    .layout
+                                    .iter()
+                                    .zip(other.layout.iter())
+                                    .filter(|(a, b)| a != b)
+                                    .map(|(a, b)| format!("{}→{}", b, a))
+                                    .collect::<Vec<String>>()
+                                    .join(" ");
+                                let metrics = Self::percentages(ctx, &current.stats)
+                                    .iter()
+                                    .zip(Self::percentages(ctx, &other.stats).iter())
+                                    .zip(ctx.metrics.iter())
+                                    .filter(|((p, q), _)| (*p - *q).abs() > f32::EPSILON)
+                                    .map(|((p, q), m)| {
+                                        Element::from(
+                                            text(format!("{:<8} {:+.2}%", m.short, p - q))
+                                                .font(self.monospaced_font()),
+                                        )
+                                    });
+                                Some(column![
+                                    text(format!("current vs {}", other.title())).size(16),
+                                    text(if keys.is_empty() {
+                                        "no keys changed".to_string()
+                                    } else {
+                                        keys
+                                    })
+                                    .font(self.monospaced_font()),
+                                    column(metrics),
+                                ])
+                            });
+
+                            column![text("Tree").size(18), scrollable(column(nodes).spacing(2)),]
+                                .push_maybe(diff)
+                                .spacing(5)
+                                .into()
+                        } else {
+                            container(text("no tree available")).into()
+                        }
+                    }
+                    PaneKind::Corpus => {
+                        if let Some(stats) = self.corpus_stats() {
+                            let list = |title: &str, list: &NgramList| {
+                                Element::from(
+                                    column![text(title).size(16)]
+                                        .extend(list.top.iter().map(|(ngram, count)| {
+                                            Element::from(
+                                                text(format!(
+                                                    "{:<4}{:>6.2}% {}",
+                                                    ngram,
+                                                    list.percentage(*count),
+                                                    count
+                                                ))
+                                                .font(self.monospaced_font()),
+                                            )
+                                        }))
+                                        .width(Length::Fill),
+                                )
+                            };
+                            let search = self.search_corpus(&self.corpus_search).map(|result| {
+                                text(
+                                    result
+                                        .counts
+                                        .iter()
+                                        .map(|(kind, count, p)| {
+                                            format!(
+                                                "{} {}: {:.3}% ({})",
+                                                kind, result.ngram, p, count
+                                            )
+                                        })
+                                        .collect::<Vec<String>>()
+                                        .join("   "),
+                                )
+                                .font(self.monospaced_font())
+                            });
+                            let uncovered = self.uncovered_chars();
+                            let coverage = if uncovered.is_empty() {
+                                "every character has a key".to_string()
+                            } else {
+                                format!(
+                                    "no key ({:.2}% of characters): {}",
+                                    uncovered.iter().map(|(_, p)| p).sum::<f32>(),
+                                    uncovered
+                                        .iter()
+                                        .map(|(c, p)| {
+                                            format!("{} {:.2}%", show_ngram(iter::once(*c)), p)
+                                        })
+                                        .collect::<Vec<String>>()
+                                        .join(", ")
+                                )
+                            };
+                            column![
+                                text(format!("Corpus: {}", stats.name)).size(18),
+                                text(format!(
+                                    "{} characters, {} distinct",
+                                    stats.unigrams.total,
+                                    stats.chars.len()
+                                )),
+                                text(coverage),
+                                text_input("search n-grams", &self.corpus_search)
+                                    .on_input(Message::CorpusSearchChanged),
+                            ]
+                            .push_maybe(search)
+                            .push(scrollable(row![
+                                list("unigrams", &stats.unigrams),
+                                list("bigrams", &stats.bigrams),
+                                list("skipgrams", &stats.skipgrams),
+                                list("trigrams", &stats.trigrams),
+                            ]))
+                            .spacing(5)
+                            .into()
+                        } else {
+                            container(text("no corpus loaded")).into()
+                        }
+                    }
+                    PaneKind::Log => {
+                        let palette = self.theme().palette();
+                        let entries = self.log.entries().rev().map(|entry| {
+                            let level =
+                                text(format!("{:<7}", entry.level)).font(self.monospaced_font());
+                            let level = match entry.level.color(&palette) {
+                                Some(color) => level.style(color),
+                                None => level,
+                            };
+                            Element::from(
+                                row![
+                                    text(entry.timestamp()).font(self.monospaced_font()),
+                                    level,
+                                    text(&entry.message).width(Length::Fill),
+                                ]
+                                .push_maybe(entry.details.as_ref().map(|_| {
+                                    button(text("details").size(12))
+                                        .on_press(Message::ViewLogEntry(entry.id))
+                                        .style(theme::Button::Text)
+                                        .padding(0)
+                                }))
+                                .spacing(8),
+                            )
+                        });
+                        column![
+                            row![
+                                text("Log").size(18).width(Length::Fill),
+                                button(text("clear").size(12))
+                                    .on_press(Message::ClearLog)
+                                    .style(theme::Button::Text)
+                                    .padding(0),
+                            ],
+                            scrollable(column(entries).spacing(2)),
+                        ]
+                        .spacing(5)
+                        .into()
+                    }
+                    PaneKind::Browser => {
+                        let matches = self.browser_matches();
+                        let rows = matches.iter().take(MAX_SHOWN).filter_map(|name| {
+                            let layout = self.layouts.get(*name)?;
+                            let (thumbnail, stats): (Element<Message>, String) =
+                                match self.browser.analysis(name) {
+                                    Some(Analysis::Done {
+                                        percentages,
+                                        thumbnail,
+                                    }) => (
+                                        Canvas::new(thumbnail).width(160).height(60).into(),
+                                        self.quick_stats(percentages),
+                                    ),
+                                    Some(Analysis::Failed(e)) => {
+                                        (container("").width(160).into(), e.clone())
+                                    }
+                                    None => (
+                                        container("").width(160).into(),
+                                        "analyzing...".to_string(),
+                                    ),
+                                };
+                            Some(Element::from(
+                                button(
+                                    row![
+                                        thumbnail,
+                                        column![
+                                            text(&layout.name).size(16),
+                                            text(layout.authors.join(", ")).size(12),
+                                            text(&layout.board).size(12),
+                                            text(stats).size(12).font(self.monospaced_font()),
+                                        ]
+                                    ]
+                                    .spacing(8),
+                                )
+                                .on_press(Message::LayoutSelected(name.to_string()))
+                                .style(if self.current_layout.as_deref() == Some(*name) {
+                                    theme::Button::Primary
+                                } else {
+                                    theme::Button::Text
+                                })
+                                .padding(0),
+                            ))
+                        });
+                        column![
+                            row![
+                                text("Layouts").size(18).width(Length::Fill),
+                                text(format!("{} of {}", matches.len(), self.layouts.len())),
+                            ],
+                            row![
+                                text_input("search layouts", &self.browser.search)
+                                    .on_input(Message::BrowserSearchChanged),
+                                pick_list(
+                                    self.browser_keyboards(),
+                                    Some(self.browser.keyboard.clone()),
+                                    Message::BrowserKeyboardSelected
+                                ),
+                            ]
+                            .spacing(8),
+                            scrollable(column(rows).spacing(4)),
+                        ]
+                        .push_maybe((matches.len() > MAX_SHOWN).then(|| {
+                            text(format!(
+                                "{} more, refine the search to see them",
+                                matches.len() - MAX_SHOWN
+                            ))
+                            .size(12)
+                        }))
+                        .spacing(5)
+                        .into()
+                    }
+                    PaneKind::Comparison => {
+                        if let Some(ctx) = &self.metric_context {
+                            let columns = self.comparison_columns();
+                            let score = self.current_score();
+                            let highlight = self.theme().palette().success;
+                            let cell = |value: String, best: bool| {
+                                let t = text(value).font(self.monospaced_font());
+                                Element::from(
+                                    container(if best { t.style(highlight) } else { t })
+                                        .width(Length::FillPortion(1)),
+                                )
+                            };
+                            let sort_button = |label: String, key: SortKey| {
+                                Element::from(
+                                    container(
+                                        button(text(label))
+                                            .on_press(Message::SortComparison(key))
+                                            .style(theme::Button::Text)
+                                            .padding(0),
+                                    )
+                                    .width(Length::FillPortion(2)),
+                                )
+                            };
+                            let header = row(iter::once(Element::from(
+                                container(text("")).width(Length::FillPortion(2)),
+                            ))
+                            .chain(columns.iter().map(|c| {
+                                Element::from(
+                                    container(text(&c.name)).width(Length::FillPortion(1)),
+                                )
+                            })));
+
+                            let scores: Vec<Option<f32>> = columns
+                                .iter()
+                                .map(|c| self.comparison_score(&c.percentages))
+                                .collect();
+                            let best_score =
+                                Better::Higher.best(scores.iter().map(|s| s.unwrap_or(f32::MIN)));
+                            let score_row =
+                                score.as_ref().map(|_| {
+                                    Element::from(row(iter::once(sort_button(
+                                        "score".to_string(),
+                                        SortKey::Score,
+                                    ))
+                                    .chain(scores.iter().enumerate().map(|(j, s)| {
+                                        cell(
+                                            format!("{:.3}", s.unwrap_or_default()),
+                                            Some(j) == best_score,
+                                        )
+                                    }))))
+                                });
+                            let metric_rows =
+                                ctx.metrics.iter().enumerate().map(|(i, m)| {
+                                    let value = |c: &ComparedLayout| {
+                                        c.percentages.get(i).copied().unwrap_or_default()
+                                    };
+                                    // the score, or else the metric itself, tells whether more is better
+                                    let better = Better::for_metric(
+                                        &m.short,
+                                        score.as_ref().map(|s| s.weight(&m.short)).unwrap_or(0.0),
+                                    );
+                                    let best = better.best(columns.iter().map(value));
+                                    Element::from(row(iter::once(sort_button(
+                                        m.name.clone(),
+                                        SortKey::Metric(i),
+                                    ))
+                                    .chain(columns.iter().enumerate().map(|(j, c)| {
+                                        cell(self.format_stat(&m.short, value(c)), Some(j) == best)
+                                    }))))
+                                });
+                            column![
+                                text("Comparison").size(18),
+                                header,
+                                scrollable(column(score_row.into_iter().chain(metric_rows))),
+                            ]
+                            .spacing(5)
+                            .into()
+                        } else {
+                            container(text("no comparison available")).into()
+                        }
+                    }
                 }
             }))
         })
@@ -492,11 +1092,21 @@ impl Application for Keymui {
         .align_y(alignment::Vertical::Bottom);
 
         let input = column![cmd_col, cmd_input];
-        let notif: iced::widget::Row<_> = row![text(&self.notification.0)];
-        let notif = if self.notification.1.is_some() {
-            notif.push(button("info").on_press(Message::ViewNotification))
-        } else {
-            notif
+        let notif: iced::widget::Row<_> = match self.log.latest() {
+            Some(entry) => {
+                let message = text(&entry.message);
+                let message = match entry.level.color(&self.theme().palette()) {
+                    Some(color) => message.style(color),
+                    None => message,
+                };
+                row![message].push_maybe(
+                    entry
+                        .details
+                        .as_ref()
+                        .map(|_| button("info").on_press(Message::ViewNotification)),
+                )
+            }
+            None => row![],
         };
 
         let top_bar = row![
@@ -539,18 +1149,18 @@ impl Application for Keymui {
             .center_x()
             .into();
 
-        let notif_modal = if self.show_notif_modal {
-            Some(container(Card::new(
-                "Notification Details",
-                if let Some(s) = &self.notification.1 {
-                    s
-                } else {
-                    ""
-                },
-            )))
-        } else {
-            None
-        };
+        let notif_modal = self
+            .shown_entry
+            .and_then(|id| self.log.get(id))
+            .map(|entry| {
+                container(Card::new(
+                    text(format!("{} at {}", entry.level, entry.timestamp())),
+                    scrollable(
+                        text(entry.details.as_deref().unwrap_or(&entry.message))
+                            .font(self.monospaced_font()),
+                    ),
+                ))
+            });
 
         modal(view, notif_modal)
             .backdrop(Message::CloseNotifModal)
@@ -559,16 +1169,15 @@ impl Application for Keymui {
     }
 
     fn theme(&self) -> Theme {
-        match self.config.theme {
-            KeymuiTheme::Light => Theme::Light,
-            KeymuiTheme::Dark => Theme::Dark,
-            KeymuiTheme::TokyoNight => Theme::TokyoNight,
-            KeymuiTheme::CatppuccinMocha => Theme::CatppuccinMocha,
-        }
+        self.config.theme.iced_theme()
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        event::listen().map(|x| Message::RuntimeEvent(x))
+        Subscription::batch([
+            event::listen().map(|x| Message::RuntimeEvent(x)),
+            watch::subscription(self.watched_dirs()),
+            browser::subscription(self.browser_job()),
+        ])
     }
 
     fn update(&mut self, message: Message) -> Command<Message> {
@@ -579,42 +1188,190 @@ impl Application for Keymui {
                     .pick_folder();
                 if let Some(dir) = dir {
                     self.config.metrics_directory = Some(dir);
-                    self.notification = ("successfully set metric directory".to_string(), None);
-                    let _ = self.save_config();
+                    self.info("successfully set metric directory");
+                    if let Err(e) = self.save_config() {
+                        self.error("couldn't save the config", &e);
+                    }
                 };
                 return text_input::focus::<Message>(text_input::Id::new("cmd"));
             }
+            Message::SetLayoutDirectory(dir) => {
+                let dir = dir.or_else(|| {
+                    FileDialog::new()
+                        .set_directory(self.base_dirs.home_dir())
+                        .pick_folder()
+                });
+                if let Some(dir) = dir {
+                    if !self.config.layout_directories.contains(&dir) {
+                        self.config.layout_directories.push(dir.clone());
+                    }
+                    self.layout_directories_changed();
+                    self.info(format!("added layout directory {}", dir.display()));
+                    self.report_problems();
+                }
+                return text_input::focus::<Message>(text_input::Id::new("cmd"));
+            }
+            Message::RemoveLayoutDirectory(dir) => {
+                if self.config.layout_directories.contains(&dir) {
+                    self.config.layout_directories.retain(|d| *d != dir);
+                    self.problems.retain(|path, _| !path.starts_with(&dir));
+                    self.layout_directories_changed();
+                    self.info(format!("removed layout directory {}", dir.display()));
+                } else {
+                    self.warn(format!("{} isn't a layout directory", dir.display()));
+                }
+            }
+            Message::FetchData(source, overwrite) => {
+                self.progress("fetching data...");
+                return self.start_fetch(source, overwrite);
+            }
+            Message::DataFetched(result) => self.data_fetched(result),
+            Message::AddDataSource(source) => {
+                if self
+                    .config
+                    .data_sources
+                    .iter()
+                    .any(|s| s.name == source.name)
+                {
+                    self.warn(format!(
+                        "a data source named {} already exists",
+                        source.name
+                    ));
+                } else {
+                    self.info(format!(
+                        "added data source {}: {} from {}",
+                        source.name,
+                        source.target.folder(),
+                        source.location
+                    ));
+                    self.config.data_sources.push(source);
+                    if let Err(e) = self.save_config() {
+                        self.error("couldn't save the config", &e);
+                    }
+                }
+            }
+            Message::RemoveDataSource(name) => {
+                let before = self.config.data_sources.len();
+                self.config.data_sources.retain(|s| s.name != name);
+                if self.config.data_sources.len() == before {
+                    self.warn(format!("there's no data source named {}", name));
+                } else {
+                    self.info(format!("removed data source {}", name));
+                    if let Err(e) = self.save_config() {
+                        self.error("couldn't save the config", &e);
+                    }
+                }
+            }
             Message::Reload => {
                 let result = self.import_metrics();
                 match result {
-                    Ok(()) => self.notification = ("reloaded successfully".to_string(), None),
-                    Err(e) => self.notification = (e.to_string(), None),
+                    Ok(()) => self.info("reloaded successfully"),
+                    Err(e) => self.error("couldn't import metrics", &e),
+                }
+                if let Err(e) = self.set_metric_list() {
+                    self.error("couldn't list metrics", &e);
                 }
-                let _ = self.set_metric_list();
+                // the corpus file may have been rebuilt
+                self.corpus_stats.take();
                 if let Err(e) = self.load_data() {
-                    println!("{:?}", e);
+                    self.error("couldn't load the analysis data", &e);
                 }
                 if let Err(e) = self.load_layouts() {
-                    println!("{:?}", e);
+                    self.error("couldn't load layouts", &e);
                 }
+                self.refresh_comparison();
+                self.report_problems();
             }
-            Message::ImportNewCorpus => {
-                let file = FileDialog::new()
-                    .set_directory(self.base_dirs.home_dir())
-                    .pick_file();
-                if let Some(file) = file {
-                    match self.import_corpus(file) {
-                        Ok(_) => {
-                            self.notification = ("successfully imported corpus".to_string(), None)
-                        }
-                        Err(e) => {
-                            self.notification =
-                                ("error importing corpus".to_string(), Some(e.to_string()))
+            Message::PickCorpusFiles(mut import, folder) => {
+                let dialog = FileDialog::new().set_directory(self.base_dirs.home_dir());
+                let picked: Vec<PathBuf> = if folder {
+                    dialog.pick_folder().into_iter().collect()
+                } else {
+                    dialog.pick_files().unwrap_or_default()
+                };
+                let focus = text_input::focus::<Message>(text_input::Id::new("cmd"));
+                if picked.is_empty() {
+                    return focus;
+                }
+                import.paths = picked
+                    .iter()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .collect();
+                return Command::batch([self.update(Message::ImportCorpus(import)), focus]);
+            }
+            Message::ImportCorpus(import) => match self.start_corpus_import(import) {
+                Ok(command) => {
+                    self.progress("importing corpus...");
+                    return command;
+                }
+                Err(e) => self.error("error importing corpus", &e),
+            },
+            Message::FilesChanged(changed) => {
+                self.files_changed(changed);
+            }
+            Message::WatchFailed(e) => {
+                self.log_entry(Level::Error, "couldn't watch for file changes", Some(e));
+            }
+            Message::CorpusSearchChanged(s) => {
+                self.corpus_search = s;
+            }
+            Message::BrowserSearchChanged(s) => {
+                self.browser.search = s;
+            }
+            Message::BrowserKeyboardSelected(keyboard) => {
+                self.browser.keyboard = keyboard;
+            }
+            Message::LayoutSummarized(generation, name, summary) => {
+                self.browser.summarized(generation, name, summary);
+            }
+            Message::BrowserFailed(generation, e) => {
+                if self.browser.is_current(generation) {
+                    self.log_entry(Level::Error, "couldn't analyze the layouts", Some(e));
+                }
+            }
+            Message::BlendCorpora(name, weights) => match self.blend_corpora(&name, &weights) {
+                Ok(()) => {
+                    self.info(format!("blended corpus {}", name));
+                    if self.current_corpus.as_ref() == Some(&name) {
+                        self.corpus_stats.take();
+                        if let Err(e) = self.load_data() {
+                            self.error("couldn't load the analysis data", &e);
                         }
                     }
                 }
-                let _ = self.set_corpus_list();
-                return text_input::focus::<Message>(text_input::Id::new("cmd"));
+                Err(e) => self.error("error blending corpora", &e),
+            },
+            Message::RebuildCorpus(name) => match self.rebuild_corpus(&name) {
+                Ok(command) => {
+                    self.progress(format!("rebuilding corpus {}...", name));
+                    return command;
+                }
+                Err(e) => self.error("error rebuilding corpus", &e),
+            },
+            Message::CorpusImportProgress(ImportProgress::Reading { name, done, total }) => {
+                self.progress(format!("importing {}: {}/{} files", name, done, total));
+            }
+            Message::CorpusImportProgress(ImportProgress::Finished(result)) => {
+                if let Err(e) = self.set_corpus_list() {
+                    self.error("couldn't list corpora", &e);
+                }
+                match result {
+                    Ok(summary) if summary.skipped.is_empty() => self.info(format!(
+                        "imported corpus {} from {} files",
+                        summary.name, summary.files
+                    )),
+                    Ok(summary) => self.log_entry(
+                        Level::Warning,
+                        format!(
+                            "imported corpus {} from {} files, skipped {}",
+                            summary.name,
+                            summary.files - summary.skipped.len(),
+                            summary.skipped.len()
+                        ),
+                        Some(summary.skipped.join("\n")),
+                    ),
+                    Err(e) => self.log_entry(Level::Error, "error importing corpus", Some(e)),
+                }
             }
             Message::CommandInputChanged(s) => {
                 let ns = self.input_completions.len();
@@ -654,27 +1411,64 @@ impl Application for Keymui {
                 self.command_input = s;
                 self.filter_commands();
             }
-            Message::CommandSubmitted => {
-                self.parse_command().unwrap();
-            }
+            Message::CommandSubmitted => match self.parse_command() {
+                Ok(command) => return command,
+                Err(e) => self.error("command failed", &e),
+            },
             Message::ViewNotification => {
-                self.show_notif_modal = true;
+                self.shown_entry = self.log.latest().map(|e| e.id);
+            }
+            Message::ViewLogEntry(id) => {
+                self.shown_entry = Some(id);
+            }
+            Message::ClearLog => {
+                self.log.clear();
             }
             Message::CloseNotifModal => {
-                self.show_notif_modal = false;
+                self.shown_entry = None;
                 return text_input::focus::<Message>(text_input::Id::new("cmd"));
             }
             Message::LayoutSelected(s) => {
                 self.current_layout = Some(s);
-                let _ = self.load_data();
+                if let Err(e) = self.load_data() {
+                    self.error("couldn't load the analysis data", &e);
+                }
             }
             Message::ContextSelected(s) => {
                 self.current_metrics = Some(s);
-                let _ = self.load_data();
+                if let Err(e) = self.load_data() {
+                    self.error("couldn't load the analysis data", &e);
+                }
+                self.refresh_comparison();
             }
             Message::CorpusSelected(s) => {
                 self.current_corpus = Some(s);
-                let _ = self.load_data();
+                if let Err(e) = self.load_data() {
+                    self.error("couldn't load the analysis data", &e);
+                }
+                self.refresh_comparison();
+            }
+            Message::CompareLayout(name) => match self
+                .read_metrics()
+                .and_then(|m| self.compare_layout(&name, &m, &self.read_corpus()?))
+            {
+                Ok(compared) => {
+                    self.comparison.retain(|c| c.name != name);
+                    self.comparison.push(compared);
+                }
+                Err(e) => self.error("couldn't compare layout", &e),
+            },
+            Message::UncompareLayout(name) => {
+                self.comparison.retain(|c| c.name != name);
+            }
+            Message::ClearComparison => {
+                self.comparison.clear();
+            }
+            Message::SortComparison(key) => {
+                self.comparison_sort = match self.comparison_sort {
+                    Some((current, descending)) if current == key => Some((key, !descending)),
+                    _ => Some((key, true)),
+                };
             }
             Message::DisplayStyleSet(style) => {
                 if let Some(display) = &mut self.layout_display {
@@ -685,11 +1479,106 @@ impl Application for Keymui {
                     display.redraw();
                 }
             }
+            Message::OpenEditor(start) => match self.current_metrics.clone() {
+                Some(metrics) => match self.open_editor(start, metrics) {
+                    Ok(()) => self.info("opened the layout editor"),
+                    Err(e) => self.error("couldn't open the layout editor", &e),
+                },
+                None => self.warn("no metrics selected"),
+            },
+            Message::ImportLayout(path) => {
+                let path = path.or_else(|| {
+                    FileDialog::new()
+                        .set_directory(self.base_dirs.home_dir())
+                        .pick_file()
+                });
+                if let Some(path) = path {
+                    if let Err(e) = self.import_layout(&path) {
+                        self.error("couldn't import the layout", &e);
+                    }
+                }
+                return text_input::focus::<Message>(text_input::Id::new("cmd"));
+            }
+            Message::EditorKeyboardSelected(metrics) => {
+                // keep what was typed and imported so far
+                let old = self.editor.take();
+                let result = match old.as_ref().and_then(|ed| ed.import.clone()) {
+                    Some(import) => self.start_import(import.layout, metrics),
+                    None => self.open_editor(EditorStart::Blank, metrics),
+                };
+                match result {
+                    Ok(()) => {
+                        if let (Some(old), Some(editor)) = (old, &mut self.editor) {
+                            editor.name = old.name;
+                            editor.authors = old.authors;
+                        }
+                    }
+                    Err(e) => {
+                        self.editor = old;
+                        self.error("couldn't open the layout editor", &e);
+                    }
+                }
+            }
+            Message::EditorMappingSelected(mapping) => {
+                if let Some(import) = self.editor.as_mut().and_then(|ed| ed.import.as_mut()) {
+                    import.mapping = mapping;
+                }
+                self.apply_import();
+            }
+            Message::EditorPlace(position, c) => {
+                if let Some(Err(e)) = self.editor.as_mut().map(|ed| ed.place(position, c)) {
+                    self.warn(e.to_string());
+                }
+            }
+            Message::EditorSwap(a, b) => {
+                if let Some(editor) = &mut self.editor {
+                    editor.swap(a, b);
+                }
+            }
+            Message::EditorComboChanged(combo, input) => {
+                if let Some(Err(e)) = self.editor.as_mut().map(|ed| ed.set_combo(combo, &input)) {
+                    self.warn(e.to_string());
+                }
+            }
+            Message::EditorNameChanged(name) => {
+                if let Some(editor) = &mut self.editor {
+                    editor.name = name;
+                }
+            }
+            Message::EditorAuthorsChanged(authors) => {
+                if let Some(editor) = &mut self.editor {
+                    editor.authors = authors;
+                }
+            }
+            Message::SaveEditor => {
+                if let Err(e) = self.save_editor() {
+                    self.error("couldn't save the layout", &e);
+                }
+            }
+            Message::CloseEditor => {
+                self.editor = None;
+            }
+            Message::SetAuthors(authors) => {
+                self.info(format!("layouts will be saved by {}", authors.join(", ")));
+                self.config.authors = authors;
+                if let Err(e) = self.save_config() {
+                    self.error("couldn't save the config", &e);
+                }
+            }
             Message::Resized(pane_grid::ResizeEvent { split, ratio }) => {
                 self.panes.resize(split, ratio);
             }
+            Message::SwapKeys(..)
+            | Message::Undo
+            | Message::Redo
+            | Message::JumpToNode(_)
+            | Message::DeleteNode(_)
+                if self.optimizer.is_some() =>
+            {
+                self.warn("can't edit the layout while the optimizer is running");
+            }
             Message::SwapKeys(a, b) => {
-                if let Some(ctx) = &mut self.metric_context {
+                if let Some(ctx) = &self.metric_context {
                     let a = ctx
                         .layout
                         .0
@@ -701,28 +1590,95 @@ impl Application for Keymui {
                         .iter()
                         .position(|c| *c == ctx.analyzer.corpus.corpus_char(b));
                     if let (Some(a), Some(b)) = (a, b) {
-                        let swap = Swap::new(a, b);
-                        let mut diffs = vec![0.0; ctx.analyzer.data.metrics.len()];
-                        ctx.analyzer.swap_diff(&mut diffs, &ctx.layout, &swap);
-                        ctx.layout.swap(&swap);
-                        self.layout_stats
-                            .iter_mut()
-                            .zip(diffs.iter())
-                            .for_each(|(v, diff)| *v += diff);
-                        println!("swapped!");
-                        let display = self
-                            .layout_display
-                            .as_mut()
-                            .expect("analyzer exists, therefore layout display should");
-
-                        display.update_keys(ctx, self.nstrokes_metric);
-                        display.redraw();
-
-                        self.set_nstroke_list();
-                        self.sort_nstroke_list();
+                        self.record_swap(a, b);
+                        self.refresh_layout_views();
                     };
                 }
             }
+            Message::Undo => {
+                if !self.undo() {
+                    self.warn("nothing to undo");
+                }
+            }
+            Message::Redo => {
+                if !self.redo() {
+                    self.warn("nothing to redo");
+                }
+            }
+            Message::JumpToNode(id) => {
+                self.jump_to_node(id);
+            }
+            Message::DeleteNode(id) => {
+                self.delete_node(id);
+            }
+            Message::RenameNode(name) => {
+                self.rename_node(name);
+            }
+            Message::DiffNode(id) => {
+                self.tree_diff = match self.tree_diff {
+                    Some(current) if current == id => None,
+                    _ => Some(id),
+                };
+            }
+            Message::StartOptimizer(iterations) => match self.start_optimizer(iterations) {
+                Ok(command) => return command,
+                Err(e) => self.error("couldn't start the optimizer", &e),
+            },
+            Message::StopOptimizer => {
+                if let Some(optimizer) = &self.optimizer {
+                    optimizer.cancel();
+                    self.progress("stopping optimizer...");
+                } else {
+                    self.warn("the optimizer isn't running");
+                }
+            }
+            Message::OptimizerProgress(Progress::Improved {
+                layout,
+                stats,
+                score,
+                iteration,
+            }) => {
+                if self.optimizer.is_some() {
+                    if let Some(ctx) = &mut self.metric_context {
+                        ctx.layout.0 = layout;
+                    }
+                    self.layout_stats = stats;
+                    self.progress(format!(
+                        "optimizing: score {:.3} at iteration {}",
+                        score, iteration
+                    ));
+                    self.refresh_layout_views();
+                }
+            }
+            Message::OptimizerProgress(Progress::Finished { layout, score, .. }) => {
+                if let Some(optimizer) = self.optimizer.take() {
+                    // return to the starting point so the result can be
+                    // recorded in the tree as ordinary swaps
+                    if let Some(ctx) = &mut self.metric_context {
+                        ctx.layout.0 = optimizer.original_layout;
+                    }
+                    self.layout_stats = optimizer.original_stats;
+                    self.apply_layout_as_swaps("optimized", &layout);
+                    self.info(format!("optimization finished with score {:.3}", score));
+                }
+            }
+            Message::SetWeight(metric, weight) => {
+                let mut score = self.current_score().unwrap_or_default();
+                score.set_weight(&metric, weight);
+                self.set_score(score);
+            }
+            Message::SetScore(s) => match s.parse::<Score>() {
+                Ok(score) => self.set_score(score),
+                Err(e) => self.error("invalid score", &e),
+            },
+            Message::PinKey(c) => {
+                self.pinned.insert(c);
+                self.info(format!("pinned {}", c));
+            }
+            Message::UnpinKey(c) => {
+                self.pinned.remove(&c);
+                self.info(format!("unpinned {}", c));
+            }
             Message::SetPrecision(n) => {
                 self.config.stat_precision = n;
             }
@@ -764,7 +1720,9 @@ impl Application for Keymui {
             #[allow(clippy::single_match)]
             Message::RuntimeEvent(e) => match e {
                 Event::Window(id, window::Event::CloseRequested) => {
-                    let _ = self.save_config();
+                    if let Err(e) = self.save_config() {
+                        eprintln!("couldn't save the config: {:?}", e);
+                    }
                     return window::close(id);
                 }
                 _ => (),
@@ -794,18 +1752,66 @@ impl Application for Keymui {
 #[derive(Clone, Debug)]
 pub enum Message {
     SetMetricsDirectory,
+    SetLayoutDirectory(Option<PathBuf>),
+    RemoveLayoutDirectory(PathBuf),
     Reload,
-    ImportNewCorpus,
+    FetchData(FetchSource, bool),
+    DataFetched(Result<FetchSummary, String>),
+    AddDataSource(DataSource),
+    RemoveDataSource(String),
+    PickCorpusFiles(CorpusImport, bool),
+    ImportCorpus(CorpusImport),
+    CorpusImportProgress(ImportProgress),
+    RebuildCorpus(String),
+    BlendCorpora(String, Vec<(String, f64)>),
+    CorpusSearchChanged(String),
+    BrowserSearchChanged(String),
+    BrowserKeyboardSelected(KeyboardFilter),
+    LayoutSummarized(usize, String, Result<Summary, String>),
+    BrowserFailed(usize, String),
+    FilesChanged(Vec<(Watched, PathBuf)>),
+    WatchFailed(String),
     CommandInputChanged(String),
     CommandSubmitted,
     ViewNotification,
+    ViewLogEntry(usize),
+    ClearLog,
     CloseNotifModal,
     LayoutSelected(String),
     ContextSelected(String),
     CorpusSelected(String),
     DisplayStyleSet(ColorStyle),
+    OpenEditor(EditorStart),
+    ImportLayout(Option<PathBuf>),
+    EditorMappingSelected(Mapping),
+    EditorKeyboardSelected(String),
+    EditorPlace(usize, char),
+    EditorSwap(usize, usize),
+    EditorComboChanged(usize, String),
+    EditorNameChanged(String),
+    EditorAuthorsChanged(String),
+    SaveEditor,
+    CloseEditor,
+    SetAuthors(Vec<String>),
     Resized(pane_grid::ResizeEvent),
     SwapKeys(char, char),
+    Undo,
+    Redo,
+    JumpToNode(usize),
+    DeleteNode(usize),
+    RenameNode(String),
+    DiffNode(usize),
+    StartOptimizer(usize),
+    StopOptimizer,
+    OptimizerProgress(Progress),
+    SetWeight(String, f32),
+    SetScore(String),
+    CompareLayout(String),
+    UncompareLayout(String),
+    ClearComparison,
+    SortComparison(SortKey),
+    PinKey(char),
+    UnpinKey(char),
     SetPrecision(u32),
     SetTheme(KeymuiTheme),
     ToggleDisplayStyle(String),
@@ -819,6 +1825,12 @@ pub enum PaneKind {
     Layout,
     Metrics,
     Nstrokes,
+    History,
+    Tree,
+    Comparison,
+    Corpus,
+    Log,
+    Browser,
 }
 
 #[derive(Copy, Clone)]
@@ -0,0 +1,205 @@
+use iced::futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use kc::Swap;
+use km::MetricContext;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the best layout found so far is sent back to the UI.
+const REPORT_INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct Job {
+    pub context: MetricContext,
+    pub stats: Vec<f32>,
+    /// Weight of each metric's raw stat in the objective. Higher scores
+    /// are better.
+    pub weights: Vec<f32>,
+    /// Layout positions the optimizer is allowed to move.
+    pub movable: Vec<usize>,
+    pub iterations: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum Progress {
+    Improved {
+        layout: Vec<usize>,
+        stats: Vec<f32>,
+        score: f32,
+        iteration: usize,
+    },
+    Finished {
+        layout: Vec<usize>,
+        stats: Vec<f32>,
+        score: f32,
+    },
+}
+
+/// A running optimization. Dropping the handle does not stop the search;
+/// call [`Optimizer::cancel`] for that.
+pub struct Optimizer {
+    cancel: Arc<AtomicBool>,
+    pub original_layout: Vec<usize>,
+    pub original_stats: Vec<f32>,
+}
+
+impl Optimizer {
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+pub fn score(stats: &[f32], weights: &[f32]) -> f32 {
+    stats.iter().zip(weights.iter()).map(|(s, w)| s * w).sum()
+}
+
+/// Starts simulated annealing over swaps in a background thread, returning
+/// the handle and a stream of progress updates.
+pub fn start(job: Job) -> (Optimizer, UnboundedReceiver<Progress>) {
+    let (sender, receiver) = mpsc::unbounded();
+    let cancel = Arc::new(AtomicBool::new(false));
+    let optimizer = Optimizer {
+        cancel: cancel.clone(),
+        original_layout: job.context.layout.0.clone(),
+        original_stats: job.stats.clone(),
+    };
+    thread::spawn(move || {
+        let finished = anneal(job, &cancel, Some(&sender));
+        let _ = sender.unbounded_send(finished);
+    });
+    (optimizer, receiver)
+}
+
+/// Runs the whole search on the current thread and returns the handle
+/// together with the [`Progress::Finished`] result.
+pub fn run(job: Job) -> (Optimizer, Progress) {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let optimizer = Optimizer {
+        cancel: cancel.clone(),
+        original_layout: job.context.layout.0.clone(),
+        original_stats: job.stats.clone(),
+    };
+    let finished = anneal(job, &cancel, None);
+    (optimizer, finished)
+}
+
+/// Two different indexes below `n`, which must be at least 2. The second
+/// is drawn from the other `n - 1` so no swap is wasted.
+fn random_pair(rng: &mut fastrand::Rng, n: usize) -> (usize, usize) {
+    let a = rng.usize(..n);
+    let b = rng.usize(..n - 1);
+    (a, if b >= a { b + 1 } else { b })
+}
+
+/// Anneals until done or cancelled, sending improvements to `sender` if
+/// there is one.
+fn anneal(
+    mut job: Job,
+    cancel: &AtomicBool,
+    sender: Option<&UnboundedSender<Progress>>,
+) -> Progress {
+    let ctx = &mut job.context;
+    let mut rng = fastrand::Rng::new();
+    let mut diffs = vec![0.0; job.stats.len()];
+    let n = job.movable.len();
+
+    let mut current = score(&job.stats, &job.weights);
+    let mut best = current;
+    let mut best_layout = ctx.layout.0.clone();
+    let mut best_stats = job.stats.clone();
+
+    let random_swap = |rng: &mut fastrand::Rng| {
+        let (a, b) = random_pair(rng, n);
+        Swap::new(job.movable[a], job.movable[b])
+    };
+
+    // the starting temperature is the average score change of a random swap
+    let temperature = if n < 2 {
+        0.0
+    } else {
+        let samples = 200;
+        (0..samples)
+            .map(|_| {
+                let swap = random_swap(&mut rng);
+                diffs.iter_mut().for_each(|d| *d = 0.0);
+                ctx.analyzer.swap_diff(&mut diffs, &ctx.layout, &swap);
+                score(&diffs, &job.weights).abs()
+            })
+            .sum::<f32>()
+            / samples as f32
+    };
+
+    let mut last_report = Instant::now();
+    for i in 0..job.iterations {
+        if n < 2 || (i % 1024 == 0 && cancel.load(Ordering::Relaxed)) {
+            break;
+        }
+        let t = temperature * (1.0 - i as f32 / job.iterations as f32);
+        let swap = random_swap(&mut rng);
+        diffs.iter_mut().for_each(|d| *d = 0.0);
+        ctx.analyzer.swap_diff(&mut diffs, &ctx.layout, &swap);
+        let delta = score(&diffs, &job.weights);
+        if delta <= 0.0 && (t <= 0.0 || rng.f32() >= (delta / t).exp()) {
+            continue;
+        }
+
+        ctx.layout.swap(&swap);
+        job.stats
+            .iter_mut()
+            .zip(diffs.iter())
+            .for_each(|(v, diff)| *v += diff);
+        current += delta;
+
+        if current > best {
+            best = current;
+            best_layout.clone_from(&ctx.layout.0);
+            best_stats.clone_from(&job.stats);
+            if let Some(sender) = sender.filter(|_| last_report.elapsed() >= REPORT_INTERVAL) {
+                last_report = Instant::now();
+                let progress = Progress::Improved {
+                    layout: best_layout.clone(),
+                    stats: best_stats.clone(),
+                    score: best,
+                    iteration: i,
+                };
+                if sender.unbounded_send(progress).is_err() {
+                    // nobody is listening anymore
+                    break;
+                }
+            }
+        }
+    }
+
+    Progress::Finished {
+        layout: best_layout,
+        stats: best_stats,
+        score: best,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairs_are_distinct_and_cover_every_index() {
+        let mut rng = fastrand::Rng::with_seed(7);
+        let mut seen = [false; 4];
+        for _ in 0..1000 {
+            let (a, b) = random_pair(&mut rng, 4);
+            assert_ne!(a, b);
+            assert!(a < 4 && b < 4);
+            seen[a] = true;
+            seen[b] = true;
+        }
+        assert!(seen.iter().all(|s| *s));
+        let (a, b) = random_pair(&mut rng, 2);
+        assert_eq!(a + b, 1);
+    }
+
+    #[test]
+    fn scores_weigh_stats() {
+        assert_eq!(score(&[1.0, 2.0], &[-1.0, 0.5]), 0.0);
+        assert_eq!(score(&[3.0], &[2.0]), 6.0);
+    }
+}
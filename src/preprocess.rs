@@ -0,0 +1,358 @@
+use color_eyre::eyre::{anyhow, Context, ContextCompat, Result};
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use unicode_normalization::UnicodeNormalization;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Lowercase {
+    #[default]
+    Keep,
+    All,
+    /// Lowercases capitalized words but keeps acronyms like `NASA`.
+    Initial,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Normalization {
+    Nfc,
+    Nfd,
+    Nfkc,
+    Nfkd,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Markup {
+    Markdown,
+    Html,
+    Latex,
+}
+
+/// Parses an option value by its serialized name, like `nfkc`.
+fn parse_name<T: DeserializeOwned>(value: &str) -> Result<T> {
+    Ok(serde_json::from_value(serde_json::Value::String(
+        value.to_string(),
+    ))?)
+}
+
+/// Replacements for typographic characters that are usually typed as
+/// their ASCII counterparts.
+const SMART_QUOTES: &[(&str, &str)] = &[
+    ("‘", "'"),
+    ("’", "'"),
+    ("‚", "'"),
+    ("‛", "'"),
+    ("“", "\""),
+    ("”", "\""),
+    ("„", "\""),
+    ("‟", "\""),
+    ("–", "-"),
+    ("—", "-"),
+    ("…", "..."),
+    ("\u{a0}", " "),
+];
+
+/// Text transformations applied to every file of a corpus before n-grams
+/// are counted. The steps run in the order of the fields: normalization,
+/// character mapping, markup stripping, line removal, lowercasing and
+/// whitespace collapsing.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Pipeline {
+    pub normalize: Option<Normalization>,
+    /// Pairs of `(from, to)` strings.
+    pub map: Vec<(String, String)>,
+    pub strip: Vec<Markup>,
+    /// Regexes; lines matching any of them are removed.
+    pub remove_lines: Vec<String>,
+    pub lowercase: Lowercase,
+    pub collapse_whitespace: bool,
+}
+
+impl Pipeline {
+    /// Option names accepted by [`Pipeline::set`].
+    pub const KEYS: [&'static str; 6] = [
+        "normalize",
+        "map",
+        "strip",
+        "remove-lines",
+        "lowercase",
+        "whitespace",
+    ];
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let s = fs::read_to_string(path)
+            .with_context(|| format!("couldn't read pipeline {}", &path.display()))?;
+        serde_json::from_str(&s)
+            .with_context(|| format!("couldn't parse pipeline {}", &path.display()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Sets an option from its palette form, like `strip=markdown,html` or
+    /// `map=smart-quotes`. `map` and `remove-lines` add to what's there.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "normalize" => {
+                self.normalize = Some(parse_name(value).context("unknown normalization")?)
+            }
+            "map" => match value {
+                "smart-quotes" => self.map.extend(
+                    SMART_QUOTES
+                        .iter()
+                        .map(|(a, b)| (a.to_string(), b.to_string())),
+                ),
+                _ => {
+                    let (from, to) = value
+                        .split_once(':')
+                        .context("map takes smart-quotes or <from>:<to>")?;
+                    self.map.push((from.to_string(), to.to_string()));
+                }
+            },
+            "strip" => {
+                for markup in value.split(',') {
+                    self.strip
+                        .push(parse_name(markup).context("unknown markup")?);
+                }
+            }
+            "remove-lines" => self.remove_lines.push(value.to_string()),
+            "lowercase" => {
+                self.lowercase = parse_name(value).context("unknown lowercase policy")?
+            }
+            "whitespace" => match value {
+                "collapse" => self.collapse_whitespace = true,
+                "keep" => self.collapse_whitespace = false,
+                _ => return Err(anyhow!("whitespace takes collapse or keep")),
+            },
+            _ => return Err(anyhow!("unknown preprocessing option {}", key)),
+        }
+        Ok(())
+    }
+
+    /// Compiles the regexes of the pipeline.
+    pub fn compile(&self) -> Result<Preprocessor> {
+        let remove_lines = self
+            .remove_lines
+            .iter()
+            .map(|r| Regex::new(r).with_context(|| format!("invalid regex {}", r)))
+            .collect::<Result<_>>()?;
+        Ok(Preprocessor {
+            pipeline: self.clone(),
+            remove_lines,
+            markup: self
+                .strip
+                .iter()
+                .flat_map(|m| markup_rules(*m))
+                .map(|(r, replacement)| {
+                    (
+                        Regex::new(r).expect("markup regexes are valid"),
+                        replacement,
+                    )
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Regexes that strip a kind of markup, with what to replace matches with.
+fn markup_rules(markup: Markup) -> Vec<(&'static str, &'static str)> {
+    match markup {
+        Markup::Markdown => vec![
+            (r"(?m)^[ \t]*(```|~~~).*$", ""),
+            (r"!\[([^\]]*)\]\([^)]*\)", "$1"),
+            (r"\[([^\]]*)\]\([^)]*\)", "$1"),
+            (r"(?m)^[ \t]{0,3}#{1,6}[ \t]+", ""),
+            (r"(?m)^[ \t]{0,3}>[ \t]?", ""),
+            (r"(?m)^[ \t]*([-*+]|\d+\.)[ \t]+", ""),
+            (r"(?m)^[ \t]*([-*_][ \t]*){3,}$", ""),
+            (r"(\*\*|__)(\S(?:.*?\S)?)(\*\*|__)", "$2"),
+            (r"`([^`]*)`", "$1"),
+        ],
+        Markup::Html => vec![
+            (r"(?is)<(script|style)[^>]*>.*?</(script|style)>", ""),
+            (r"(?s)<!--.*?-->", ""),
+            (r"<[^>]+>", ""),
+            (r"&nbsp;", " "),
+            (r"&lt;", "<"),
+            (r"&gt;", ">"),
+            (r"&quot;", "\""),
+            (r"&#39;", "'"),
+            (r"&amp;", "&"),
+        ],
+        Markup::Latex => vec![
+            (r"(?m)(^|[^\\])%.*$", "$1"),
+            (r"\$\$[^$]*\$\$|\$[^$]*\$", ""),
+            (r"\\(begin|end)\{[^}]*\}", ""),
+            (
+                r"\\(textbf|textit|emph|underline|text|title|chapter|section|subsection|subsubsection|paragraph)\*?\{([^}]*)\}",
+                "$2",
+            ),
+            (r"\\[a-zA-Z]+\*?(\[[^\]]*\])?(\{[^}]*\})?", ""),
+            (r"[{}]", ""),
+        ],
+    }
+}
+
+pub struct Preprocessor {
+    pipeline: Pipeline,
+    remove_lines: Vec<Regex>,
+    markup: Vec<(Regex, &'static str)>,
+}
+
+impl Preprocessor {
+    pub fn apply(&self, text: &str) -> String {
+        let p = &self.pipeline;
+        let mut text = match p.normalize {
+            Some(Normalization::Nfc) => text.nfc().collect(),
+            Some(Normalization::Nfd) => text.nfd().collect(),
+            Some(Normalization::Nfkc) => text.nfkc().collect(),
+            Some(Normalization::Nfkd) => text.nfkd().collect(),
+            None => text.to_string(),
+        };
+        for (from, to) in &p.map {
+            text = text.replace(from.as_str(), to);
+        }
+        for (regex, replacement) in &self.markup {
+            text = regex.replace_all(&text, *replacement).into_owned();
+        }
+        if !self.remove_lines.is_empty() {
+            text = text
+                .lines()
+                .filter(|l| !self.remove_lines.iter().any(|r| r.is_match(l)))
+                .collect::<Vec<&str>>()
+                .join("\n");
+        }
+        text = match p.lowercase {
+            Lowercase::Keep => text,
+            Lowercase::All => text.to_lowercase(),
+            Lowercase::Initial => text
+                .split_inclusive(|c: char| !c.is_alphanumeric())
+                .map(|word| {
+                    let mut chars = word.chars();
+                    let capitalized = chars.next().is_some_and(char::is_uppercase)
+                        && !chars.any(char::is_uppercase);
+                    if capitalized {
+                        word.to_lowercase()
+                    } else {
+                        word.to_string()
+                    }
+                })
+                .collect(),
+        };
+        if p.collapse_whitespace {
+            text = text
+                .lines()
+                .map(|l| l.split_whitespace().collect::<Vec<&str>>().join(" "))
+                .filter(|l| !l.is_empty())
+                .collect::<Vec<String>>()
+                .join("\n");
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pipeline(options: &[(&str, &str)]) -> Pipeline {
+        let mut pipeline = Pipeline::default();
+        for (key, value) in options {
+            pipeline.set(key, value).unwrap();
+        }
+        pipeline
+    }
+
+    #[test]
+    fn options_are_set_by_name() {
+        let p = pipeline(&[
+            ("normalize", "nfkc"),
+            ("map", "ß:ss"),
+            ("map", "a:b:c"),
+            ("strip", "markdown,html"),
+            ("remove-lines", "^#"),
+            ("lowercase", "initial"),
+            ("whitespace", "collapse"),
+        ]);
+        assert_eq!(p.normalize, Some(Normalization::Nfkc));
+        assert_eq!(
+            p.map,
+            [
+                ("ß".to_string(), "ss".to_string()),
+                ("a".to_string(), "b:c".to_string())
+            ]
+        );
+        assert_eq!(p.strip, [Markup::Markdown, Markup::Html]);
+        assert_eq!(p.remove_lines, ["^#"]);
+        assert_eq!(p.lowercase, Lowercase::Initial);
+        assert!(p.collapse_whitespace);
+        assert!(!p.is_empty());
+
+        let p = pipeline(&[("whitespace", "collapse"), ("whitespace", "keep")]);
+        assert!(p.is_empty());
+        assert_eq!(
+            pipeline(&[("map", "smart-quotes")]).map.len(),
+            SMART_QUOTES.len()
+        );
+    }
+
+    #[test]
+    fn invalid_options_are_rejected() {
+        let mut p = Pipeline::default();
+        assert!(p.set("normalize", "nfx").is_err());
+        assert!(p.set("map", "no-colon").is_err());
+        assert!(p.set("strip", "markdown,rtf").is_err());
+        assert!(p.set("lowercase", "some").is_err());
+        assert!(p.set("whitespace", "squash").is_err());
+        assert!(p.set("color", "red").is_err());
+        assert!(Pipeline {
+            remove_lines: vec!["(".to_string()],
+            ..Pipeline::default()
+        }
+        .compile()
+        .is_err());
+    }
+
+    #[test]
+    fn steps_run_in_order() {
+        let p = pipeline(&[
+            ("map", "smart-quotes"),
+            ("strip", "markdown"),
+            ("remove-lines", "^skip"),
+            ("lowercase", "initial"),
+            ("whitespace", "collapse"),
+        ]);
+        let text = "# The  “NASA” [Page](https://x.y)\nskip this\n\n  **Bold**   `code`  \n";
+        assert_eq!(
+            p.compile().unwrap().apply(text),
+            "the \"NASA\" page\nbold code"
+        );
+    }
+
+    #[test]
+    fn html_and_latex_are_stripped() {
+        let html = pipeline(&[("strip", "html")]).compile().unwrap();
+        assert_eq!(
+            html.apply("<p>a &lt;b&gt;<script>x()</script><!-- c --></p>"),
+            "a <b>"
+        );
+        let latex = pipeline(&[("strip", "latex")]).compile().unwrap();
+        assert_eq!(
+            latex.apply("\\section{Intro} Some \\emph{text} $x^2$ % note"),
+            "Intro Some text  "
+        );
+    }
+
+    #[test]
+    fn normalization() {
+        let p = pipeline(&[("normalize", "nfkc")]).compile().unwrap();
+        assert_eq!(p.apply("ﬁ e\u{301}"), "fi é");
+    }
+}
@@ -0,0 +1,271 @@
+use crate::layout_display::{ColorStyle, LayoutDisplay};
+use crate::{Keymui, KeymuiTheme};
+use color_eyre::eyre::{anyhow, Context, ContextCompat, Result};
+use resvg::{tiny_skia, usvg};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Svg,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "svg" => Some(ImageFormat::Svg),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Svg => "svg",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+/// How [`LayoutDisplay::svg`] draws a layout.
+pub struct ImageOptions {
+    /// Width of the image in pixels. The height follows from the keyboard.
+    pub width: u32,
+    pub theme: KeymuiTheme,
+    /// Whether to add the layout's name and an explanation of the colors.
+    pub legend: bool,
+}
+
+fn parse_theme(s: &str) -> Option<KeymuiTheme> {
+    [
+        KeymuiTheme::Light,
+        KeymuiTheme::Dark,
+        KeymuiTheme::TokyoNight,
+        KeymuiTheme::CatppuccinMocha,
+    ]
+    .into_iter()
+    .find(|t| {
+        let s = s.to_lowercase();
+        t.to_string().to_lowercase().replace(' ', "-") == s
+            || format!("{:?}", t).to_lowercase() == s.replace('-', "")
+    })
+}
+
+fn parse_style(s: &str) -> Option<ColorStyle> {
+    ColorStyle::ALL
+        .into_iter()
+        .find(|style| style.to_string().eq_ignore_ascii_case(s))
+}
+
+/// Rasterizes an SVG with the system's fonts.
+pub fn png(svg: &str) -> Result<Vec<u8>> {
+    let mut options = usvg::Options::default();
+    options.fontdb_mut().load_system_fonts();
+    let tree = usvg::Tree::from_str(svg, &options).context("couldn't read the rendered svg")?;
+    let size = tree.size().to_int_size();
+    let mut pixmap =
+        tiny_skia::Pixmap::new(size.width(), size.height()).context("the image is empty")?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    pixmap.encode_png().context("couldn't encode the png")
+}
+
+/// The options given to `render-layout`. Anything left out falls back to
+/// the app's settings.
+#[derive(Debug, Default, PartialEq)]
+struct RenderArgs {
+    width: Option<u32>,
+    theme: Option<KeymuiTheme>,
+    style: Option<ColorStyle>,
+    /// Short name of the metric to color keys by.
+    metric: Option<String>,
+    legend: Option<bool>,
+    format: Option<ImageFormat>,
+    path: Option<String>,
+}
+
+impl RenderArgs {
+    fn parse(args: &str) -> Result<Self> {
+        let mut parsed = Self::default();
+        let mut path = vec![];
+        for token in args.split_whitespace() {
+            match token.split_once('=') {
+                Some(("width", v)) => {
+                    parsed.width = Some(
+                        v.parse()
+                            .ok()
+                            .filter(|w| (16..=10000).contains(w))
+                            .context("width must be between 16 and 10000 pixels")?,
+                    )
+                }
+                Some(("theme", v)) => {
+                    parsed.theme = Some(parse_theme(v).with_context(|| {
+                        format!(
+                            "unknown theme {}, use light, dark, tokyo-night or catppuccin-mocha",
+                            v
+                        )
+                    })?)
+                }
+                Some(("style", v)) => {
+                    parsed.style = Some(parse_style(v).with_context(|| {
+                        format!(
+                            "unknown style {}, use frequency, metric, fingers or none",
+                            v
+                        )
+                    })?)
+                }
+                Some(("metric", v)) => parsed.metric = Some(v.to_string()),
+                Some(("legend", v)) => {
+                    parsed.legend = Some(match v {
+                        "on" | "yes" | "true" => true,
+                        "off" | "no" | "false" => false,
+                        _ => return Err(anyhow!("legend must be on or off")),
+                    })
+                }
+                _ => match ImageFormat::parse(token) {
+                    Some(f) if path.is_empty() => parsed.format = Some(f),
+                    _ => path.push(token),
+                },
+            }
+        }
+        if !path.is_empty() {
+            parsed.path = Some(path.join(" "));
+        }
+        Ok(parsed)
+    }
+}
+
+impl Keymui {
+    /// Renders the current layout to an image and returns its path. `args`
+    /// are `key=value` options and an optional output path, whose
+    /// extension picks the format unless `svg` or `png` is given.
+    pub fn render_layout(&self, args: &str) -> Result<PathBuf> {
+        let ctx = self.metric_context.as_ref().context("no layout loaded")?;
+        let key = self.current_layout.as_ref().context("no layout selected")?;
+        let args = RenderArgs::parse(args)?;
+        let options = ImageOptions {
+            width: args.width.unwrap_or(800),
+            theme: args.theme.unwrap_or(self.config.theme),
+            legend: args.legend.unwrap_or(true),
+        };
+        let metric = match &args.metric {
+            Some(short) => ctx
+                .metrics
+                .iter()
+                .position(|m| &m.short == short)
+                .with_context(|| format!("metric {} doesn't exist", short))?,
+            None => self.nstrokes_metric,
+        };
+        // a metric colors the keys by it unless another style is given
+        let style = args
+            .style
+            .or(args.metric.as_ref().map(|_| ColorStyle::Metric));
+        let path = args.path.map(|p| self.expand_path(&p)).transpose()?;
+        let format = args
+            .format
+            .or_else(|| {
+                path.as_ref()
+                    .and_then(|p| p.extension())
+                    .and_then(|e| ImageFormat::parse(&e.to_string_lossy()))
+            })
+            .unwrap_or(ImageFormat::Png);
+        let path = match path {
+            Some(p) if p.is_dir() => p.join(format!("{}.{}", key, format.extension())),
+            Some(p) => p,
+            None => self
+                .exports_dir()
+                .join(format!("{}.{}", key, format.extension())),
+        };
+
+        let style = style
+            .or_else(|| self.layout_display.as_ref().map(|d| d.style))
+            .unwrap_or(ColorStyle::Frequency);
+        let mut display = LayoutDisplay::new(ctx, style, metric);
+        display.style = style;
+        let title = self
+            .layouts
+            .get(key)
+            .map_or(key.as_str(), |l| l.name.as_str());
+        let heatmap = match style {
+            ColorStyle::Metric => ctx.metrics[metric].name.as_str(),
+            _ => "key frequency",
+        };
+        let svg = display.svg(&options, title, heatmap);
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("couldn't create folder {}", dir.display()))?;
+        }
+        let bytes = match format {
+            ImageFormat::Svg => svg.into_bytes(),
+            ImageFormat::Png => png(&svg)?,
+        };
+        fs::write(&path, bytes).with_context(|| format!("couldn't write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_are_parsed_and_the_rest_is_the_path() {
+        let args = RenderArgs::parse(
+            "svg width=1200 theme=tokyo-night style=Fingers metric=sfb legend=off \
+             ~/my layouts/semimak.svg",
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            RenderArgs {
+                width: Some(1200),
+                theme: Some(KeymuiTheme::TokyoNight),
+                style: Some(ColorStyle::Fingers),
+                metric: Some("sfb".to_string()),
+                legend: Some(false),
+                format: Some(ImageFormat::Svg),
+                path: Some("~/my layouts/semimak.svg".to_string()),
+            }
+        );
+        assert_eq!(RenderArgs::parse("").unwrap(), RenderArgs::default());
+    }
+
+    #[test]
+    fn formats_only_count_before_the_path() {
+        let args = RenderArgs::parse("PNG").unwrap();
+        assert_eq!(args.format, Some(ImageFormat::Png));
+        assert_eq!(args.path, None);
+        let args = RenderArgs::parse("out svg").unwrap();
+        assert_eq!(args.format, None);
+        assert_eq!(args.path.as_deref(), Some("out svg"));
+    }
+
+    #[test]
+    fn themes_are_matched_by_name() {
+        for (name, theme) in [
+            ("light", KeymuiTheme::Light),
+            ("Dark", KeymuiTheme::Dark),
+            ("tokyo-night", KeymuiTheme::TokyoNight),
+            ("tokyonight", KeymuiTheme::TokyoNight),
+            ("catppuccin-mocha", KeymuiTheme::CatppuccinMocha),
+        ] {
+            assert_eq!(parse_theme(name), Some(theme), "{}", name);
+        }
+        assert_eq!(parse_theme("solarized"), None);
+    }
+
+    #[test]
+    fn invalid_options_are_rejected() {
+        for args in [
+            "width=8",
+            "width=20000",
+            "width=wide",
+            "theme=solarized",
+            "style=rainbow",
+            "legend=maybe",
+        ] {
+            assert!(RenderArgs::parse(args).is_err(), "{}", args);
+        }
+    }
+}
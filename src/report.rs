@@ -0,0 +1,377 @@
+use crate::export::ExportedLayout;
+use crate::Keymui;
+use color_eyre::eyre::{anyhow, Context, ContextCompat, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Markdown,
+    Csv,
+    Json,
+}
+
+impl ReportFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "md" | "markdown" => Some(ReportFormat::Markdown),
+            "csv" => Some(ReportFormat::Csv),
+            "json" => Some(ReportFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "md",
+            ReportFormat::Csv => "csv",
+            ReportFormat::Json => "json",
+        }
+    }
+}
+
+/// Everything the stats and nstrokes panes show for a layout, ready to be
+/// shared.
+#[derive(Serialize)]
+pub struct Report {
+    pub layout: String,
+    pub authors: Vec<String>,
+    pub metrics: String,
+    pub corpus: String,
+    /// The keys of every row of the keyboard, separated by spaces.
+    pub rows: Vec<String>,
+    pub stats: Vec<MetricStat>,
+    pub nstrokes: Vec<MetricNstrokes>,
+}
+
+#[derive(Serialize)]
+pub struct MetricStat {
+    pub name: String,
+    pub short: String,
+    /// The value in the metric's display style.
+    pub value: String,
+    pub percentage: f32,
+}
+
+#[derive(Serialize)]
+pub struct MetricNstrokes {
+    pub metric: String,
+    pub short: String,
+    pub nstrokes: Vec<NstrokeStat>,
+}
+
+#[derive(Serialize)]
+pub struct NstrokeStat {
+    pub nstroke: String,
+    pub frequency: f32,
+    pub amount: f32,
+}
+
+impl Report {
+    pub fn render(&self, format: ReportFormat) -> Result<String> {
+        Ok(match format {
+            ReportFormat::Markdown => self.markdown(),
+            ReportFormat::Csv => self.csv(),
+            ReportFormat::Json => {
+                serde_json::to_string_pretty(self).context("couldn't serialize the report")? + "\n"
+            }
+        })
+    }
+
+    fn markdown(&self) -> String {
+        let cell = |s: &str| s.replace('|', "\\|");
+        let mut lines = vec![format!("# {}", self.layout)];
+        if !self.authors.is_empty() {
+            lines.push(format!("by {}", self.authors.join(", ")));
+        }
+        lines.push(String::new());
+        lines.push(format!(
+            "metrics `{}`, corpus `{}`",
+            self.metrics, self.corpus
+        ));
+        lines.push(String::new());
+        lines.push("```".to_string());
+        lines.extend(self.rows.iter().cloned());
+        lines.push("```".to_string());
+        lines.push(String::new());
+        lines.push("| metric | value |".to_string());
+        lines.push("| --- | --- |".to_string());
+        for stat in &self.stats {
+            lines.push(format!("| {} | {} |", cell(&stat.name), cell(&stat.value)));
+        }
+        for metric in self.nstrokes.iter().filter(|m| !m.nstrokes.is_empty()) {
+            lines.push(String::new());
+            lines.push(format!("## {}", metric.metric));
+            lines.push(String::new());
+            lines.push("| nstroke | frequency | amount |".to_string());
+            lines.push("| --- | --- | --- |".to_string());
+            for n in &metric.nstrokes {
+                lines.push(format!(
+                    "| {} | {:.2}% | {:.3} |",
+                    code_cell(&n.nstroke),
+                    n.frequency,
+                    n.amount
+                ));
+            }
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// One table after another, each with its own header, separated by
+    /// empty lines.
+    fn csv(&self) -> String {
+        let mut lines = vec![
+            "layout,authors,metrics,corpus".to_string(),
+            [
+                &self.layout,
+                &self.authors.join(", "),
+                &self.metrics,
+                &self.corpus,
+            ]
+            .map(|s| csv_field(s))
+            .join(","),
+            String::new(),
+            "row,keys".to_string(),
+        ];
+        for (i, row) in self.rows.iter().enumerate() {
+            lines.push(format!("{},{}", i + 1, csv_field(row)));
+        }
+        lines.push(String::new());
+        lines.push("metric,short,value,percentage".to_string());
+        for stat in &self.stats {
+            lines.push(format!(
+                "{},{},{},{}",
+                csv_field(&stat.name),
+                csv_field(&stat.short),
+                csv_field(&stat.value),
+                stat.percentage
+            ));
+        }
+        lines.push(String::new());
+        lines.push("metric,rank,nstroke,frequency,amount".to_string());
+        for metric in &self.nstrokes {
+            for (i, n) in metric.nstrokes.iter().enumerate() {
+                lines.push(format!(
+                    "{},{},{},{},{}",
+                    csv_field(&metric.short),
+                    i + 1,
+                    csv_field(&n.nstroke),
+                    n.frequency,
+                    n.amount
+                ));
+            }
+        }
+        lines.join("\n") + "\n"
+    }
+}
+
+/// A Markdown code span for a table cell. The fence is one backtick
+/// longer than the longest run of backticks inside, and padding keeps
+/// backticks or spaces at the ends from being taken as part of the fence.
+fn code_cell(s: &str) -> String {
+    let longest = s.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest + 1);
+    let spaced = s.starts_with(' ') && s.ends_with(' ') && !s.trim().is_empty();
+    let pad = match s.starts_with('`') || s.ends_with('`') || spaced {
+        true => " ",
+        false => "",
+    };
+    format!("{}{}{}{}{}", fence, pad, s.replace('|', "\\|"), pad, fence)
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) || s.starts_with(' ') || s.ends_with(' ') {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+impl Keymui {
+    /// A report of the current layout, including unsaved changes, with
+    /// the top `count` nstrokes of every metric.
+    pub fn report(&self, count: usize) -> Result<Report> {
+        let ctx = self.metric_context.as_ref().context("no layout loaded")?;
+        let key = self.current_layout.as_ref().context("no layout selected")?;
+        let data = self.layouts.get(key);
+        let name = data.map_or(key.as_str(), |l| l.name.as_str());
+        let layout = ExportedLayout::new(name, ctx);
+        let rows = layout
+            .rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|c| match c {
+                        '\0' => '_',
+                        ' ' => '␣',
+                        _ => *c,
+                    })
+                    .map(String::from)
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            })
+            .collect();
+        let stats = ctx
+            .metrics
+            .iter()
+            .zip(Self::percentages(ctx, &self.layout_stats))
+            .map(|(m, percentage)| MetricStat {
+                name: m.name.clone(),
+                short: m.short.clone(),
+                value: self.format_stat(&m.short, percentage),
+                percentage,
+            })
+            .collect();
+        let nstrokes = ctx
+            .metrics
+            .iter()
+            .enumerate()
+            .map(|(i, m)| {
+                let mut list = Self::metric_nstrokes(ctx, i);
+                Self::sort_nstrokes(&mut list, self.nstroke_sort_method(&m.short));
+                MetricNstrokes {
+                    metric: m.name.clone(),
+                    short: m.short.clone(),
+                    nstrokes: list
+                        .into_iter()
+                        .take(count)
+                        .map(|(_, nstroke, frequency, amount)| NstrokeStat {
+                            nstroke,
+                            frequency,
+                            amount,
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        Ok(Report {
+            layout: name.to_string(),
+            authors: data.map(|l| l.authors.clone()).unwrap_or_default(),
+            metrics: self.current_metrics.clone().unwrap_or_default(),
+            corpus: self.current_corpus.clone().unwrap_or_default(),
+            rows,
+            stats,
+            nstrokes,
+        })
+    }
+
+    /// Writes a report of the current layout into `dir`, or the data
+    /// dir's `exports` folder, and returns its path.
+    pub fn export_report(
+        &self,
+        format: ReportFormat,
+        count: usize,
+        dir: Option<&Path>,
+    ) -> Result<PathBuf> {
+        let report = self.report(count)?;
+        let key = self.current_layout.as_ref().context("no layout selected")?;
+        let dir = dir.map_or_else(|| self.exports_dir(), Path::to_path_buf);
+        if dir.is_file() {
+            return Err(anyhow!("{} isn't a folder", dir.display()));
+        }
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("couldn't create folder {}", dir.display()))?;
+        let path = dir.join(format!("{}-report.{}", key, format.extension()));
+        fs::write(&path, report.render(format)?)
+            .with_context(|| format!("couldn't write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> Report {
+        Report {
+            layout: "Semimak".to_string(),
+            authors: vec!["semi".to_string(), "a friend".to_string()],
+            metrics: "ansi".to_string(),
+            corpus: "english".to_string(),
+            rows: vec!["f l h".to_string(), "␣".to_string()],
+            stats: vec![MetricStat {
+                name: "Same finger | bigrams".to_string(),
+                short: "sfb".to_string(),
+                value: "1/108".to_string(),
+                percentage: 0.5,
+            }],
+            nstrokes: vec![MetricNstrokes {
+                metric: "Same finger bigrams".to_string(),
+                short: "sfb".to_string(),
+                nstrokes: vec![NstrokeStat {
+                    nstroke: ",\"".to_string(),
+                    frequency: 0.25,
+                    amount: 1.0,
+                }],
+            }],
+        }
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a, b"), "\"a, b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field(" padded"), "\" padded\"");
+    }
+
+    #[test]
+    fn code_cells_fence_backticks_and_escape_pipes() {
+        assert_eq!(code_cell("th"), "`th`");
+        assert_eq!(code_cell("a|b"), "`a\\|b`");
+        assert_eq!(code_cell("a`b"), "``a`b``");
+        assert_eq!(code_cell("`"), "`` ` ``");
+        assert_eq!(code_cell("x``"), "``` x`` ```");
+        assert_eq!(code_cell(" a "), "`  a  `");
+        assert_eq!(code_cell("  "), "`  `");
+    }
+
+    #[test]
+    fn csv_report() {
+        assert_eq!(
+            report().render(ReportFormat::Csv).unwrap(),
+            "layout,authors,metrics,corpus
+Semimak,\"semi, a friend\",ansi,english
+
+row,keys
+1,f l h
+2,␣
+
+metric,short,value,percentage
+Same finger | bigrams,sfb,1/108,0.5
+
+metric,rank,nstroke,frequency,amount
+sfb,1,\",\"\"\",0.25,1
+"
+        );
+    }
+
+    #[test]
+    fn markdown_report_escapes_table_cells() {
+        let markdown = report().render(ReportFormat::Markdown).unwrap();
+        assert_eq!(
+            markdown,
+            "# Semimak
+by semi, a friend
+
+metrics `ansi`, corpus `english`
+
+```
+f l h
+␣
+```
+
+| metric | value |
+| --- | --- |
+| Same finger \\| bigrams | 1/108 |
+
+## Same finger bigrams
+
+| nstroke | frequency | amount |
+| --- | --- | --- |
+| `,\"` | 0.25% | 1.000 |
+"
+        );
+    }
+}
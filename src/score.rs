@@ -0,0 +1,150 @@
+use color_eyre::eyre::{anyhow, Report, Result};
+use core::fmt;
+use std::str::FromStr;
+
+/// A weighted sum of metric percentages, written like
+/// `-2*sfb + 0.5 roll - sr-roll`. Higher scores are better.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Score {
+    pub terms: Vec<(f32, String)>,
+}
+
+impl Score {
+    /// Combined weight of a metric, by its short name.
+    pub fn weight(&self, short: &str) -> f32 {
+        self.terms
+            .iter()
+            .filter(|(_, s)| s == short)
+            .map(|(w, _)| w)
+            .sum()
+    }
+
+    /// Replaces every term of a metric with a single one. A weight of zero
+    /// removes the metric from the score.
+    pub fn set_weight(&mut self, short: &str, weight: f32) {
+        let idx = self.terms.iter().position(|(_, s)| s == short);
+        self.terms.retain(|(_, s)| s != short);
+        if weight != 0.0 {
+            let idx = idx.unwrap_or(self.terms.len()).min(self.terms.len());
+            self.terms.insert(idx, (weight, short.to_string()));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Scores a list of metric percentages given as `(short, percentage)`.
+    pub fn evaluate<'a>(&self, percentages: impl Iterator<Item = (&'a str, f32)>) -> f32 {
+        percentages.map(|(short, p)| self.weight(short) * p).sum()
+    }
+}
+
+impl FromStr for Score {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut terms = vec![];
+        let mut sign = 1.0;
+        let mut coefficient: Option<f32> = None;
+        let spaced = s.replace('*', " * ");
+        for token in spaced.split_whitespace() {
+            let mut token = token;
+            match token {
+                "*" => continue,
+                "+" => continue,
+                "-" => {
+                    sign = -sign;
+                    continue;
+                }
+                _ => (),
+            }
+            if let Ok(n) = token.parse::<f32>() {
+                coefficient = Some(coefficient.unwrap_or(1.0) * n);
+                continue;
+            }
+            while let Some(rest) = token.strip_prefix('-') {
+                sign = -sign;
+                token = rest;
+            }
+            let token = token.trim_start_matches('+');
+            if token.is_empty() {
+                continue;
+            }
+            terms.push((sign * coefficient.unwrap_or(1.0), token.to_string()));
+            sign = 1.0;
+            coefficient = None;
+        }
+        if coefficient.is_some() {
+            return Err(anyhow!("coefficients must be followed by a metric name"));
+        }
+        Ok(Score { terms })
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (w, short)) in self.terms.iter().enumerate() {
+            match (i, *w < 0.0) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => (),
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+            write!(f, "{}*{}", w.abs(), short)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(score: &Score) -> Vec<(f32, &str)> {
+        score.terms.iter().map(|(w, s)| (*w, s.as_str())).collect()
+    }
+
+    #[test]
+    fn parses_signs_and_coefficients() {
+        let score: Score = "-2*sfb + 0.5 roll - sr-roll".parse().unwrap();
+        assert_eq!(
+            terms(&score),
+            [(-2.0, "sfb"), (0.5, "roll"), (-1.0, "sr-roll")]
+        );
+        let score: Score = "3 * -lsb - - alt".parse().unwrap();
+        assert_eq!(terms(&score), [(-3.0, "lsb"), (1.0, "alt")]);
+        assert!("".parse::<Score>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn dangling_coefficients_are_rejected() {
+        assert!("sfb + 2".parse::<Score>().is_err());
+    }
+
+    #[test]
+    fn round_trips_through_display() {
+        for s in ["-2*sfb + 0.5*roll - 1*sr-roll", "1*alt", ""] {
+            let score: Score = s.parse().unwrap();
+            assert_eq!(score.to_string(), s);
+            assert_eq!(score.to_string().parse::<Score>().unwrap(), score);
+        }
+    }
+
+    #[test]
+    fn weights_combine_per_metric() {
+        let mut score: Score = "-2*sfb + roll - sfb".parse().unwrap();
+        assert_eq!(score.weight("sfb"), -3.0);
+        assert_eq!(score.weight("alt"), 0.0);
+        assert_eq!(
+            score.evaluate([("sfb", 1.0), ("roll", 4.0)].into_iter()),
+            1.0
+        );
+
+        score.set_weight("sfb", -1.5);
+        assert_eq!(score.to_string(), "-1.5*sfb + 1*roll");
+        score.set_weight("alt", 2.0);
+        score.set_weight("sfb", 0.0);
+        assert_eq!(score.to_string(), "1*roll + 2*alt");
+    }
+}
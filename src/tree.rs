@@ -0,0 +1,580 @@
+use crate::Keymui;
+use color_eyre::eyre::{Context, ContextCompat, Result};
+use km::MetricContext;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Node {
+    pub name: Option<String>,
+    pub label: String,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    /// The child that redo moves to: the one most recently visited.
+    pub last_child: Option<usize>,
+    /// Key positions swapped, in order, to get from the parent to this node.
+    pub swaps: Vec<(usize, usize)>,
+    pub layout: Vec<char>,
+    pub stats: Vec<f32>,
+}
+
+impl Node {
+    pub fn title(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{} ({})", name, self.label),
+            None => self.label.clone(),
+        }
+    }
+}
+
+/// Every edit session of a layout as a tree of layouts. The root is the
+/// layout as it was loaded, and each swap or batch of swaps is a child of
+/// the node it was made from. Deleted nodes leave a hole so that ids stay
+/// stable.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LayoutTree {
+    nodes: Vec<Option<Node>>,
+    current: usize,
+    /// Metric data and corpus the cached stats were computed with.
+    pub source: (String, String),
+    /// The files of `source` as they were then, since they can change
+    /// under the same name.
+    #[serde(default)]
+    pub stamps: Option<(FileStamp, FileStamp)>,
+}
+
+/// Size and modification time of a data file.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    len: u64,
+    modified: (u64, u32),
+}
+
+impl FileStamp {
+    pub fn of(path: &Path) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            len: meta.len(),
+            modified: (modified.as_secs(), modified.subsec_nanos()),
+        })
+    }
+}
+
+pub fn swap_label(a: char, b: char) -> String {
+    let show = |c: char| match c {
+        ' ' => '␣',
+        '\0' => '�',
+        _ => c,
+    };
+    format!("{} ↔ {}", show(a), show(b))
+}
+
+impl LayoutTree {
+    pub fn new(layout: Vec<char>, stats: Vec<f32>, source: (String, String)) -> Self {
+        Self {
+            nodes: vec![Some(Node {
+                name: None,
+                label: "initial layout".to_string(),
+                parent: None,
+                children: vec![],
+                last_child: None,
+                swaps: vec![],
+                layout,
+                stats,
+            })],
+            current: 0,
+            source,
+            stamps: None,
+        }
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn node(&self, id: usize) -> Option<&Node> {
+        self.nodes.get(id)?.as_ref()
+    }
+
+    pub fn root(&self) -> &Node {
+        self.node(0).expect("the root is never deleted")
+    }
+
+    pub fn current_node(&self) -> &Node {
+        self.node(self.current)
+            .expect("the current node is never deleted")
+    }
+
+    pub fn nodes_mut(&mut self) -> impl Iterator<Item = &mut Node> {
+        self.nodes.iter_mut().flatten()
+    }
+
+    /// Adds a child to the current node and moves to it.
+    pub fn add_child(
+        &mut self,
+        label: String,
+        swaps: Vec<(usize, usize)>,
+        layout: Vec<char>,
+        stats: Vec<f32>,
+    ) -> usize {
+        let id = self.nodes.len();
+        self.nodes.push(Some(Node {
+            name: None,
+            label,
+            parent: Some(self.current),
+            children: vec![],
+            last_child: None,
+            swaps,
+            layout,
+            stats,
+        }));
+        if let Some(Some(parent)) = self.nodes.get_mut(self.current) {
+            parent.children.push(id);
+        }
+        self.set_current(id);
+        id
+    }
+
+    /// Moves to a node, remembering the way back down for redo.
+    pub fn set_current(&mut self, id: usize) {
+        if self.node(id).is_none() {
+            return;
+        }
+        self.current = id;
+        let mut child = id;
+        while let Some(parent) = self.node(child).and_then(|n| n.parent) {
+            if let Some(Some(node)) = self.nodes.get_mut(parent) {
+                node.last_child = Some(child);
+            }
+            child = parent;
+        }
+    }
+
+    pub fn parent(&self) -> Option<usize> {
+        self.current_node().parent
+    }
+
+    pub fn redo_target(&self) -> Option<usize> {
+        self.current_node().last_child
+    }
+
+    /// The nodes from the root to the current node, followed by the nodes
+    /// redo would move through.
+    pub fn path(&self) -> Vec<usize> {
+        let mut path = vec![self.current];
+        while let Some(parent) = self.node(path[0]).and_then(|n| n.parent) {
+            path.insert(0, parent);
+        }
+        let mut last = self.current;
+        while let Some(child) = self.node(last).and_then(|n| n.last_child) {
+            path.push(child);
+            last = child;
+        }
+        path
+    }
+
+    /// Every node with its depth, in depth-first order.
+    pub fn depth_first(&self) -> Vec<(usize, usize)> {
+        let mut out = vec![];
+        let mut stack = vec![(0, 0)];
+        while let Some((id, depth)) = stack.pop() {
+            if let Some(node) = self.node(id) {
+                out.push((id, depth));
+                stack.extend(node.children.iter().rev().map(|c| (*c, depth + 1)));
+            }
+        }
+        out
+    }
+
+    pub fn rename(&mut self, id: usize, name: Option<String>) {
+        if let Some(Some(node)) = self.nodes.get_mut(id) {
+            node.name = name;
+        }
+    }
+
+    /// Deletes a node and all of its descendants. If the current node was
+    /// among them, the deleted node's parent becomes current and `true` is
+    /// returned. The root can't be deleted.
+    pub fn delete(&mut self, id: usize) -> bool {
+        let Some(parent) = self.node(id).and_then(|n| n.parent) else {
+            return false;
+        };
+        if let Some(Some(node)) = self.nodes.get_mut(parent) {
+            node.children.retain(|c| *c != id);
+            if node.last_child == Some(id) {
+                node.last_child = None;
+            }
+        }
+        let mut moved = false;
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            if let Some(node) = self.nodes.get_mut(id).and_then(|n| n.take()) {
+                moved |= id == self.current;
+                stack.extend(node.children);
+            }
+        }
+        if moved {
+            self.set_current(parent);
+        }
+        moved
+    }
+}
+
+impl Keymui {
+    fn layout_chars(ctx: &MetricContext) -> Vec<char> {
+        ctx.layout
+            .0
+            .iter()
+            .map(|c| ctx.analyzer.corpus.uncorpus_unigram(*c))
+            .collect()
+    }
+
+    fn chars_to_layout(ctx: &MetricContext, chars: &[char]) -> Vec<usize> {
+        chars
+            .iter()
+            .map(|c| ctx.analyzer.corpus.corpus_char(*c))
+            .collect()
+    }
+
+    fn tree_path(&self, layout: &str) -> PathBuf {
+        self.data_dir()
+            .join("trees")
+            .join(format!("{}.json", layout))
+    }
+
+    pub fn read_tree(&self, layout: &str) -> Result<LayoutTree> {
+        let path = self.tree_path(layout);
+        let s = fs::read_to_string(&path)
+            .with_context(|| format!("couldn't read tree file {}", &path.display()))?;
+        serde_json::from_str(&s)
+            .with_context(|| format!("couldn't parse tree file {}", &path.display()))
+    }
+
+    pub fn save_current_tree(&self) -> Result<()> {
+        let layout = self.current_layout.as_ref().context("no layout selected")?;
+        let tree = self.trees.get(layout).context("layout has no tree")?;
+        let path = self.tree_path(layout);
+        fs::create_dir_all(path.parent().context("tree path has no parent")?)?;
+        fs::write(&path, serde_json::to_string(tree)?)
+            .with_context(|| format!("couldn't write tree file {}", &path.display()))
+    }
+
+    /// Saves the current tree, logging failures since editing keeps working
+    /// without it.
+    fn persist_tree(&mut self) {
+        if let Err(e) = self.save_current_tree() {
+            self.error("couldn't save the layout tree", &e);
+        }
+    }
+
+    pub fn current_tree(&self) -> Option<&LayoutTree> {
+        self.trees.get(self.current_layout.as_ref()?)
+    }
+
+    /// Sets up the tree of a freshly loaded layout and moves to its current
+    /// node. A tree whose root doesn't match the layout anymore is started
+    /// over, and cached stats from other or changed metrics or corpora are
+    /// recomputed.
+    pub fn restore_tree(&mut self, layout: &str) {
+        let (Some(metrics), Some(corpus)) = (&self.current_metrics, &self.current_corpus) else {
+            return;
+        };
+        let source = (metrics.clone(), corpus.clone());
+        let stamp = |path: Option<&PathBuf>| FileStamp::of(path?);
+        let stamps = stamp(self.metric_lists.get(metrics)).zip(stamp(self.corpora.get(corpus)));
+        let tree = self
+            .trees
+            .remove(layout)
+            .or_else(|| self.read_tree(layout).ok());
+        let Some(ctx) = &mut self.metric_context else {
+            return;
+        };
+
+        let root = Self::layout_chars(ctx);
+        let mut tree = match tree {
+            Some(tree) if tree.root().layout == root => tree,
+            _ => {
+                let mut tree = LayoutTree::new(root, self.layout_stats.clone(), source.clone());
+                tree.stamps = stamps;
+                tree
+            }
+        };
+        let fresh = tree.source == source
+            && stamps.is_some()
+            && tree.stamps == stamps
+            && tree.current_node().stats.len() == self.layout_stats.len();
+        if !fresh {
+            for node in tree.nodes_mut() {
+                ctx.layout.0 = Self::chars_to_layout(ctx, &node.layout);
+                node.stats = vec![0.0; self.layout_stats.len()];
+                ctx.analyzer.recalc_stats(&mut node.stats, &ctx.layout);
+            }
+            tree.source = source;
+            tree.stamps = stamps;
+        }
+
+        let node = tree.current_node();
+        ctx.layout.0 = Self::chars_to_layout(ctx, &node.layout);
+        self.layout_stats.clone_from(&node.stats);
+        self.trees.insert(layout.to_string(), tree);
+    }
+
+    /// Applies swaps and records them as a single new node of the current
+    /// layout's tree.
+    pub fn record_swaps(&mut self, label: String, swaps: Vec<(usize, usize)>) {
+        for (a, b) in &swaps {
+            self.swap_positions(*a, *b);
+        }
+        if let (Some(ctx), Some(layout)) = (&self.metric_context, &self.current_layout) {
+            if let Some(tree) = self.trees.get_mut(layout) {
+                tree.add_child(
+                    label,
+                    swaps,
+                    Self::layout_chars(ctx),
+                    self.layout_stats.clone(),
+                );
+            }
+        }
+        self.persist_tree();
+    }
+
+    pub fn record_swap(&mut self, a: usize, b: usize) {
+        if let Some(ctx) = &self.metric_context {
+            let corpus = &ctx.analyzer.corpus;
+            let label = swap_label(
+                corpus.uncorpus_unigram(ctx.layout.0[a]),
+                corpus.uncorpus_unigram(ctx.layout.0[b]),
+            );
+            self.record_swaps(label, vec![(a, b)]);
+        }
+    }
+
+    /// Turns the current layout into `target` through swaps that are
+    /// recorded as one node, so that the change can be undone at once.
+    pub fn apply_layout_as_swaps(&mut self, label: &str, target: &[usize]) {
+        let mut layout = match &self.metric_context {
+            Some(ctx) if ctx.layout.0.len() == target.len() => ctx.layout.0.clone(),
+            _ => return,
+        };
+        let mut swaps = vec![];
+        for i in 0..target.len() {
+            if layout[i] == target[i] {
+                continue;
+            }
+            if let Some(j) = (i + 1..target.len()).find(|j| layout[*j] == target[i]) {
+                layout.swap(i, j);
+                swaps.push((i, j));
+            }
+        }
+        if !swaps.is_empty() {
+            self.record_swaps(format!("{} ({} swaps)", label, swaps.len()), swaps);
+        }
+        self.refresh_layout_views();
+    }
+
+    /// Moves to the parent or the redo child of the current node by
+    /// applying the swaps between them.
+    fn step_tree(&mut self, forward: bool) -> bool {
+        if self.metric_context.is_none() {
+            return false;
+        }
+        let Some(tree) = self.current_tree() else {
+            return false;
+        };
+        let step = if forward {
+            tree.redo_target()
+                .and_then(|id| Some((id, tree.node(id)?.swaps.clone())))
+        } else {
+            tree.parent().map(|id| {
+                let swaps = tree.current_node().swaps.iter().rev().copied().collect();
+                (id, swaps)
+            })
+        };
+        let Some((target, swaps)) = step else {
+            return false;
+        };
+        for (a, b) in swaps {
+            self.swap_positions(a, b);
+        }
+        if let Some(layout) = &self.current_layout {
+            if let Some(tree) = self.trees.get_mut(layout) {
+                tree.set_current(target);
+            }
+        }
+        self.persist_tree();
+        true
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let stepped = self.step_tree(false);
+        if stepped {
+            self.refresh_layout_views();
+        }
+        stepped
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let stepped = self.step_tree(true);
+        if stepped {
+            self.refresh_layout_views();
+        }
+        stepped
+    }
+
+    /// Moves to any node of the current tree using its cached layout and
+    /// stats.
+    pub fn jump_to_node(&mut self, id: usize) {
+        let Some(layout) = &self.current_layout else {
+            return;
+        };
+        if let (Some(ctx), Some(tree)) = (&mut self.metric_context, self.trees.get_mut(layout)) {
+            if let Some(node) = tree.node(id) {
+                ctx.layout.0 = Self::chars_to_layout(ctx, &node.layout);
+                self.layout_stats.clone_from(&node.stats);
+                tree.set_current(id);
+            }
+        }
+        self.persist_tree();
+        self.refresh_layout_views();
+    }
+
+    pub fn delete_node(&mut self, id: usize) {
+        let moved = self
+            .current_layout
+            .as_ref()
+            .and_then(|l| self.trees.get_mut(l))
+            .map(|tree| tree.delete(id).then(|| tree.current()));
+        if self.tree_diff == Some(id) {
+            self.tree_diff = None;
+        }
+        match moved {
+            Some(Some(current)) => self.jump_to_node(current),
+            _ => self.persist_tree(),
+        }
+    }
+
+    pub fn rename_node(&mut self, name: String) {
+        if let Some(tree) = self
+            .current_layout
+            .as_ref()
+            .and_then(|l| self.trees.get_mut(l))
+        {
+            let current = tree.current();
+            tree.rename(current, (!name.is_empty()).then_some(name));
+        }
+        self.persist_tree();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source() -> (String, String) {
+        ("ansi".to_string(), "english".to_string())
+    }
+
+    fn child(tree: &mut LayoutTree, label: &str) -> usize {
+        tree.add_child(label.to_string(), vec![(0, 1)], vec!['b', 'a'], vec![1.0])
+    }
+
+    /// 0 ─ 1 ┬ 2
+    ///       └ 3 (current)
+    fn tree() -> LayoutTree {
+        let mut tree = LayoutTree::new(vec!['a', 'b'], vec![0.0], source());
+        child(&mut tree, "one");
+        child(&mut tree, "two");
+        tree.set_current(1);
+        child(&mut tree, "three");
+        tree
+    }
+
+    #[test]
+    fn children_become_current() {
+        let tree = tree();
+        assert_eq!(tree.current(), 3);
+        assert_eq!(tree.current_node().label, "three");
+        assert_eq!(tree.parent(), Some(1));
+        assert_eq!(tree.node(1).unwrap().children, [2, 3]);
+        assert_eq!(tree.root().layout, ['a', 'b']);
+    }
+
+    #[test]
+    fn redo_follows_the_last_visited_child() {
+        let mut tree = tree();
+        tree.set_current(0);
+        assert_eq!(tree.redo_target(), Some(1));
+        assert_eq!(tree.path(), [0, 1, 3]);
+
+        tree.set_current(2);
+        tree.set_current(0);
+        assert_eq!(tree.path(), [0, 1, 2]);
+        tree.set_current(1);
+        assert_eq!(tree.redo_target(), Some(2));
+        assert_eq!(tree.path(), [0, 1, 2]);
+
+        // unknown nodes are ignored
+        tree.set_current(9);
+        assert_eq!(tree.current(), 1);
+    }
+
+    #[test]
+    fn depth_first_lists_children_in_order() {
+        assert_eq!(tree().depth_first(), [(0, 0), (1, 1), (2, 2), (3, 2)]);
+    }
+
+    #[test]
+    fn delete_removes_descendants() {
+        let mut tree = tree();
+        assert!(!tree.delete(0));
+        assert!(!tree.delete(2));
+        assert_eq!(tree.current(), 3);
+        assert_eq!(tree.depth_first(), [(0, 0), (1, 1), (3, 2)]);
+
+        assert!(tree.delete(1));
+        assert_eq!(tree.current(), 0);
+        assert!(tree.node(3).is_none());
+        assert_eq!(tree.redo_target(), None);
+        assert_eq!(tree.depth_first(), [(0, 0)]);
+
+        // ids of deleted nodes aren't reused
+        assert_eq!(child(&mut tree, "four"), 4);
+    }
+
+    #[test]
+    fn trees_round_trip_through_json() {
+        let mut tree = tree();
+        tree.rename(2, Some("best".to_string()));
+        let json = serde_json::to_string(&tree).unwrap();
+        let read: LayoutTree = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.current(), 3);
+        assert_eq!(read.path(), tree.path());
+        assert_eq!(read.depth_first(), tree.depth_first());
+        assert_eq!(read.node(2).unwrap().title(), "best (two)");
+        assert_eq!(read.source, source());
+        assert_eq!(read.stamps, None);
+    }
+
+    #[test]
+    fn trees_without_stamps_still_load() {
+        let mut json: serde_json::Value = serde_json::to_value(tree()).unwrap();
+        json.as_object_mut().unwrap().remove("stamps");
+        let read: LayoutTree = serde_json::from_value(json).unwrap();
+        assert_eq!(read.stamps, None);
+        assert_eq!(read.depth_first().len(), 4);
+    }
+
+    #[test]
+    fn stamps_change_with_the_file() {
+        let path = std::env::temp_dir().join(format!("keymui-stamp-{}", std::process::id()));
+        assert_eq!(FileStamp::of(&path), None);
+        fs::write(&path, "a").unwrap();
+        let before = FileStamp::of(&path).unwrap();
+        fs::write(&path, "ab").unwrap();
+        assert_ne!(FileStamp::of(&path), Some(before));
+        fs::remove_file(&path).unwrap();
+    }
+}
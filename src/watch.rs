@@ -0,0 +1,285 @@
+use crate::log::{report_chain, Level};
+use crate::{Keymui, Message};
+use color_eyre::eyre::Report;
+use iced::futures::channel::mpsc as async_mpsc;
+use iced::futures::{future, SinkExt, StreamExt};
+use iced::{subscription, Subscription};
+use notify::{RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// How long to wait for a burst of changes, like a file being written in
+/// several steps, to end before reloading.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Watched {
+    Layouts,
+    Metrics,
+    Corpora,
+}
+
+/// Watches directories and reports changed files in batches through
+/// [`Message::FilesChanged`], or sends a [`Message::WatchFailed`] when
+/// watching doesn't work. The watcher restarts whenever the set of
+/// directories changes.
+pub fn subscription(dirs: Vec<(Watched, PathBuf)>) -> Subscription<Message> {
+    subscription::channel(dirs.clone(), 16, |mut output| async move {
+        let (sender, receiver) = mpsc::channel();
+        let mut errors = vec![];
+        let watcher = match notify::recommended_watcher(sender) {
+            Ok(mut watcher) => {
+                // missing folders are normal before the first fetch
+                for (_, dir) in dirs.iter().filter(|(_, dir)| dir.exists()) {
+                    if let Err(e) = watcher.watch(dir, RecursiveMode::Recursive) {
+                        errors.push(format!("{}\n    {}", dir.display(), e));
+                    }
+                }
+                Some(watcher)
+            }
+            Err(e) => {
+                errors.push(report_chain(&Report::new(e)));
+                None
+            }
+        };
+        if !errors.is_empty() {
+            let _ = output.send(Message::WatchFailed(errors.join("\n\n"))).await;
+        }
+        let Some(watcher) = watcher else {
+            loop {
+                future::pending::<()>().await;
+            }
+        };
+
+        let (batch_sender, mut batches) = async_mpsc::unbounded();
+        thread::spawn(move || {
+            while let Ok(event) = receiver.recv() {
+                let mut changed = vec![];
+                let mut event = Some(event);
+                while let Some(e) = event {
+                    if let Ok(e) = e {
+                        if !e.kind.is_access() {
+                            changed.extend(e.paths);
+                        }
+                    }
+                    event = receiver.recv_timeout(DEBOUNCE).ok();
+                }
+                let changed: Vec<(Watched, PathBuf)> = changed
+                    .into_iter()
+                    .filter_map(|path| Some((classify(&dirs, &path)?, path)))
+                    .collect();
+                if !changed.is_empty() && batch_sender.unbounded_send(changed).is_err() {
+                    return;
+                }
+            }
+        });
+
+        // keeps the watcher alive for as long as the subscription runs
+        let _watcher = watcher;
+        loop {
+            match batches.next().await {
+                Some(changed) => {
+                    let _ = output.send(Message::FilesChanged(changed)).await;
+                }
+                None => future::pending::<()>().await,
+            }
+        }
+    })
+}
+
+/// The kind of the first watched directory that contains `path`.
+fn classify(dirs: &[(Watched, PathBuf)], path: &Path) -> Option<Watched> {
+    let (kind, _) = dirs.iter().find(|(_, dir)| path.starts_with(dir))?;
+    Some(*kind)
+}
+
+/// Names, without extension, of the changed files of one kind.
+fn changed_names(changed: &[(Watched, PathBuf)], kind: Watched) -> Vec<String> {
+    changed
+        .iter()
+        .filter(|(k, _)| *k == kind)
+        .filter_map(|(_, path)| Some(path.file_stem()?.to_string_lossy().to_string()))
+        .collect()
+}
+
+fn watch_list(
+    layout_dirs: Vec<PathBuf>,
+    corpora_dir: PathBuf,
+    metrics_dir: Option<PathBuf>,
+) -> Vec<(Watched, PathBuf)> {
+    let mut dirs: Vec<(Watched, PathBuf)> = layout_dirs
+        .into_iter()
+        .map(|dir| (Watched::Layouts, dir))
+        .collect();
+    dirs.push((Watched::Corpora, corpora_dir));
+    if let Some(dir) = metrics_dir {
+        dirs.push((Watched::Metrics, dir));
+    }
+    dirs
+}
+
+impl Keymui {
+    pub fn watched_dirs(&self) -> Vec<(Watched, PathBuf)> {
+        watch_list(
+            self.layout_dirs(),
+            self.corpora_dir(),
+            self.config.metrics_directory.clone(),
+        )
+    }
+
+    /// Reloads what changed on disk. The analysis is only redone when the
+    /// selected layout, metrics or corpus changed, and the comparison only
+    /// when the metrics, corpus or a compared layout changed, so unrelated
+    /// changes keep the current state as is.
+    pub fn files_changed(&mut self, changed: Vec<(Watched, PathBuf)>) {
+        let mut reloaded = vec![];
+        let mut errors = vec![];
+        let mut reanalyze = false;
+        let mut data_changed = false;
+
+        let layouts = changed_names(&changed, Watched::Layouts);
+        if !layouts.is_empty() {
+            let current_data = |keymui: &Self| {
+                let data = keymui.layouts.get(keymui.current_layout.as_ref()?)?;
+                serde_json::to_string(data).ok()
+            };
+            let before = current_data(self);
+            if let Err(e) = self.load_layouts() {
+                errors.push(report_chain(&e));
+            }
+            reanalyze |= current_data(self) != before;
+            reloaded.push("layouts");
+        }
+
+        let metrics = changed_names(&changed, Watched::Metrics);
+        if !metrics.is_empty() {
+            if let Err(e) = self.import_metrics() {
+                errors.push(report_chain(&e));
+            }
+            if let Err(e) = self.set_metric_list() {
+                errors.push(report_chain(&e));
+            }
+            data_changed |= self
+                .current_metrics
+                .as_ref()
+                .is_some_and(|m| metrics.contains(m));
+            reloaded.push("metrics");
+        }
+
+        let corpora = changed_names(&changed, Watched::Corpora);
+        if !corpora.is_empty() {
+            if let Err(e) = self.set_corpus_list() {
+                errors.push(report_chain(&e));
+            }
+            if self
+                .current_corpus
+                .as_ref()
+                .is_some_and(|c| corpora.contains(c))
+            {
+                self.corpus_stats.take();
+                data_changed = true;
+            }
+            reloaded.push("corpora");
+        }
+
+        if reanalyze || data_changed {
+            if let Err(e) = self.load_data() {
+                errors.push(report_chain(&e));
+            }
+        }
+        if data_changed || self.comparison.iter().any(|c| layouts.contains(&c.name)) {
+            self.refresh_comparison();
+        } else if !layouts.is_empty() {
+            // the browser's analyses of the changed layouts are outdated
+            self.browser.invalidate();
+        }
+        errors.extend(changed.iter().filter_map(|(_, path)| {
+            let problem = self.problems.get(path)?;
+            Some(format!("{}\n    {}", path.display(), problem))
+        }));
+        let level = match errors.is_empty() {
+            true => Level::Info,
+            false => Level::Warning,
+        };
+        self.log_entry(
+            level,
+            format!("reloaded {}", reloaded.join(", ")),
+            (!errors.is_empty()).then(|| errors.join("\n\n")),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dirs() -> Vec<(Watched, PathBuf)> {
+        watch_list(
+            vec![
+                PathBuf::from("/data/layouts"),
+                PathBuf::from("/home/me/layouts"),
+            ],
+            PathBuf::from("/data/corpora"),
+            Some(PathBuf::from("/home/me/metrics")),
+        )
+    }
+
+    #[test]
+    fn every_directory_is_watched_once() {
+        assert_eq!(
+            dirs(),
+            [
+                (Watched::Layouts, PathBuf::from("/data/layouts")),
+                (Watched::Layouts, PathBuf::from("/home/me/layouts")),
+                (Watched::Corpora, PathBuf::from("/data/corpora")),
+                (Watched::Metrics, PathBuf::from("/home/me/metrics")),
+            ]
+        );
+        let without_metrics = watch_list(vec![], PathBuf::from("/data/corpora"), None);
+        assert_eq!(
+            without_metrics,
+            [(Watched::Corpora, PathBuf::from("/data/corpora"))]
+        );
+    }
+
+    #[test]
+    fn paths_take_the_kind_of_their_directory() {
+        let dirs = dirs();
+        let kind = |path: &str| classify(&dirs, Path::new(path));
+        assert_eq!(kind("/data/layouts/semimak.json"), Some(Watched::Layouts));
+        assert_eq!(
+            kind("/home/me/layouts/sub/qwerty.json"),
+            Some(Watched::Layouts)
+        );
+        assert_eq!(kind("/data/corpora/english.corpus"), Some(Watched::Corpora));
+        assert_eq!(kind("/home/me/metrics/ansi.json"), Some(Watched::Metrics));
+        assert_eq!(kind("/data/layouts-old/semimak.json"), None);
+        assert_eq!(kind("/tmp/semimak.json"), None);
+    }
+
+    #[test]
+    fn changed_names_drop_the_extension() {
+        let changed = vec![
+            (
+                Watched::Layouts,
+                PathBuf::from("/data/layouts/semimak.json"),
+            ),
+            (
+                Watched::Corpora,
+                PathBuf::from("/data/corpora/english.corpus"),
+            ),
+            (
+                Watched::Layouts,
+                PathBuf::from("/data/layouts/sub/qwerty.json"),
+            ),
+        ];
+        assert_eq!(
+            changed_names(&changed, Watched::Layouts),
+            ["semimak", "qwerty"]
+        );
+        assert_eq!(changed_names(&changed, Watched::Corpora), ["english"]);
+        assert!(changed_names(&changed, Watched::Metrics).is_empty());
+    }
+}
@@ -0,0 +1,17 @@
+// Small, exported from keymui
+// use the LAYOUT macro of your keyboard
+// UC() needs UNICODE_ENABLE = yes in rules.mk
+[0] = LAYOUT(
+    KC_F, KC_L, KC_H, S(KC_Q),
+    KC_S, KC_R, KC_NO, S(KC_1),
+    KC_COMM, UC(0x00DF), UC(0x00E9), KC_SCLN,
+    KC_SPC
+),
+
+// combos need COMBO_ENABLE = yes in rules.mk
+const uint16_t PROGMEM combo0[] = {KC_F, KC_L, COMBO_END};
+const uint16_t PROGMEM combo1[] = {KC_S, KC_R, COMBO_END};
+combo_t key_combos[] = {
+    COMBO(combo0, KC_SLSH),
+    COMBO(combo1, UC(0x00E9)),
+};